@@ -25,15 +25,21 @@ class ProviderApi {
       {required String reviewId,
       required String filePath,
       required String revision,
+      String? cancellationToken,
       dynamic hint}) {
     return api.getReviewFile(
         providerId: providerId,
         reviewId: reviewId,
         filePath: filePath,
         revision: revision,
+        cancellationToken: cancellationToken,
         hint: hint);
   }
 
+  Future<void> cancelRequest({required String token, dynamic hint}) {
+    return api.cancelRequest(token: token, hint: hint);
+  }
+
   Future<void> markFileRead(
       {required String reviewId,
       required String filePath,
@@ -48,11 +54,79 @@ class ProviderApi {
         read: read,
         hint: hint);
   }
+
+  Future<void> submitReviewVerdict(
+      {required String reviewId,
+      required ReviewVerdict verdict,
+      String? body,
+      dynamic hint}) {
+    return api.submitReviewVerdict(
+        providerId: providerId,
+        reviewId: reviewId,
+        verdict: verdict,
+        body: body,
+        hint: hint);
+  }
+
+  Future<void> setDiscussionResolved(
+      {required String reviewId,
+      required String discussionId,
+      required bool resolved,
+      dynamic hint}) {
+    return api.setDiscussionResolved(
+        providerId: providerId,
+        reviewId: reviewId,
+        discussionId: discussionId,
+        resolved: resolved,
+        hint: hint);
+  }
+
+  Future<void> createFileDiscussion(
+      {required String reviewId,
+      required String filePath,
+      required String revision,
+      required int line,
+      required String text,
+      dynamic hint}) {
+    return api.createFileDiscussion(
+        providerId: providerId,
+        reviewId: reviewId,
+        filePath: filePath,
+        revision: revision,
+        line: line,
+        text: text,
+        hint: hint);
+  }
+
+  Future<void> createReviewDiscussion(
+      {required String reviewId, required String text, dynamic hint}) {
+    return api.createReviewDiscussion(
+        providerId: providerId, reviewId: reviewId, text: text, hint: hint);
+  }
+
+  Future<void> replyToDiscussion(
+      {required String reviewId,
+      required String discussionId,
+      required String text,
+      dynamic hint}) {
+    return api.replyToDiscussion(
+        providerId: providerId,
+        reviewId: reviewId,
+        discussionId: discussionId,
+        text: text,
+        hint: hint);
+  }
 }
 
 extension ProviderSettingsExtension on ProviderSettings {
   String get title {
     return module.map(
-        upsource: (_) => "Upsource", github: (_) => "Github", gitlab: (_) => "Gitlab");
+        upsource: (_) => "Upsource",
+        github: (_) => "Github",
+        gitlab: (_) => "Gitlab",
+        gitea: (_) => "Gitea",
+        bitbucket: (_) => "Bitbucket",
+        gerrit: (_) => "Gerrit",
+        mock: (_) => "Mock");
   }
 }
@@ -1,19 +1,52 @@
 import 'package:flutter/material.dart';
+import 'package:path_provider/path_provider.dart';
 
 import 'api.dart';
 import 'ffi.dart';
 import 'service_binder.dart';
 import 'settings.dart';
 import 'ui/nav_bar.dart';
+import 'ui/unlock.dart';
 import 'ui/views/review/review.dart';
 import 'ui/views/review_list/review_list_screen.dart';
 import 'ui/views/settings/settings.dart';
 
 void main() async {
-  Settings settings = await Settings.load();
-  await api.configureModules(modules: settings.providers);
+  WidgetsFlutterBinding.ensureInitialized();
+  final supportDirectory = await getApplicationSupportDirectory();
 
-  runApp(ReviewTool(settings));
+  // the provider tokens are encrypted, so the settings are only loaded after unlocking them
+  runApp(UnlockApp(
+    secretsPath: '${supportDirectory.path}/secrets.json',
+    onUnlock: () async {
+      Settings settings = await Settings.load();
+      await api.configureModules(modules: settings.providers);
+      await api.openReadStateStore(path: '${supportDirectory.path}/read_state.db');
+      await api.openOfflineStore(path: '${supportDirectory.path}/offline.db');
+
+      runApp(ReviewTool(settings));
+    },
+  ));
+}
+
+class UnlockApp extends StatelessWidget {
+  final String secretsPath;
+  final Function() onUnlock;
+
+  const UnlockApp({required this.secretsPath, required this.onUnlock, Key? key}) : super(key: key);
+
+  @override
+  Widget build(BuildContext context) {
+    return MaterialApp(
+      title: 'Review Tool',
+      theme: ThemeData(
+        visualDensity: VisualDensity.compact,
+        primarySwatch: Colors.blue,
+        brightness: Brightness.dark,
+      ),
+      home: Scaffold(body: UnlockScreen(secretsPath: secretsPath, onUnlock: onUnlock)),
+    );
+  }
 }
 
 class ReviewTool extends StatelessWidget {
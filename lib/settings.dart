@@ -1,5 +1,6 @@
 import 'package:review_tool/api.dart';
 import 'package:review_tool/ffi.dart';
+import 'package:path_provider/path_provider.dart';
 import 'package:shared_preferences/shared_preferences.dart';
 import 'package:uuid/uuid.dart';
 
@@ -7,12 +8,18 @@ const String defaultTheme = "Darcula";
 
 class Settings {
   final SharedPreferences _prefs;
+  final String _providersPath;
+  List<ProviderSettings> _providers;
 
-  Settings(this._prefs);
+  Settings(this._prefs, this._providersPath, this._providers);
 
   static Future<Settings> load() async {
     var prefs = await SharedPreferences.getInstance();
-    var settings = Settings(prefs);
+    var supportDirectory = await getApplicationSupportDirectory();
+    var providersPath = '${supportDirectory.path}/providers.json';
+    var providers = await api.loadProviderSettings(path: providersPath);
+    var settings = Settings(prefs, providersPath, providers);
+    await settings._importLegacyProviders();
 
     return settings;
   }
@@ -21,46 +28,41 @@ class Settings {
     return _prefs.getString("theme") ?? defaultTheme;
   }
 
+  List<ProviderSettings> get providers => _providers;
+
   Future<void> addProvider(ProviderSettings settings) async {
     var id = const Uuid().v4();
-    var type = settings.module.map(
-        upsource: (_) => ProviderType.upsource,
-        github: (_) => ProviderType.github,
-        gitlab: (_) => ProviderType.gitlab);
-    var providers = _prefs.getStringList("providers") ?? [];
-    providers.add(id);
-    await _prefs.setInt("$id.type", type.index);
-    await _setProvider(id, settings);
-    await _prefs.setStringList("providers", providers);
-    await _reconfigureProviders();
+    var provider = ProviderSettings(
+        id: id, name: settings.name, module: settings.module, connection: settings.connection);
+    await _saveProviders([..._providers, provider]);
   }
 
   Future<void> editProvider(String id, ProviderSettings settings) async {
-    await _setProvider(id, settings);
-    await _reconfigureProviders();
+    await _saveProviders(_providers.map((provider) => provider.id == id ? settings : provider).toList());
   }
 
-  Future<void> _setProvider(String id, ProviderSettings settings) async {
-    await _prefs.setString("$id.name", settings.name);
-    await settings.module.when(upsource: (upsource) async {
-      await _prefs.setString("$id.url", upsource.url);
-      await _prefs.setString("$id.token", upsource.token);
-    }, github: (github) async {
-      await _prefs.setString("$id.token", github.token);
-      await _prefs.setString("$id.query", github.query);
-    }, gitlab: (gitlab) async {
-      await _prefs.setString("$id.url", gitlab.url);
-      await _prefs.setString("$id.token", gitlab.token);
-    });
+  Future<void> _saveProviders(List<ProviderSettings> providers) async {
+    await api.saveProviderSettings(path: _providersPath, providers: providers);
+    _providers = providers;
+    await api.configureModules(modules: providers);
   }
 
-  List<ProviderSettings> get providers {
-    var providers = _prefs.getStringList("providers") ?? [];
-
-    return providers.map((e) => _getProvider(e)).toList();
+  /// Providers used to be stored in the shared preferences, they are moved to the native settings file once.
+  Future<void> _importLegacyProviders() async {
+    var ids = _prefs.getStringList("providers");
+    if (ids == null) {
+      return;
+    }
+    var legacyProviders = ids.map(_getLegacyProvider).toList();
+    await api.saveProviderSettings(path: _providersPath, providers: [..._providers, ...legacyProviders]);
+    _providers = [..._providers, ...legacyProviders];
+    for (var key in _prefs.getKeys().where((key) => ids.any((id) => key.startsWith("$id.")))) {
+      await _prefs.remove(key);
+    }
+    await _prefs.remove("providers");
   }
 
-  ProviderSettings _getProvider(String id) {
+  ProviderSettings _getLegacyProvider(String id) {
     int typeIndex = _prefs.getInt("$id.type")!;
     String name = _prefs.getString("$id.name")!;
     ProviderType type = ProviderType.values[typeIndex];
@@ -68,50 +70,36 @@ class Settings {
 
     switch (type) {
       case ProviderType.upsource:
-        module = ProviderModule.upsource(_getUpsourceProvider(id));
+        module = ProviderModule.upsource(UpsourceProviderSettings(
+          url: _prefs.getString("$id.url")!,
+          token: _prefs.getString("$id.token")!,
+        ));
         break;
       case ProviderType.github:
-        module = ProviderModule.github(_getGithubProvider(id));
+        module = ProviderModule.github(GithubProviderSettings(
+          token: _prefs.getString("$id.token")!,
+          query: _prefs.getString("$id.query")!,
+        ));
         break;
       case ProviderType.gitlab:
-        module = ProviderModule.gitlab(_getGitlabProvider(id));
+        module = ProviderModule.gitlab(GitlabProviderSettings(
+          url: _prefs.getString("$id.url")!,
+          token: _prefs.getString("$id.token")!,
+        ));
+        break;
+      case ProviderType.mock:
+        module = ProviderModule.mock(MockProviderSettings(path: _prefs.getString("$id.path")!));
         break;
     }
 
-    return ProviderSettings(id: id, name: name, module: module);
-  }
-
-  UpsourceProviderSettings _getUpsourceProvider(String id) {
-    String url = _prefs.getString("$id.url")!;
-    String token = _prefs.getString("$id.token")!;
-
-    return UpsourceProviderSettings(
-      url: url,
-      token: token,
-    );
-  }
-
-  GithubProviderSettings _getGithubProvider(String key) {
-    String token = _prefs.getString("$key.token")!;
-    String query = _prefs.getString("$key.query")!;
-
-    return GithubProviderSettings(token: token, query: query);
-  }
-
-  GitlabProviderSettings _getGitlabProvider(String key) {
-    String url = _prefs.getString("$key.url")!;
-    String token = _prefs.getString("$key.token")!;
-
-    return GitlabProviderSettings(token: token, url: url);
-  }
-
-  Future<void> _reconfigureProviders() async {
-    await api.configureModules(modules: providers);
+    return ProviderSettings(id: id, name: name, module: module, connection: null);
   }
 }
 
+/// Types of the providers stored in the shared preferences, only used to import them.
 enum ProviderType {
   upsource,
   github,
   gitlab,
+  mock,
 }
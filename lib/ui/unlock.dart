@@ -0,0 +1,53 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/ffi.dart';
+
+/// Asks for the passphrase the provider tokens are encrypted with.
+class UnlockScreen extends StatefulWidget {
+  final String secretsPath;
+  final Function() onUnlock;
+
+  const UnlockScreen({required this.secretsPath, required this.onUnlock, Key? key}) : super(key: key);
+
+  @override
+  State<UnlockScreen> createState() => _UnlockScreenState();
+}
+
+class _UnlockScreenState extends State<UnlockScreen> {
+  final TextEditingController _passphraseController = TextEditingController();
+  String? _error;
+  bool _unlocking = false;
+
+  Future<void> _unlock() async {
+    setState(() => _unlocking = true);
+    try {
+      await api.unlockSecrets(path: widget.secretsPath, passphrase: _passphraseController.text);
+      widget.onUnlock();
+    } catch (err) {
+      setState(() {
+        _error = err.toString();
+        _unlocking = false;
+      });
+    }
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Center(
+      child: SizedBox(
+        width: 320,
+        child: Column(mainAxisSize: MainAxisSize.min, children: [
+          TextFormField(
+            decoration: InputDecoration(labelText: "Passphrase", errorText: _error),
+            obscureText: true,
+            autofocus: true,
+            enabled: !_unlocking,
+            controller: _passphraseController,
+            onFieldSubmitted: (_) => _unlock(),
+          ),
+          const SizedBox(height: 16),
+          ElevatedButton(onPressed: _unlocking ? null : _unlock, child: const Text("Unlock")),
+        ]),
+      ),
+    );
+  }
+}
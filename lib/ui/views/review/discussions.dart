@@ -45,6 +45,9 @@ class ReviewDiscussionView extends StatelessWidget {
                     discussion.file!.revision!,
                   )),
           const Padding(padding: EdgeInsets.all(2)),
+          if (discussion.file!.lines != null)
+            Text(_formatLines(discussion.file!), style: textTheme.bodySmall),
+          const Padding(padding: EdgeInsets.all(2)),
           if (discussion.file!.revision != null)
             Text("revision ${discussion.file!.revision!.substring(0, 7)}",
                 style: textTheme.bodySmall)
@@ -56,6 +59,15 @@ class ReviewDiscussionView extends StatelessWidget {
                 ))
             .toList());
   }
+
+  String _formatLines(ReviewFileDiscussion file) {
+    var lines = file.lines!;
+    var prefix = file.side == DiffSide.Old ? "old " : "";
+    if (lines.start == lines.end) {
+      return "${prefix}line ${lines.start}";
+    }
+    return "${prefix}lines ${lines.start}-${lines.end}";
+  }
 }
 
 class DiscussionComment extends StatelessWidget {
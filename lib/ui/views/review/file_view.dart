@@ -7,6 +7,7 @@ import 'package:flutter_highlight/themes/darcula.dart';
 import 'package:review_tool/api.dart';
 import 'package:review_tool/ui/widgets/file_type_icon.dart';
 import 'package:review_tool/ui/widgets/scroll_container.dart';
+import 'package:uuid/uuid.dart';
 
 import 'change_type.dart';
 
@@ -56,25 +57,65 @@ class ReviewFileView extends StatelessWidget {
   }
 }
 
-class FileContent extends StatelessWidget {
+class FileContent extends StatefulWidget {
   final Review review;
   final ReviewFileSummary file;
 
   const FileContent(this.review, this.file, {Key? key}) : super(key: key);
 
+  @override
+  State<FileContent> createState() => _FileContentState();
+}
+
+class _FileContentState extends State<FileContent> {
+  late ProviderApi _api;
+  late String _cancellationToken;
+  late Future<ReviewFileChanges> _changes;
+
+  @override
+  void initState() {
+    super.initState();
+    _api = context.read();
+    _load();
+  }
+
+  @override
+  void didUpdateWidget(FileContent oldWidget) {
+    super.didUpdateWidget(oldWidget);
+    if (oldWidget.file.filePath != widget.file.filePath ||
+        oldWidget.file.revisionId != widget.file.revisionId) {
+      _api.cancelRequest(token: _cancellationToken);
+      _load();
+    }
+  }
+
+  @override
+  void dispose() {
+    // the diff of a file which isn't shown anymore isn't needed
+    _api.cancelRequest(token: _cancellationToken);
+    super.dispose();
+  }
+
+  void _load() {
+    _cancellationToken = const Uuid().v4();
+    _changes = _api.getReviewFile(
+        reviewId: widget.review.id,
+        filePath: widget.file.filePath,
+        revision: widget.file.revisionId,
+        cancellationToken: _cancellationToken);
+  }
+
   @override
   Widget build(BuildContext context) {
-    ProviderApi api = context.read();
     return FutureBuilder<ReviewFileChanges>(
-      future: api.getReviewFile(
-          reviewId: review.id, filePath: file.filePath, revision: file.revisionId),
+      future: _changes,
       builder: (context, state) {
         if (!state.hasData) {
           log("loading or error $state");
           return Container();
         }
 
-        return CodeViewer(file, state.data!);
+        return CodeViewer(widget.file, state.data!);
       },
     );
   }
@@ -125,13 +166,105 @@ class CodeViewer extends StatelessWidget {
     return ScrollContainer(
       builder: (context, scrollController) => SingleChildScrollView(
           controller: scrollController,
-          child: HighlightView(
-            file.text,
-            language: fileLanguage,
-            padding: const EdgeInsets.all(8),
-            theme: darculaTheme,
-            tabSize: 4,
+          child: Column(
+            crossAxisAlignment: CrossAxisAlignment.stretch,
+            children: file.hunks.map((hunk) => DiffHunkView(hunk, language: fileLanguage)).toList(),
           )),
     );
   }
 }
+
+class DiffHunkView extends StatelessWidget {
+  final DiffHunk hunk;
+  final String language;
+
+  const DiffHunkView(this.hunk, {required this.language, Key? key}) : super(key: key);
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(crossAxisAlignment: CrossAxisAlignment.stretch, children: [
+      Container(
+        color: Colors.blueGrey.withOpacity(0.2),
+        padding: const EdgeInsets.symmetric(horizontal: 8, vertical: 4),
+        child: Text(
+            "@@ -${hunk.oldStart},${hunk.oldLines} +${hunk.newStart},${hunk.newLines} @@",
+            style: const TextStyle(fontFamily: "monospace", color: Colors.white54)),
+      ),
+      ...hunk.lines.map((line) => DiffLineView(line, language: language)),
+    ]);
+  }
+}
+
+class DiffLineView extends StatelessWidget {
+  final DiffLine line;
+  final String language;
+
+  const DiffLineView(this.line, {required this.language, Key? key}) : super(key: key);
+
+  @override
+  Widget build(BuildContext context) {
+    const lineNumberStyle = TextStyle(fontFamily: "monospace", color: Colors.white38);
+    return Container(
+      color: _background(),
+      child: Row(crossAxisAlignment: CrossAxisAlignment.start, children: [
+        SizedBox(
+            width: 48,
+            child: Text(line.oldLineNumber?.toString() ?? "",
+                textAlign: TextAlign.right, style: lineNumberStyle)),
+        SizedBox(
+            width: 48,
+            child: Text(line.newLineNumber?.toString() ?? "",
+                textAlign: TextAlign.right, style: lineNumberStyle)),
+        Expanded(
+          child: line.changedRanges.isEmpty
+              ? HighlightView(
+                  line.text,
+                  language: language,
+                  padding: const EdgeInsets.symmetric(horizontal: 8),
+                  theme: {
+                    ...darculaTheme,
+                    'root': darculaTheme['root']!.copyWith(backgroundColor: Colors.transparent)
+                  },
+                  tabSize: 4,
+                )
+              : Padding(
+                  padding: const EdgeInsets.symmetric(horizontal: 8),
+                  child: Text.rich(TextSpan(
+                      style: const TextStyle(fontFamily: "monospace"), children: _changedSpans())),
+                ),
+        ),
+      ]),
+    );
+  }
+
+  List<TextSpan> _changedSpans() {
+    var highlight = line.lineType == DiffLineType.Removed
+        ? Colors.red.withOpacity(0.4)
+        : Colors.green.withOpacity(0.4);
+    List<TextSpan> spans = [];
+    var offset = 0;
+    for (var range in line.changedRanges) {
+      if (range.start > offset) {
+        spans.add(TextSpan(text: line.text.substring(offset, range.start)));
+      }
+      spans.add(TextSpan(
+          text: line.text.substring(range.start, range.end),
+          style: TextStyle(backgroundColor: highlight)));
+      offset = range.end;
+    }
+    spans.add(TextSpan(text: line.text.substring(offset)));
+
+    return spans;
+  }
+
+  Color? _background() {
+    switch (line.lineType) {
+      case DiffLineType.Added:
+        return Colors.green.withOpacity(0.15);
+      case DiffLineType.Removed:
+        return Colors.red.withOpacity(0.15);
+      case DiffLineType.Context:
+        return null;
+    }
+  }
+}
@@ -2,7 +2,12 @@ import 'package:flutter/material.dart';
 import 'package:review_tool/api.dart';
 import 'package:review_tool/ui/views/settings/provider_settings/provider_gitlab.dart';
 
+import 'provider_bitbucket.dart';
+import 'provider_gerrit.dart';
+import 'provider_gitea.dart';
 import 'provider_github.dart';
+import 'provider_mock.dart';
+import 'provider_test.dart';
 import 'provider_upsource.dart';
 
 class EditProviderDialog extends StatefulWidget {
@@ -33,11 +38,18 @@ class _EditProviderDialogState extends State<EditProviderDialog> {
       actions: [
         TextButton(child: const Text("Cancel"), onPressed: () => Navigator.of(context).pop()),
         TextButton(
-            child: const Text("Save"), onPressed: () => Navigator.of(context).pop(_settings)),
+            child: const Text("Save"), onPressed: _save),
       ],
     );
   }
 
+  Future<void> _save() async {
+    var settings = _settings;
+    if (await validateProvider(context, settings) && mounted) {
+      Navigator.of(context).pop(settings);
+    }
+  }
+
   Widget _providerSettings() {
     var providerSettings = _settings.module.when(
         upsource: (settings) => EditUpsourceSettings(
@@ -45,7 +57,15 @@ class _EditProviderDialogState extends State<EditProviderDialog> {
         github: (settings) => EditGithubSettings(
             onUpdate: (s) => _updateModule(ProviderModule.github(s)), settings: settings),
         gitlab: (settings) => EditGitlabSettings(
-            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.gitlab(s))));
+            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.gitlab(s))),
+        gitea: (settings) => EditGiteaSettings(
+            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.gitea(s))),
+        bitbucket: (settings) => EditBitbucketSettings(
+            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.bitbucket(s))),
+        gerrit: (settings) => EditGerritSettings(
+            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.gerrit(s))),
+        mock: (settings) => EditMockSettings(
+            settings: settings, onUpdate: (s) => _updateModule(ProviderModule.mock(s))));
 
     return Column(mainAxisSize: MainAxisSize.min, children: [
       TextFormField(
@@ -59,13 +79,15 @@ class _EditProviderDialogState extends State<EditProviderDialog> {
 
   _updateModule(ProviderModule module) {
     setState(() {
-      _settings = ProviderSettings(id: _settings.id, name: _settings.name, module: module);
+      _settings = ProviderSettings(
+          id: _settings.id, name: _settings.name, module: module, connection: _settings.connection);
     });
   }
 
   _updateName(String name) {
     setState(() {
-      _settings = ProviderSettings(id: _settings.id, name: name, module: _settings.module);
+      _settings = ProviderSettings(
+          id: _settings.id, name: name, module: _settings.module, connection: _settings.connection);
     });
   }
 }
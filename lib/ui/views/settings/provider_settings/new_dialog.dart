@@ -1,8 +1,13 @@
 import 'package:flutter/material.dart';
 import 'package:review_tool/api.dart';
 
+import 'provider_bitbucket.dart';
+import 'provider_gerrit.dart';
+import 'provider_gitea.dart';
 import 'provider_github.dart';
 import 'provider_gitlab.dart';
+import 'provider_mock.dart';
+import 'provider_test.dart';
 import 'provider_upsource.dart';
 
 class NewProviderDialog extends StatefulWidget {
@@ -25,11 +30,18 @@ class _NewProviderDialogState extends State<NewProviderDialog> {
         TextButton(child: const Text("Cancel"), onPressed: () => Navigator.of(context).pop()),
         TextButton(
             child: const Text("Add"),
-            onPressed: _settings == null ? null : () => Navigator.of(context).pop(_settings)),
+            onPressed: _settings == null ? null : _add),
       ],
     );
   }
 
+  Future<void> _add() async {
+    var settings = _settings!;
+    if (await validateProvider(context, settings) && mounted) {
+      Navigator.of(context).pop(settings);
+    }
+  }
+
   Widget _providerSelector() {
     return Column(mainAxisSize: MainAxisSize.min, children: [
       ListTile(
@@ -47,6 +59,24 @@ class _NewProviderDialogState extends State<NewProviderDialog> {
         onTap: () => _initSettings(
             ProviderModule.gitlab(GitlabProviderSettings(url: "https://gitlab.com", token: ""))),
       ),
+      ListTile(
+        title: const Text("Gitea / Forgejo"),
+        onTap: () => _initSettings(ProviderModule.gitea(GiteaProviderSettings(url: "", token: ""))),
+      ),
+      ListTile(
+        title: const Text("Bitbucket Server"),
+        onTap: () =>
+            _initSettings(ProviderModule.bitbucket(BitbucketProviderSettings(url: "", token: ""))),
+      ),
+      ListTile(
+        title: const Text("Gerrit"),
+        onTap: () => _initSettings(
+            ProviderModule.gerrit(GerritProviderSettings(url: "", username: "", token: ""))),
+      ),
+      ListTile(
+        title: const Text("Mock"),
+        onTap: () => _initSettings(ProviderModule.mock(MockProviderSettings(path: ""))),
+      ),
     ]);
   }
 
@@ -58,6 +88,14 @@ class _NewProviderDialogState extends State<NewProviderDialog> {
           onUpdate: (s) => _updateModule(ProviderModule.github(s)), settings: settings),
       gitlab: (settings) => EditGitlabSettings(
           onUpdate: (s) => _updateModule(ProviderModule.gitlab(s)), settings: settings),
+      gitea: (settings) => EditGiteaSettings(
+          onUpdate: (s) => _updateModule(ProviderModule.gitea(s)), settings: settings),
+      bitbucket: (settings) => EditBitbucketSettings(
+          onUpdate: (s) => _updateModule(ProviderModule.bitbucket(s)), settings: settings),
+      gerrit: (settings) => EditGerritSettings(
+          onUpdate: (s) => _updateModule(ProviderModule.gerrit(s)), settings: settings),
+      mock: (settings) => EditMockSettings(
+          onUpdate: (s) => _updateModule(ProviderModule.mock(s)), settings: settings),
     );
 
     return Column(mainAxisSize: MainAxisSize.min, children: [
@@ -72,19 +110,21 @@ class _NewProviderDialogState extends State<NewProviderDialog> {
 
   _initSettings(ProviderModule module) {
     setState(() {
-      _settings = ProviderSettings(id: "", name: "", module: module);
+      _settings = ProviderSettings(id: "", name: "", module: module, connection: null);
     });
   }
 
   _updateModule(ProviderModule module) {
     setState(() {
-      _settings = ProviderSettings(id: _settings!.id, name: _settings!.name, module: module);
+      _settings = ProviderSettings(
+          id: _settings!.id, name: _settings!.name, module: module, connection: _settings!.connection);
     });
   }
 
   _updateName(String name) {
     setState(() {
-      _settings = ProviderSettings(id: _settings!.id, name: name, module: _settings!.module);
+      _settings = ProviderSettings(
+          id: _settings!.id, name: name, module: _settings!.module, connection: _settings!.connection);
     });
   }
 }
@@ -0,0 +1,48 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/api.dart';
+
+class EditBitbucketSettings extends StatefulWidget {
+  final Function(BitbucketProviderSettings) onUpdate;
+  final BitbucketProviderSettings settings;
+
+  const EditBitbucketSettings({required this.settings, required this.onUpdate, Key? key})
+      : super(key: key);
+
+  @override
+  State<EditBitbucketSettings> createState() => _EditBitbucketSettingsState();
+}
+
+class _EditBitbucketSettingsState extends State<EditBitbucketSettings> {
+  final TextEditingController _urlController = TextEditingController();
+  final TextEditingController _tokenController = TextEditingController();
+
+  @override
+  void initState() {
+    super.initState();
+    _urlController.text = widget.settings.url;
+    _tokenController.text = widget.settings.token;
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(mainAxisSize: MainAxisSize.min, children: [
+      TextFormField(
+        decoration: const InputDecoration(labelText: "URL"),
+        controller: _urlController,
+        onChanged: (url) {
+          var settings = BitbucketProviderSettings(url: url, token: widget.settings.token);
+          widget.onUpdate(settings);
+        },
+      ),
+      TextFormField(
+        decoration: const InputDecoration(labelText: "Token"),
+        obscureText: true,
+        controller: _tokenController,
+        onChanged: (token) {
+          var settings = BitbucketProviderSettings(token: token, url: widget.settings.url);
+          widget.onUpdate(settings);
+        },
+      ),
+    ]);
+  }
+}
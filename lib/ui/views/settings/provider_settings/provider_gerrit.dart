@@ -0,0 +1,61 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/api.dart';
+
+class EditGerritSettings extends StatefulWidget {
+  final Function(GerritProviderSettings) onUpdate;
+  final GerritProviderSettings settings;
+
+  const EditGerritSettings({required this.settings, required this.onUpdate, Key? key})
+      : super(key: key);
+
+  @override
+  State<EditGerritSettings> createState() => _EditGerritSettingsState();
+}
+
+class _EditGerritSettingsState extends State<EditGerritSettings> {
+  final TextEditingController _urlController = TextEditingController();
+  final TextEditingController _usernameController = TextEditingController();
+  final TextEditingController _tokenController = TextEditingController();
+
+  @override
+  void initState() {
+    super.initState();
+    _urlController.text = widget.settings.url;
+    _usernameController.text = widget.settings.username;
+    _tokenController.text = widget.settings.token;
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(mainAxisSize: MainAxisSize.min, children: [
+      TextFormField(
+        decoration: const InputDecoration(labelText: "URL"),
+        controller: _urlController,
+        onChanged: (url) {
+          var settings = GerritProviderSettings(
+              url: url, username: widget.settings.username, token: widget.settings.token);
+          widget.onUpdate(settings);
+        },
+      ),
+      TextFormField(
+        decoration: const InputDecoration(labelText: "Username"),
+        controller: _usernameController,
+        onChanged: (username) {
+          var settings = GerritProviderSettings(
+              username: username, url: widget.settings.url, token: widget.settings.token);
+          widget.onUpdate(settings);
+        },
+      ),
+      TextFormField(
+        decoration: const InputDecoration(labelText: "HTTP Password"),
+        obscureText: true,
+        controller: _tokenController,
+        onChanged: (token) {
+          var settings = GerritProviderSettings(
+              token: token, url: widget.settings.url, username: widget.settings.username);
+          widget.onUpdate(settings);
+        },
+      ),
+    ]);
+  }
+}
@@ -0,0 +1,48 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/api.dart';
+
+class EditGiteaSettings extends StatefulWidget {
+  final Function(GiteaProviderSettings) onUpdate;
+  final GiteaProviderSettings settings;
+
+  const EditGiteaSettings({required this.settings, required this.onUpdate, Key? key})
+      : super(key: key);
+
+  @override
+  State<EditGiteaSettings> createState() => _EditGiteaSettingsState();
+}
+
+class _EditGiteaSettingsState extends State<EditGiteaSettings> {
+  final TextEditingController _urlController = TextEditingController();
+  final TextEditingController _tokenController = TextEditingController();
+
+  @override
+  void initState() {
+    super.initState();
+    _urlController.text = widget.settings.url;
+    _tokenController.text = widget.settings.token;
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(mainAxisSize: MainAxisSize.min, children: [
+      TextFormField(
+        decoration: const InputDecoration(labelText: "URL"),
+        controller: _urlController,
+        onChanged: (url) {
+          var settings = GiteaProviderSettings(url: url, token: widget.settings.token);
+          widget.onUpdate(settings);
+        },
+      ),
+      TextFormField(
+        decoration: const InputDecoration(labelText: "Token"),
+        obscureText: true,
+        controller: _tokenController,
+        onChanged: (token) {
+          var settings = GiteaProviderSettings(token: token, url: widget.settings.url);
+          widget.onUpdate(settings);
+        },
+      ),
+    ]);
+  }
+}
@@ -0,0 +1,37 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/api.dart';
+
+class EditMockSettings extends StatefulWidget {
+  final Function(MockProviderSettings) onUpdate;
+  final MockProviderSettings settings;
+
+  const EditMockSettings({required this.settings, required this.onUpdate, Key? key})
+      : super(key: key);
+
+  @override
+  State<EditMockSettings> createState() => _EditMockSettingsState();
+}
+
+class _EditMockSettingsState extends State<EditMockSettings> {
+  final TextEditingController _pathController = TextEditingController();
+
+  @override
+  void initState() {
+    super.initState();
+    _pathController.text = widget.settings.path;
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(mainAxisSize: MainAxisSize.min, children: [
+      TextFormField(
+        decoration: const InputDecoration(labelText: "Fixture Directory"),
+        controller: _pathController,
+        onChanged: (path) {
+          var settings = MockProviderSettings(path: path);
+          widget.onUpdate(settings);
+        },
+      ),
+    ]);
+  }
+}
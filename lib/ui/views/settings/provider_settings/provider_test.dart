@@ -0,0 +1,41 @@
+import 'package:flutter/material.dart';
+import 'package:review_tool/api.dart';
+import 'package:review_tool/ffi.dart';
+
+/// Tests the settings against the provider, returns whether they should be saved.
+///
+/// Failed checks are shown to the user, who can still decide to save the settings.
+Future<bool> validateProvider(BuildContext context, ProviderSettings settings) async {
+  var report = await api.testProvider(settings: settings);
+  var problem = _describeProblem(report);
+  if (problem == null) {
+    return true;
+  }
+  var save = await showDialog<bool>(
+    context: context,
+    builder: (context) => AlertDialog(
+      title: const Text("Provider Check Failed"),
+      content: Text(problem),
+      actions: [
+        TextButton(child: const Text("Back"), onPressed: () => Navigator.of(context).pop(false)),
+        TextButton(child: const Text("Save Anyway"), onPressed: () => Navigator.of(context).pop(true)),
+      ],
+    ),
+  );
+
+  return save ?? false;
+}
+
+String? _describeProblem(ProviderTestReport report) {
+  if (!report.reachable) {
+    return "The provider could not be reached, check the url.";
+  }
+  if (!report.authenticated) {
+    return "The provider rejected the token.";
+  }
+  if (report.missingScopes.isNotEmpty) {
+    var user = report.user?.name ?? "the user";
+    return "The token of $user lacks the scopes ${report.missingScopes.join(", ")}.";
+  }
+  return null;
+}
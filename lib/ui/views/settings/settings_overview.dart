@@ -27,7 +27,8 @@ class SettingsOverviewView extends StatelessWidget {
                 value: Text(provider.module.when(
                     upsource: (upsource) => upsource.url,
                     github: (github) => github.query,
-                    gitlab: (gitlab) => gitlab.url)))),
+                    gitlab: (gitlab) => gitlab.url,
+                    mock: (mock) => mock.path)))),
             SettingsTile(
               title: const Text("Add Provider", style: TextStyle(color: Colors.white54)),
               onPressed: (context) => onAddProvider(context),
@@ -0,0 +1,36 @@
+use native::api;
+use native::models::*;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let id = "module".to_string();
+    api::configure_modules(vec![ProviderSettings {
+        id: id.clone(),
+        name: "".to_string(),
+        module: ProviderModule::Gitea(GiteaProviderSettings {
+            token: std::env::var("GITEA_TOKEN").unwrap(),
+            url: std::env::var("GITEA_URL").unwrap(),
+        })
+        .into(),
+        connection: None,
+    }])?;
+
+    let reviews = api::get_reviews(id.clone())?;
+    println!("{reviews:?}");
+
+    for review in reviews {
+        let discussions = api::get_review_discussions(id.clone(), review.id.clone())?;
+        println!("{discussions:?}");
+
+        let summaries = api::get_review_file_summaries(id.clone(), review.id.clone())?;
+        println!("{summaries:?}");
+
+        if let Some(file) = summaries.into_iter().next() {
+            let file =
+                api::get_review_file(id.clone(), review.id, file.file_path, file.revision_id, None);
+            println!("{:?}", file)
+        }
+    }
+
+    Ok(())
+}
@@ -11,13 +11,13 @@ fn main() -> anyhow::Result<()> {
             token: std::env::var("UPSOURCE_TOKEN").unwrap(),
         })
         .into(),
+        connection: None,
     }])?;
 
     let reviews = api::get_reviews(id.clone())?;
     println!("{reviews:?}");
 
-    let mut reviews = reviews.into_iter();
-    while let Some(review) = reviews.next() {
+    for review in reviews {
         let discussions = api::get_review_discussions(id.clone(), review.id.clone())?;
         println!("{discussions:?}");
 
@@ -26,7 +26,7 @@ fn main() -> anyhow::Result<()> {
 
         if let Some(file) = summaries.into_iter().next() {
             let file =
-                api::get_review_file(id.clone(), review.id, file.file_path, file.revision_id)?;
+                api::get_review_file(id.clone(), review.id, file.file_path, file.revision_id, None)?;
             println!("{:?}", file)
         }
     }
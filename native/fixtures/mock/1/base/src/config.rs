@@ -0,0 +1,4 @@
+pub struct Config {
+    pub name: String,
+    pub timeout: u64,
+}
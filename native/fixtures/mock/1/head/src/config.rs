@@ -0,0 +1,6 @@
+use std::time::Duration;
+
+pub struct Config {
+    pub name: String,
+    pub timeout: Duration,
+}
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let (amount, unit) = value.split_at(value.len().checked_sub(1)?);
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount * 60)),
+        "h" => Some(Duration::from_secs(amount * 60 * 60)),
+        _ => None,
+    }
+}
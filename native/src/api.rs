@@ -1,34 +1,51 @@
+pub use crate::error::ApiError;
 pub use crate::models::*;
 
+use crate::runtime;
+use crate::watcher::Watcher;
 use crate::ApiModules;
+use flutter_rust_bridge::StreamSink;
 use lazy_static::lazy_static;
+use std::time::Duration;
 
 lazy_static! {
     static ref MODULE: ApiModules = ApiModules::new();
+    static ref WATCHER: Watcher = Watcher::new();
 }
 
-pub fn get_reviews(provider_id: String) -> anyhow::Result<Vec<Review>> {
-    MODULE.get_reviews(provider_id)
+pub fn get_reviews(provider_id: String) -> Result<Vec<Review>, ApiError> {
+    runtime::block_on(MODULE.get_reviews(provider_id)).map_err(ApiError::from)
 }
 
-pub fn get_review_discussions(provider_id: String, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
-    MODULE
-        .get_review_discussions(provider_id, review_id)
+pub fn get_all_reviews() -> Result<Inbox, ApiError> {
+    Ok(runtime::block_on(MODULE.get_all_reviews()))
 }
 
-pub fn get_review_file_summaries(provider_id: String, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
-    MODULE
-        .get_review_file_summaries(provider_id, review_id)
+pub fn get_review_discussions(provider_id: String, review_id: String) -> Result<Vec<ReviewDiscussion>, ApiError> {
+    runtime::block_on(MODULE.get_review_discussions(provider_id, review_id)).map_err(ApiError::from)
 }
 
+pub fn get_review_file_summaries(provider_id: String, review_id: String) -> Result<Vec<ReviewFileSummary>, ApiError> {
+    runtime::block_on(MODULE.get_review_file_summaries(provider_id, review_id)).map_err(ApiError::from)
+}
+
+/// With a `cancellation_token` the request can be aborted through [`cancel_request`].
 pub fn get_review_file(
     provider_id: String,
     review_id: String,
     file_path: String,
     revision: String,
-) -> anyhow::Result<ReviewFileChanges> {
-    MODULE
-        .get_review_file_changes(provider_id, review_id, file_path, revision)
+    cancellation_token: Option<String>,
+) -> Result<ReviewFileChanges, ApiError> {
+    let request = MODULE.get_review_file_changes(provider_id, review_id, file_path, revision);
+    runtime::block_on(MODULE.cancellable(cancellation_token, request)).map_err(ApiError::from)
+}
+
+/// Aborts the pending request started with `token`, it fails with `ApiError::Cancelled`.
+pub fn cancel_request(token: String) -> Result<(), ApiError> {
+    MODULE.cancel_request(&token);
+
+    Ok(())
 }
 
 pub fn mark_file_read(
@@ -37,12 +54,140 @@ pub fn mark_file_read(
     file_path: String,
     revision: String,
     read: bool,
-) -> anyhow::Result<()> {
-    MODULE
-        .mark_file_read(provider_id, review_id, file_path, revision, read)
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.mark_file_read(provider_id, review_id, file_path, revision, read)).map_err(ApiError::from)
+}
+
+pub fn submit_review_verdict(
+    provider_id: String,
+    review_id: String,
+    verdict: ReviewVerdict,
+    body: Option<String>,
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.submit_review_verdict(provider_id, review_id, verdict, body)).map_err(ApiError::from)
+}
+
+pub fn set_discussion_resolved(
+    provider_id: String,
+    review_id: String,
+    discussion_id: String,
+    resolved: bool,
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.set_discussion_resolved(provider_id, review_id, discussion_id, resolved)).map_err(ApiError::from)
+}
+
+pub fn create_file_discussion(
+    provider_id: String,
+    review_id: String,
+    file_path: String,
+    revision: String,
+    line: u32,
+    text: String,
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.create_file_discussion(provider_id, review_id, file_path, revision, line, text)).map_err(ApiError::from)
+}
+
+pub fn create_review_discussion(
+    provider_id: String,
+    review_id: String,
+    text: String,
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.create_review_discussion(provider_id, review_id, text)).map_err(ApiError::from)
+}
+
+pub fn reply_to_discussion(
+    provider_id: String,
+    review_id: String,
+    discussion_id: String,
+    text: String,
+) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.reply_to_discussion(provider_id, review_id, discussion_id, text)).map_err(ApiError::from)
+}
+
+/// Request budget the provider reported last, values it doesn't report are `None`.
+pub fn get_rate_limit(provider_id: String) -> Result<RateLimit, ApiError> {
+    MODULE.get_rate_limit(&provider_id).map_err(ApiError::from)
+}
+
+pub fn configure_modules(modules: Vec<ProviderSettings>) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.configure(modules)).map_err(ApiError::from)
+}
+
+/// Configures the providers whose module can be created and returns the others with their error.
+pub fn configure_available_modules(modules: Vec<ProviderSettings>) -> Result<Vec<ProviderError>, ApiError> {
+    Ok(runtime::block_on(MODULE.configure_available(modules)))
+}
+
+/// Checks reachability, authentication and scopes of the settings without configuring the provider.
+pub fn test_provider(settings: ProviderSettings) -> Result<ProviderTestReport, ApiError> {
+    Ok(runtime::block_on(MODULE.test_provider(settings)))
+}
+
+/// Reads the provider settings stored at `path`, files of older versions are migrated.
+///
+/// Tokens are only filled in while the secrets are unlocked.
+pub fn load_provider_settings(path: String) -> Result<Vec<ProviderSettings>, ApiError> {
+    MODULE.load_provider_settings(path).map_err(ApiError::from)
+}
+
+/// Saves the provider settings at `path`, while the secrets are unlocked the tokens are encrypted in them.
+///
+/// Fails with `ApiError::InvalidConfig` while the secrets are locked, unless they don't exist yet
+/// and none of the providers has a token.
+pub fn save_provider_settings(path: String, providers: Vec<ProviderSettings>) -> Result<(), ApiError> {
+    MODULE.save_provider_settings(path, providers).map_err(ApiError::from)
+}
+
+/// Opens the encrypted secrets at `path`, creating them when they don't exist yet.
+///
+/// Fails with `ApiError::Unauthorized` for a wrong passphrase.
+pub fn unlock_secrets(path: String, passphrase: String) -> Result<(), ApiError> {
+    MODULE.unlock_secrets(path, passphrase).map_err(ApiError::from)
+}
+
+pub fn lock_secrets() -> Result<(), ApiError> {
+    MODULE.lock_secrets();
+
+    Ok(())
+}
+
+/// Persists responses in the database at `path` so they can be shown while offline.
+pub fn open_offline_store(path: String) -> Result<(), ApiError> {
+    MODULE.open_offline_store(path).map_err(ApiError::from)
+}
+
+/// Keeps the files marked as read of providers which don't track them in the database at `path`.
+///
+/// Without it marking such files as read fails with `ApiError::InvalidConfig`.
+pub fn open_read_state_store(path: String) -> Result<(), ApiError> {
+    MODULE.open_read_state_store(path).map_err(ApiError::from)
+}
+
+/// Amount of writes made while offline which weren't sent yet.
+pub fn get_pending_write_count() -> Result<u32, ApiError> {
+    MODULE.get_pending_write_count().map_err(ApiError::from)
+}
+
+pub fn replay_pending_writes(provider_id: String) -> Result<(), ApiError> {
+    runtime::block_on(MODULE.replay_pending_writes(&provider_id)).map_err(ApiError::from)
 }
 
-pub fn configure_modules(modules: Vec<ProviderSettings>) -> anyhow::Result<()> {
-    println!("Configuring modules {:?}", modules);
-    MODULE.configure(modules)
+/// Polls all providers every `interval_seconds` and emits the changes of their reviews.
+///
+/// Only one watcher is active, starting a new one stops the previous stream.
+pub fn watch_reviews(sink: StreamSink<ReviewEvent>, interval_seconds: u32) -> Result<(), ApiError> {
+    if interval_seconds == 0 {
+        return Err(ApiError::InvalidConfig {
+            message: "The watch interval must be at least one second".into(),
+        });
+    }
+    WATCHER.start(&MODULE, Duration::from_secs(interval_seconds.into()), sink);
+
+    Ok(())
+}
+
+pub fn stop_watching_reviews() -> Result<(), ApiError> {
+    WATCHER.stop();
+
+    Ok(())
 }
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+/// `$REVIEW_TOOL_CONFIG`, otherwise `review-tool/config.json` in the user's config directory.
+///
+/// The file holds the provider settings in the format shared with the app.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    if let Some(path) = std::env::var_os("REVIEW_TOOL_CONFIG") {
+        return Ok(path.into());
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .context("Neither XDG_CONFIG_HOME nor HOME is set, pass --config")?;
+
+    Ok(config_dir.join("review-tool").join("config.json"))
+}
@@ -0,0 +1,261 @@
+//! Command line access to the configured review providers, e.g. for scripting or use over ssh.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use native::api;
+use native::models::*;
+use serde::Serialize;
+
+mod config;
+mod output;
+
+/// Environment variable holding the passphrase of the encrypted tokens
+const PASSPHRASE_VARIABLE: &str = "REVIEW_TOOL_PASSPHRASE";
+
+#[derive(Debug, Parser)]
+#[command(name = "review-tool", about = "Triage code reviews from the command line")]
+struct Cli {
+    /// Config file with the provider settings
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Print json instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    /// Keep responses in `offline.db` next to the config, to show them and queue writes while a provider is unreachable
+    #[arg(long, global = true)]
+    offline: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the reviews of all providers or a single one
+    Reviews {
+        #[arg(long)]
+        provider: Option<String>,
+    },
+    /// Show the files and discussions of a review
+    Show { provider: String, review: String },
+    /// Print the changes of a file in a review
+    Diff {
+        provider: String,
+        review: String,
+        file: String,
+        /// Revision of the file, the latest one of the review by default
+        #[arg(long)]
+        revision: Option<String>,
+    },
+    /// Mark files of a review as read
+    MarkRead {
+        provider: String,
+        review: String,
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Mark the files as unread instead
+        #[arg(long)]
+        unread: bool,
+    },
+    /// Move the tokens of the config file into the encrypted secrets next to it
+    ///
+    /// The passphrase is read from `REVIEW_TOOL_PASSPHRASE`, which other commands need from then on.
+    EncryptTokens,
+    /// Post a comment on a review, a file or as a reply to a discussion
+    Comment {
+        provider: String,
+        review: String,
+        text: String,
+        /// Comment on this file instead of the whole review
+        #[arg(long, requires = "line", conflicts_with = "reply_to")]
+        file: Option<String>,
+        #[arg(long, requires = "file")]
+        line: Option<u32>,
+        /// Reply to the discussion with this id
+        #[arg(long)]
+        reply_to: Option<String>,
+    },
+}
+
+impl Command {
+    /// The provider the command works with, `None` for all of them.
+    fn provider(&self) -> Option<&str> {
+        match self {
+            Command::Reviews { provider } => provider.as_deref(),
+            Command::Show { provider, .. }
+            | Command::Diff { provider, .. }
+            | Command::MarkRead { provider, .. }
+            | Command::Comment { provider, .. } => Some(provider),
+            Command::EncryptTokens => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => config::default_path()?,
+    };
+    let config = config_path.to_string_lossy().into_owned();
+    let passphrase = std::env::var(PASSPHRASE_VARIABLE).ok();
+    if let Some(passphrase) = passphrase.clone() {
+        let secrets = config_path.with_file_name("secrets.json");
+        api::unlock_secrets(secrets.to_string_lossy().into_owned(), passphrase)?;
+    }
+    let providers = api::load_provider_settings(config.clone())?;
+    anyhow::ensure!(
+        !providers.is_empty(),
+        "No providers are configured in {}",
+        config_path.display()
+    );
+    if let Command::EncryptTokens = cli.command {
+        anyhow::ensure!(passphrase.is_some(), "Set {PASSPHRASE_VARIABLE} to encrypt the tokens");
+        // saving while the secrets are unlocked moves the tokens into them
+        api::save_provider_settings(config, providers)?;
+
+        return Ok(());
+    }
+    // a broken provider must not keep the others from working
+    let configure_errors = match cli.command.provider() {
+        Some(provider_id) => {
+            let provider = providers
+                .into_iter()
+                .find(|provider| provider.id == provider_id)
+                .with_context(|| format!("Unknown provider {provider_id}"))?;
+            api::configure_modules(vec![provider])?;
+            vec![]
+        }
+        None => api::configure_available_modules(providers)?,
+    };
+    // providers which don't track read files need it, with or without `--offline`
+    let read_state_store = config_path.with_file_name("read_state.db");
+    api::open_read_state_store(read_state_store.to_string_lossy().into_owned())?;
+    if cli.offline {
+        let offline_store = config_path.with_file_name("offline.db");
+        api::open_offline_store(offline_store.to_string_lossy().into_owned())?;
+    }
+
+    match cli.command {
+        Command::Reviews { provider } => {
+            let inbox = match provider {
+                Some(provider_id) => Inbox {
+                    reviews: api::get_reviews(provider_id.clone())?
+                        .into_iter()
+                        .map(|review| InboxReview {
+                            provider_id: provider_id.clone(),
+                            review,
+                        })
+                        .collect(),
+                    errors: vec![],
+                },
+                None => {
+                    let mut inbox = api::get_all_reviews()?;
+                    inbox.errors.extend(configure_errors);
+                    inbox
+                }
+            };
+            if cli.json {
+                output::print_json(&inbox)?;
+            } else {
+                output::print_reviews(&inbox);
+            }
+        }
+        Command::Show { provider, review } => {
+            let files = api::get_review_file_summaries(provider.clone(), review.clone())?;
+            let discussions = api::get_review_discussions(provider, review)?;
+            if cli.json {
+                #[derive(Serialize)]
+                struct ReviewDetails {
+                    files: Vec<ReviewFileSummary>,
+                    discussions: Vec<ReviewDiscussion>,
+                }
+                output::print_json(&ReviewDetails { files, discussions })?;
+            } else {
+                output::print_files(&files);
+                println!();
+                output::print_discussions(&discussions);
+            }
+        }
+        Command::Diff {
+            provider,
+            review,
+            file,
+            revision,
+        } => {
+            let revision = match revision {
+                Some(revision) => revision,
+                None => latest_revision(&provider, &review, &file)?,
+            };
+            let changes = api::get_review_file(provider, review, file, revision, None)?;
+            if cli.json {
+                output::print_json(&changes)?;
+            } else {
+                output::print_diff(&changes);
+            }
+        }
+        Command::MarkRead {
+            provider,
+            review,
+            files,
+            unread,
+        } => {
+            let summaries = api::get_review_file_summaries(provider.clone(), review.clone())?;
+            for file in files {
+                let summary = summaries
+                    .iter()
+                    .find(|summary| summary.file_path == file)
+                    .with_context(|| format!("{file} is not part of the review"))?;
+                api::mark_file_read(
+                    provider.clone(),
+                    review.clone(),
+                    file,
+                    summary.revision_id.clone(),
+                    !unread,
+                )?;
+            }
+        }
+        Command::EncryptTokens => unreachable!("handled before the providers are configured"),
+        Command::Comment {
+            provider,
+            review,
+            text,
+            file,
+            line,
+            reply_to,
+        } => match (file.zip(line), reply_to) {
+            (Some((file, line)), _) => {
+                let revision = latest_revision(&provider, &review, &file)?;
+                api::create_file_discussion(provider, review, file, revision, line, text)?;
+            }
+            (None, Some(discussion_id)) => {
+                api::reply_to_discussion(provider, review, discussion_id, text)?;
+            }
+            (None, None) => api::create_review_discussion(provider, review, text)?,
+        },
+    }
+
+    Ok(())
+}
+
+fn latest_revision(provider_id: &str, review_id: &str, file_path: &str) -> anyhow::Result<String> {
+    let summaries = api::get_review_file_summaries(provider_id.to_string(), review_id.to_string())?;
+    let summary = summaries
+        .into_iter()
+        .find(|summary| summary.file_path == file_path)
+        .with_context(|| format!("{file_path} is not part of the review"))?;
+
+    Ok(summary.revision_id)
+}
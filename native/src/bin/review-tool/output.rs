@@ -0,0 +1,147 @@
+use native::models::*;
+use serde::Serialize;
+
+/// Prints rows aligned in columns, the last column isn't padded.
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: Vec<&'static str>) -> Self {
+        Self {
+            header,
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self) {
+        let header = self.header.iter().map(|cell| cell.to_string()).collect();
+        let rows = std::iter::once(&header).chain(self.rows.iter());
+        let widths = self.header.iter().enumerate().map(|(column, _)| {
+            rows.clone()
+                .map(|row| row.get(column).map_or(0, |cell| cell.chars().count()))
+                .max()
+                .unwrap_or_default()
+        });
+        let widths = widths.collect::<Vec<_>>();
+        for row in rows {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, width))| {
+                    if column + 1 == row.len() {
+                        cell.clone()
+                    } else {
+                        format!("{cell:width$}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            println!("{line}");
+        }
+    }
+}
+
+pub fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+
+    Ok(())
+}
+
+pub fn print_reviews(inbox: &Inbox) {
+    let mut table = Table::new(vec!["PROVIDER", "ID", "STATE", "AUTHORS", "TITLE"]);
+    for InboxReview { provider_id, review } in &inbox.reviews {
+        table.row(vec![
+            provider_id.clone(),
+            review.id.clone(),
+            format!("{:?}", review.state),
+            user_names(&review.authors),
+            review.title.clone(),
+        ]);
+    }
+    table.print();
+    for ProviderError { provider_id, error } in &inbox.errors {
+        eprintln!("{provider_id}: {error}");
+    }
+}
+
+pub fn print_files(files: &[ReviewFileSummary]) {
+    let mut table = Table::new(vec!["READ", "CHANGE", "ADDED", "REMOVED", "REVISION", "PATH"]);
+    for file in files {
+        table.row(vec![
+            if file.is_read { "yes" } else { "no" }.to_string(),
+            format!("{:?}", file.change_type),
+            format!("+{}", file.added_lines),
+            format!("-{}", file.removed_lines),
+            file.revision_id.clone(),
+            file.file_path.clone(),
+        ]);
+    }
+    table.print();
+}
+
+pub fn print_discussions(discussions: &[ReviewDiscussion]) {
+    for discussion in discussions {
+        let location = match &discussion.file {
+            Some(ReviewFileDiscussion {
+                file_path,
+                lines: Some(lines),
+                ..
+            }) if lines.start == lines.end => format!("{file_path}:{}", lines.start),
+            Some(ReviewFileDiscussion {
+                file_path,
+                lines: Some(lines),
+                ..
+            }) => format!("{file_path}:{}-{}", lines.start, lines.end),
+            Some(file) => file.file_path.clone(),
+            None => "review".to_string(),
+        };
+        let state = if discussion.resolved { "resolved" } else { "open" };
+        println!("{} ({location}, {state})", discussion.id);
+        for comment in &discussion.comments {
+            println!("  {}:", comment.user.name);
+            for line in comment.text.lines() {
+                println!("    {line}");
+            }
+        }
+        println!();
+    }
+}
+
+/// Prints the changes in the unified diff format.
+pub fn print_diff(changes: &ReviewFileChanges) {
+    let path = |path: &Option<String>, prefix: &str| match path {
+        Some(path) => format!("{prefix}/{path}"),
+        None => "/dev/null".to_string(),
+    };
+    println!("--- {}", path(&changes.old_path, "a"));
+    println!("+++ {}", path(&changes.new_path, "b"));
+    for hunk in &changes.hunks {
+        println!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+        );
+        for line in &hunk.lines {
+            let prefix = match line.line_type {
+                DiffLineType::Added => '+',
+                DiffLineType::Removed => '-',
+                DiffLineType::Context => ' ',
+            };
+            println!("{prefix}{}", line.text);
+        }
+    }
+}
+
+fn user_names(users: &[User]) -> String {
+    users
+        .iter()
+        .map(|user| user.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::models::*;
+use crate::{ApiModule, ReviewModule};
+
+const REVIEWS_TTL: Duration = Duration::from_secs(60);
+const DISCUSSIONS_TTL: Duration = Duration::from_secs(30);
+const FILE_SUMMARIES_TTL: Duration = Duration::from_secs(60);
+const FILE_CHANGES_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// In memory cache for responses, entries expire after `ttl` or never when no ttl is set.
+pub(crate) struct Cache<K, V> {
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// For immutable data like file contents at a given revision.
+    pub fn permanent() -> Self {
+        Self {
+            ttl: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock();
+        let (inserted_at, value) = entries.get(key)?;
+        if self.ttl.is_some_and(|ttl| inserted_at.elapsed() > ttl) {
+            entries.remove(key);
+            return None;
+        }
+
+        Some(value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.entries.lock().insert(key, (Instant::now(), value));
+    }
+
+    /// Returns the cached value or stores the result of `fetch`, failures are not cached.
+    pub async fn get_or_fetch<F>(&self, key: K, fetch: F) -> anyhow::Result<V>
+    where
+        F: Future<Output = anyhow::Result<V>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = fetch.await?;
+        self.insert(key, value.clone());
+
+        Ok(value)
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        self.entries.lock().remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+/// Caches the responses of a module, writes invalidate the responses they affect.
+pub(crate) struct CachedModule {
+    module: ApiModule,
+    reviews: Cache<(), Vec<Review>>,
+    discussions: Cache<String, Vec<ReviewDiscussion>>,
+    file_summaries: Cache<String, Vec<ReviewFileSummary>>,
+    file_changes: Cache<(String, String, String), ReviewFileChanges>,
+}
+
+impl CachedModule {
+    pub fn new(module: ApiModule) -> Self {
+        Self {
+            module,
+            reviews: Cache::new(REVIEWS_TTL),
+            discussions: Cache::new(DISCUSSIONS_TTL),
+            file_summaries: Cache::new(FILE_SUMMARIES_TTL),
+            file_changes: Cache::new(FILE_CHANGES_TTL),
+        }
+    }
+
+    /// The module itself, for reads which must not be answered from the cache.
+    pub fn uncached(&self) -> &ApiModule {
+        &self.module
+    }
+}
+
+#[async_trait]
+impl ReviewModule for CachedModule {
+    fn tracks_read_state(&self) -> bool {
+        self.module.tracks_read_state()
+    }
+
+    fn required_scopes(&self) -> &'static [&'static str] {
+        self.module.required_scopes()
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.module.rate_limit()
+    }
+
+    /// Never cached, it's used to check whether the token is still valid.
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        self.module.get_authentication().await
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.reviews
+            .get_or_fetch((), self.module.get_reviews())
+            .await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.discussions
+            .get_or_fetch(
+                review_id.clone(),
+                self.module.get_review_discussions(review_id),
+            )
+            .await
+    }
+
+    async fn get_review_file_summaries(
+        &self,
+        review_id: String,
+    ) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        self.file_summaries
+            .get_or_fetch(
+                review_id.clone(),
+                self.module.get_review_file_summaries(review_id),
+            )
+            .await
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        self.file_changes
+            .get_or_fetch(
+                (review_id.clone(), file_path.clone(), revision.clone()),
+                self.module
+                    .get_review_file_changes(review_id, file_path, revision),
+            )
+            .await
+    }
+
+    async fn mark_file_read(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        read: bool,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .mark_file_read(review_id.clone(), file_path, revision, read)
+            .await;
+        self.file_summaries.invalidate(&review_id);
+
+        result
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .set_discussion_resolved(review_id.clone(), discussion_id, resolved)
+            .await;
+        self.discussions.invalidate(&review_id);
+
+        result
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .submit_review_verdict(review_id.clone(), verdict, body)
+            .await;
+        self.reviews.clear();
+        self.discussions.invalidate(&review_id);
+
+        result
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .create_file_discussion(review_id.clone(), file_path, revision, line, text)
+            .await;
+        self.discussions.invalidate(&review_id);
+
+        result
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .create_review_discussion(review_id.clone(), text)
+            .await;
+        self.discussions.invalidate(&review_id);
+
+        result
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .module
+            .reply_to_discussion(review_id.clone(), discussion_id, text)
+            .await;
+        self.discussions.invalidate(&review_id);
+
+        result
+    }
+}
@@ -0,0 +1,82 @@
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+use crate::models::{DiffHunk, DiffLine, DiffLineType};
+
+use super::{group_into_hunks, highlight_changed_words, CONTEXT_LINES};
+
+/// Computes the hunks between two versions of a file.
+///
+/// `None` marks a side where the file doesn't exist, e.g. the old version of an added file.
+pub fn diff_files(old: Option<&str>, new: Option<&str>) -> Vec<DiffHunk> {
+    let lines = diff_lines(old.unwrap_or_default(), new.unwrap_or_default());
+    let mut hunks = group_into_hunks(lines, CONTEXT_LINES);
+    highlight_changed_words(&mut hunks);
+
+    hunks
+}
+
+/// Computes a patience line diff covering the whole file.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Patience)
+        .diff_lines(old, new);
+
+    diff.iter_all_changes()
+        .map(|change| DiffLine {
+            line_type: match change.tag() {
+                ChangeTag::Equal => DiffLineType::Context,
+                ChangeTag::Delete => DiffLineType::Removed,
+                ChangeTag::Insert => DiffLineType::Added,
+            },
+            old_line_number: change.old_index().map(|index| index as u32 + 1),
+            new_line_number: change.new_index().map(|index| index as u32 + 1),
+            text: change
+                .value()
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string(),
+            changed_ranges: vec![],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(old: &str, new: &str) -> Vec<(DiffLineType, String)> {
+        diff_lines(old, new)
+            .into_iter()
+            .map(|line| (line.line_type, line.text))
+            .collect()
+    }
+
+    #[test]
+    fn diffs_lines() {
+        use DiffLineType::*;
+        let cases = [
+            ("modified", "a\nb\nc\n", "a\nx\nc\n", vec![(Context, "a"), (Removed, "b"), (Added, "x"), (Context, "c")]),
+            ("added", "", "a\nb\n", vec![(Added, "a"), (Added, "b")]),
+            ("removed", "a\n", "", vec![(Removed, "a")]),
+            // line endings are not part of the text
+            ("crlf", "a\r\nb\r\n", "a\r\nc\r\n", vec![(Context, "a"), (Removed, "b"), (Added, "c")]),
+            // a missing newline at the end changes the last line
+            ("no newline at end", "a\nb", "a\nb\n", vec![(Context, "a"), (Removed, "b"), (Added, "b")]),
+        ];
+
+        for (name, old, new, expected) in cases {
+            let expected = expected
+                .into_iter()
+                .map(|(line_type, text)| (line_type, text.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(lines(old, new), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn missing_files_have_no_lines_on_their_side() {
+        let hunks = diff_files(None, Some("a\n"));
+
+        let line = &hunks[0].lines[0];
+        assert_eq!((line.old_line_number, line.new_line_number), (None, Some(1)));
+    }
+}
@@ -0,0 +1,142 @@
+use similar::{ChangeTag, TextDiff};
+
+use crate::models::{DiffHunk, DiffLine, DiffLineType, DiffRange};
+
+/// Marks the changed words of modified lines.
+///
+/// A block of removed lines directly followed by a block of added lines is treated as modification,
+/// the lines of both blocks are paired up in order.
+pub fn highlight_changed_words(hunks: &mut [DiffHunk]) {
+    for hunk in hunks {
+        let mut index = 0;
+        while index < hunk.lines.len() {
+            let removed = count_lines(&hunk.lines[index..], DiffLineType::Removed);
+            let added = count_lines(&hunk.lines[index + removed..], DiffLineType::Added);
+            if removed == 0 || added == 0 {
+                index += removed.max(1);
+                continue;
+            }
+            let (removed_lines, added_lines) = hunk.lines[index..index + removed + added].split_at_mut(removed);
+            for (old, new) in removed_lines.iter_mut().zip(added_lines.iter_mut()) {
+                highlight_line_pair(old, new);
+            }
+            index += removed + added;
+        }
+    }
+}
+
+fn count_lines(lines: &[DiffLine], line_type: DiffLineType) -> usize {
+    lines
+        .iter()
+        .take_while(|line| line.line_type == line_type)
+        .count()
+}
+
+fn highlight_line_pair(old: &mut DiffLine, new: &mut DiffLine) {
+    let diff = TextDiff::configure().diff_words(&old.text, &new.text);
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    for change in diff.iter_all_changes() {
+        let length = change.value().encode_utf16().count() as u32;
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_offset += length;
+                new_offset += length;
+            }
+            ChangeTag::Delete => {
+                push_range(&mut old.changed_ranges, old_offset, old_offset + length);
+                old_offset += length;
+            }
+            ChangeTag::Insert => {
+                push_range(&mut new.changed_ranges, new_offset, new_offset + length);
+                new_offset += length;
+            }
+        }
+    }
+}
+
+/// Adds the range, merging it with the previous one when they are adjacent
+fn push_range(ranges: &mut Vec<DiffRange>, start: u32, end: u32) {
+    match ranges.last_mut() {
+        Some(last) if last.end == start => last.end = end,
+        _ => ranges.push(DiffRange { start, end }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(line_type: DiffLineType, text: &str) -> DiffLine {
+        DiffLine {
+            line_type,
+            old_line_number: None,
+            new_line_number: None,
+            text: text.to_string(),
+            changed_ranges: vec![],
+        }
+    }
+
+    fn ranges(line: &DiffLine) -> Vec<(u32, u32)> {
+        line.changed_ranges.iter().map(|range| (range.start, range.end)).collect()
+    }
+
+    fn highlight(lines: Vec<DiffLine>) -> Vec<DiffLine> {
+        let mut hunks = [DiffHunk {
+            old_start: 1,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 0,
+            lines,
+        }];
+        highlight_changed_words(&mut hunks);
+        let [hunk] = hunks;
+
+        hunk.lines
+    }
+
+    #[test]
+    fn highlights_changed_words_in_utf16_units() {
+        let cases = [
+            ("changed word", "let a = 1;", "let a = 2;", vec![(8, 10)], vec![(8, 10)]),
+            // the emoji takes two code units
+            ("non bmp", "emoji 😀 a", "emoji 😀 b", vec![(9, 10)], vec![(9, 10)]),
+            ("umlaut", "grüße a", "grüße b", vec![(6, 7)], vec![(6, 7)]),
+            // adjacent changes form one range
+            ("adjacent", "a", "b c", vec![(0, 1)], vec![(0, 3)]),
+            ("unchanged", "same", "same", vec![], vec![]),
+        ];
+
+        for (name, old, new, old_ranges, new_ranges) in cases {
+            let lines = highlight(vec![line(DiffLineType::Removed, old), line(DiffLineType::Added, new)]);
+            assert_eq!(ranges(&lines[0]), old_ranges, "{name}");
+            assert_eq!(ranges(&lines[1]), new_ranges, "{name}");
+        }
+    }
+
+    #[test]
+    fn pairs_removed_and_added_blocks_in_order() {
+        let lines = highlight(vec![
+            line(DiffLineType::Removed, "a 1"),
+            line(DiffLineType::Removed, "b 1"),
+            line(DiffLineType::Added, "a 2"),
+            line(DiffLineType::Added, "b 2"),
+            line(DiffLineType::Added, "c 2"),
+        ]);
+
+        let ranges = lines.iter().map(ranges).collect::<Vec<_>>();
+        // the third added line has no removed counterpart
+        assert_eq!(ranges, vec![vec![(2, 3)], vec![(2, 3)], vec![(2, 3)], vec![(2, 3)], vec![]]);
+    }
+
+    #[test]
+    fn lines_separated_by_context_are_not_paired() {
+        let lines = highlight(vec![
+            line(DiffLineType::Removed, "a 1"),
+            line(DiffLineType::Context, "b"),
+            line(DiffLineType::Added, "a 2"),
+        ]);
+
+        assert!(lines.iter().all(|line| line.changed_ranges.is_empty()));
+    }
+}
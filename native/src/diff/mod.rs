@@ -0,0 +1,183 @@
+use crate::models::{DiffHunk, DiffLine, DiffLineType};
+
+pub use self::engine::diff_files;
+pub use self::inline::highlight_changed_words;
+pub use self::unified::parse_unified_diff;
+
+mod engine;
+mod inline;
+mod unified;
+
+/// Amount of unchanged lines kept around every change when grouping a whole file into hunks.
+pub const CONTEXT_LINES: usize = 3;
+
+/// Groups the lines of a whole file into hunks, keeping `context` unchanged lines around every change.
+pub fn group_into_hunks(lines: Vec<DiffLine>, context: usize) -> Vec<DiffHunk> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for (index, line) in lines.iter().enumerate() {
+        if line.line_type == DiffLineType::Context {
+            continue;
+        }
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if *last_end >= start => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut hunks = vec![];
+    let mut lines = lines.into_iter();
+    let mut position = 0;
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    for (start, end) in ranges {
+        for line in lines.by_ref().take(start - position) {
+            count_line(&line, &mut old_offset, &mut new_offset);
+        }
+        let hunk_lines = lines.by_ref().take(end - start).collect::<Vec<_>>();
+        position = end;
+
+        let mut hunk = DiffHunk {
+            old_start: old_offset,
+            old_lines: 0,
+            new_start: new_offset,
+            new_lines: 0,
+            lines: vec![],
+        };
+        for line in hunk_lines.iter() {
+            count_line(line, &mut hunk.old_lines, &mut hunk.new_lines);
+        }
+        old_offset += hunk.old_lines;
+        new_offset += hunk.new_lines;
+        // unified diffs point to the line before the hunk when it doesn't contain any lines of one side
+        if hunk.old_lines > 0 {
+            hunk.old_start += 1;
+        }
+        if hunk.new_lines > 0 {
+            hunk.new_start += 1;
+        }
+        hunk.lines = hunk_lines;
+        hunks.push(hunk);
+    }
+
+    hunks
+}
+
+/// Assigns old and new line numbers to a sequence of lines spanning a whole file.
+pub fn number_lines(lines: impl IntoIterator<Item = (DiffLineType, String)>) -> Vec<DiffLine> {
+    let mut old_line = 0;
+    let mut new_line = 0;
+
+    lines
+        .into_iter()
+        .map(|(line_type, text)| {
+            let (old_line_number, new_line_number) = match line_type {
+                DiffLineType::Context => {
+                    old_line += 1;
+                    new_line += 1;
+                    (Some(old_line), Some(new_line))
+                }
+                DiffLineType::Removed => {
+                    old_line += 1;
+                    (Some(old_line), None)
+                }
+                DiffLineType::Added => {
+                    new_line += 1;
+                    (None, Some(new_line))
+                }
+            };
+
+            DiffLine {
+                line_type,
+                old_line_number,
+                new_line_number,
+                text,
+                changed_ranges: vec![],
+            }
+        })
+        .collect()
+}
+
+fn count_line(line: &DiffLine, old_lines: &mut u32, new_lines: &mut u32) {
+    if line.old_line_number.is_some() {
+        *old_lines += 1;
+    }
+    if line.new_line_number.is_some() {
+        *new_lines += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::engine::diff_lines;
+    use super::*;
+
+    fn headers(hunks: &[DiffHunk]) -> Vec<(u32, u32, u32, u32)> {
+        hunks
+            .iter()
+            .map(|hunk| (hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines))
+            .collect()
+    }
+
+    fn numbered(count: u32) -> Vec<String> {
+        (1..=count).map(|line| line.to_string()).collect()
+    }
+
+    fn file(lines: &[String]) -> String {
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    #[test]
+    fn groups_changes_into_hunks_with_context() {
+        let twelve = numbered(12);
+        let mut middle = twelve.clone();
+        middle[5] = "changed".into();
+        let mut close = twelve.clone();
+        close[3] = "changed".into();
+        close[9] = "changed".into();
+        let mut apart = twelve.clone();
+        apart[0] = "changed".into();
+        apart[11] = "changed".into();
+        let cases = [
+            ("unchanged", file(&twelve), vec![]),
+            ("single change", file(&middle), vec![(3, 7, 3, 7)]),
+            // the context of both changes overlaps
+            ("merged context", file(&close), vec![(1, 12, 1, 12)]),
+            ("separate hunks", file(&apart), vec![(1, 4, 1, 4), (9, 4, 9, 4)]),
+        ];
+
+        for (name, new, expected) in cases {
+            let hunks = group_into_hunks(diff_lines(&file(&twelve), &new), CONTEXT_LINES);
+            assert_eq!(headers(&hunks), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn hunks_of_added_and_removed_files_start_before_the_missing_side() {
+        let cases = [
+            ("added", None, Some("a\nb\n"), (0, 0, 1, 2)),
+            ("removed", Some("a\nb\n"), None, (1, 2, 0, 0)),
+        ];
+
+        for (name, old, new, expected) in cases {
+            assert_eq!(headers(&diff_files(old, new)), vec![expected], "{name}");
+        }
+    }
+
+    #[test]
+    fn numbers_lines_of_both_sides() {
+        let lines = number_lines([
+            (DiffLineType::Context, "a".to_string()),
+            (DiffLineType::Removed, "b".to_string()),
+            (DiffLineType::Added, "c".to_string()),
+            (DiffLineType::Context, "d".to_string()),
+        ]);
+
+        let numbers = lines
+            .iter()
+            .map(|line| (line.old_line_number, line.new_line_number))
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, vec![(Some(1), Some(1)), (Some(2), None), (None, Some(2)), (Some(3), Some(3))]);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::models::{DiffHunk, DiffLine, DiffLineType};
+
+use super::highlight_changed_words;
+
+/// Parses the hunks of a unified diff like the patches returned by Github and Gitlab.
+///
+/// File headers (`---`/`+++`) and anything before the first hunk header are skipped.
+pub fn parse_unified_diff(patch: &str) -> anyhow::Result<Vec<DiffHunk>> {
+    let mut hunks: Vec<DiffHunk> = vec![];
+    let mut old_line = 0;
+    let mut new_line = 0;
+
+    for line in patch.lines() {
+        if line.starts_with("@@") {
+            let hunk = parse_hunk_header(line)?;
+            old_line = hunk.old_start;
+            new_line = hunk.new_start;
+            hunks.push(hunk);
+            continue;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+        let line_type = match line.chars().next() {
+            Some('+') => DiffLineType::Added,
+            Some('-') => DiffLineType::Removed,
+            Some(' ') | None => DiffLineType::Context,
+            // "\ No newline at end of file"
+            Some('\\') => continue,
+            _ => anyhow::bail!("Invalid diff line: {line}"),
+        };
+        let text = line.get(1..).unwrap_or_default();
+        let old_line_number = (line_type != DiffLineType::Added).then_some(old_line);
+        let new_line_number = (line_type != DiffLineType::Removed).then_some(new_line);
+        if old_line_number.is_some() {
+            old_line += 1;
+        }
+        if new_line_number.is_some() {
+            new_line += 1;
+        }
+        hunk.lines.push(DiffLine {
+            line_type,
+            old_line_number,
+            new_line_number,
+            text: text.to_string(),
+            changed_ranges: vec![],
+        });
+    }
+    highlight_changed_words(&mut hunks);
+
+    Ok(hunks)
+}
+
+/// Parses `@@ -old_start,old_lines +new_start,new_lines @@ section`
+fn parse_hunk_header(line: &str) -> anyhow::Result<DiffHunk> {
+    let invalid_header = || anyhow::anyhow!("Invalid hunk header: {line}");
+    let mut parts = line
+        .trim_start_matches("@@")
+        .split("@@")
+        .next()
+        .ok_or_else(invalid_header)?
+        .split_whitespace();
+    let old = parts
+        .next()
+        .and_then(|part| part.strip_prefix('-'))
+        .ok_or_else(invalid_header)?;
+    let new = parts
+        .next()
+        .and_then(|part| part.strip_prefix('+'))
+        .ok_or_else(invalid_header)?;
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+
+    Ok(DiffHunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: vec![],
+    })
+}
+
+fn parse_range(range: &str) -> anyhow::Result<(u32, u32)> {
+    if let Some((start, lines)) = range.split_once(',') {
+        Ok((start.parse()?, lines.parse()?))
+    } else {
+        Ok((range.parse()?, 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(hunk: &DiffHunk) -> Vec<(DiffLineType, Option<u32>, Option<u32>, &str)> {
+        hunk.lines
+            .iter()
+            .map(|line| (line.line_type, line.old_line_number, line.new_line_number, line.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_hunks() {
+        use DiffLineType::*;
+        let patch = "@@ -1,3 +1,3 @@ fn main() {\n a\n-b\n+c\n d\n@@ -10 +10,2 @@\n x\n+y\n";
+
+        let hunks = parse_unified_diff(patch).unwrap();
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].old_start, hunks[0].old_lines, hunks[0].new_start, hunks[0].new_lines), (1, 3, 1, 3));
+        assert_eq!(
+            lines(&hunks[0]),
+            vec![
+                (Context, Some(1), Some(1), "a"),
+                (Removed, Some(2), None, "b"),
+                (Added, None, Some(2), "c"),
+                (Context, Some(3), Some(3), "d"),
+            ]
+        );
+        // a range without count has a single line
+        assert_eq!((hunks[1].old_start, hunks[1].old_lines, hunks[1].new_start, hunks[1].new_lines), (10, 1, 10, 2));
+        assert_eq!(lines(&hunks[1]), vec![(Context, Some(10), Some(10), "x"), (Added, None, Some(11), "y")]);
+    }
+
+    #[test]
+    fn parses_patches_of_changed_files() {
+        use DiffLineType::*;
+        let cases = [
+            ("added", "@@ -0,0 +1,2 @@\n+a\n+b\n", vec![(Added, None, Some(1), "a"), (Added, None, Some(2), "b")]),
+            ("removed", "@@ -1,2 +0,0 @@\n-a\n-b\n", vec![(Removed, Some(1), None, "a"), (Removed, Some(2), None, "b")]),
+            (
+                "renamed",
+                "diff --git a/old.rs b/new.rs\nsimilarity index 90%\nrename from old.rs\nrename to new.rs\n--- a/old.rs\n+++ b/new.rs\n@@ -1 +1 @@\n-a\n+b\n",
+                vec![(Removed, Some(1), None, "a"), (Added, None, Some(1), "b")],
+            ),
+            (
+                "no newline at end",
+                "@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+a\n",
+                vec![(Removed, Some(1), None, "a"), (Added, None, Some(1), "a")],
+            ),
+            // empty lines are context lines whose leading space was stripped
+            ("empty context", "@@ -1,2 +1,2 @@\n\n-a\n+b\n", vec![(Context, Some(1), Some(1), ""), (Removed, Some(2), None, "a"), (Added, None, Some(2), "b")]),
+        ];
+
+        for (name, patch, expected) in cases {
+            let hunks = parse_unified_diff(patch).unwrap();
+            assert_eq!(hunks.len(), 1, "{name}");
+            assert_eq!(lines(&hunks[0]), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn renames_without_changes_have_no_hunks() {
+        let patch = "diff --git a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\n";
+
+        assert!(parse_unified_diff(patch).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_patches() {
+        let cases = [
+            ("header without ranges", "@@ @@\n a\n"),
+            ("header with invalid range", "@@ -a,1 +1 @@\n a\n"),
+            ("unknown line marker", "@@ -1 +1 @@\n*a\n"),
+        ];
+
+        for (name, patch) in cases {
+            assert!(parse_unified_diff(patch).is_err(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+/// Error returned over the ffi boundary so the ui can react to the kind of failure.
+///
+/// Modules keep using `anyhow` internally, they either return this error directly
+/// or the failures of the underlying clients get classified when crossing the api.
+#[derive(Debug, Clone, Serialize)]
+pub enum ApiError {
+    /// The provider rejected the configured credentials.
+    Unauthorized { message: String },
+    /// The credentials are valid but lack the permissions for the request.
+    Forbidden { message: String },
+    NotFound { message: String },
+    /// `reset_at` is the unix timestamp in seconds from which the provider accepts requests again.
+    RateLimited { reset_at: Option<i64>, message: String },
+    /// The provider could not be reached.
+    Network { message: String },
+    /// The provider settings are invalid or reference an unknown provider.
+    InvalidConfig { message: String },
+    /// Any other failure reported by the provider.
+    Provider { message: String },
+    /// The request was cancelled through its cancellation token.
+    Cancelled { message: String },
+}
+
+impl ApiError {
+    /// Classifies a non success http response.
+    pub(crate) fn from_status(status: u16, message: String, reset_at: Option<i64>) -> Self {
+        match status {
+            401 => ApiError::Unauthorized { message },
+            403 if reset_at.is_some() => ApiError::RateLimited { reset_at, message },
+            403 => ApiError::Forbidden { message },
+            404 => ApiError::NotFound { message },
+            429 => ApiError::RateLimited { reset_at, message },
+            _ => ApiError::Provider { message },
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            ApiError::Unauthorized { message }
+            | ApiError::Forbidden { message }
+            | ApiError::NotFound { message }
+            | ApiError::RateLimited { message, .. }
+            | ApiError::Network { message }
+            | ApiError::InvalidConfig { message }
+            | ApiError::Provider { message }
+            | ApiError::Cancelled { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ApiError::Unauthorized { .. } => "Unauthorized",
+            ApiError::Forbidden { .. } => "Forbidden",
+            ApiError::NotFound { .. } => "Not found",
+            ApiError::RateLimited { .. } => "Rate limited",
+            ApiError::Network { .. } => "Network error",
+            ApiError::InvalidConfig { .. } => "Invalid configuration",
+            ApiError::Provider { .. } => "Provider error",
+            ApiError::Cancelled { .. } => "Cancelled",
+        };
+        write!(f, "{kind}: {}", self.message())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError::classify(&err)
+    }
+}
+
+impl ApiError {
+    pub(crate) fn classify(err: &anyhow::Error) -> Self {
+        let message = format!("{err:#}");
+        for cause in err.chain() {
+            if let Some(error) = cause.downcast_ref::<ApiError>() {
+                return error.clone();
+            }
+            if let Some(error) = crate::modules::gitlab::classify_error(cause, &message) {
+                return error;
+            }
+            // middleware errors don't expose the error they wrap as source
+            if let Some(reqwest_middleware::Error::Middleware(error)) = cause.downcast_ref::<reqwest_middleware::Error>() {
+                return ApiError::classify(error);
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                if let Some(status) = error.status() {
+                    return ApiError::from_status(status.as_u16(), message, None);
+                }
+                if error.is_connect() || error.is_timeout() || error.is_request() {
+                    return ApiError::Network { message };
+                }
+            }
+            if cause.is::<std::io::Error>() {
+                return ApiError::Network { message };
+            }
+        }
+
+        classify_octorust_error(&err.root_cause().to_string(), &message)
+            .unwrap_or(ApiError::Provider { message })
+    }
+
+    /// Whether the provider couldn't be reached at all, as opposed to rejecting the request.
+    pub(crate) fn is_network_error(err: &anyhow::Error) -> bool {
+        matches!(ApiError::classify(err), ApiError::Network { .. })
+    }
+}
+
+/// Octorust only reports failures as formatted messages:
+/// `rate limit exceeded, will reset in {seconds} seconds` or `code: {status}, error: {body}`.
+fn classify_octorust_error(root_cause: &str, message: &str) -> Option<ApiError> {
+    if let Some(seconds) = root_cause
+        .strip_prefix("rate limit exceeded, will reset in ")
+        .and_then(|rest| rest.strip_suffix(" seconds"))
+    {
+        let reset_at = seconds.parse::<i64>().ok().map(|seconds| unix_now() + seconds);
+        return Some(ApiError::RateLimited {
+            reset_at,
+            message: message.to_string(),
+        });
+    }
+    let status = root_cause
+        .strip_prefix("code: ")?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+
+    Some(ApiError::from_status(status, message.to_string(), None))
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -1,92 +1,335 @@
+use crate::cache::CachedModule;
+use crate::error::ApiError;
 use crate::models::*;
-use crate::modules::{github::GithubModule, upsource::UpsourceModule, gitlab::GitlabModule};
+use crate::modules::{github::GithubModule, upsource::UpsourceModule, gitlab::GitlabModule, gitea::GiteaModule, bitbucket::BitbucketModule, gerrit::GerritModule, mock::MockModule};
+use crate::secrets::SecretStore;
+use crate::store::{OfflineStore, PendingWrite, ReadStateStore, ResponseKind};
 use enum_dispatch::enum_dispatch;
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use futures::future::{AbortHandle, Abortable, Aborted};
+use parking_lot::{Mutex, RwLock};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::PathBuf;
 use std::sync::Arc;
 use anyhow::Context;
+use async_trait::async_trait;
 
 pub mod api;
 mod bridge_generated;
+mod cache;
+mod diff;
+mod error;
 pub mod models;
 pub mod modules;
+mod retry;
+mod runtime;
+mod scheduler;
+mod secrets;
+mod settings;
+mod store;
 mod util;
+mod watcher;
 
 pub(crate) struct ApiModules {
-    modules: Arc<RwLock<HashMap<String, ApiModule>>>,
+    modules: Arc<RwLock<HashMap<String, Arc<CachedModule>>>>,
+    store: RwLock<Option<Arc<OfflineStore>>>,
+    /// Read files of providers which don't track them
+    read_state: RwLock<Option<Arc<ReadStateStore>>>,
+    /// Tokens of the saved provider settings, `None` while locked
+    secrets: RwLock<Option<SecretStore>>,
+    /// Path of the secrets unlocked last, still known after locking them again
+    secrets_path: RwLock<Option<PathBuf>>,
+    /// Providers whose queued writes are currently replayed
+    replaying: Mutex<HashSet<String>>,
+    /// Pending requests by their cancellation token
+    requests: Mutex<HashMap<String, AbortHandle>>,
 }
 
 impl ApiModules {
     pub fn new() -> Self {
         Self {
             modules: Arc::new(RwLock::new(HashMap::new())),
+            store: RwLock::new(None),
+            read_state: RwLock::new(None),
+            secrets: RwLock::new(None),
+            secrets_path: RwLock::new(None),
+            replaying: Mutex::new(HashSet::new()),
+            requests: Mutex::new(HashMap::new()),
         }
     }
 
-    pub fn configure(&self, modules: Vec<ProviderSettings>) -> anyhow::Result<()> {
-        let modules = modules
-            .into_iter()
-            .map(|provider| {
-                let module = match *provider.module {
-                    ProviderModule::Github(github) => {
-                        GithubModule::new(github.token, github.query).context("Creating Github module")?.into()
-                    }
-                    ProviderModule::Upsource(upsource) => {
-                        UpsourceModule::new(upsource.url, upsource.token).into()
-                    }
-                    ProviderModule::Gitlab(gitlab) => {
-                        GitlabModule::new(gitlab.url, gitlab.token).context("Creating Gitlab module")?.into()
-                    }
+    pub async fn configure(&self, modules: Vec<ProviderSettings>) -> anyhow::Result<()> {
+        let mut configured_modules = HashMap::new();
+        for provider in modules {
+            let connection = provider.connection.unwrap_or_default();
+            let module = create_module(*provider.module, &connection).await?;
+            configured_modules.insert(provider.id, Arc::new(CachedModule::new(module)));
+        }
+        let mut modules_ref = self.modules.write();
+        *modules_ref = configured_modules;
+
+        Ok(())
+    }
+
+    /// Like `configure`, but providers whose module can't be created are left out and returned with their error.
+    pub async fn configure_available(&self, modules: Vec<ProviderSettings>) -> Vec<ProviderError> {
+        let mut configured_modules = HashMap::new();
+        let mut errors = vec![];
+        for provider in modules {
+            let connection = provider.connection.unwrap_or_default();
+            match create_module(*provider.module, &connection).await {
+                Ok(module) => {
+                    configured_modules.insert(provider.id, Arc::new(CachedModule::new(module)));
+                }
+                Err(err) => errors.push(ProviderError {
+                    provider_id: provider.id,
+                    error: ApiError::from(err),
+                }),
+            }
+        }
+        *self.modules.write() = configured_modules;
+
+        errors
+    }
+
+    /// Checks the settings against the provider without registering the module.
+    pub async fn test_provider(&self, settings: ProviderSettings) -> ProviderTestReport {
+        let result = async {
+            let connection = settings.connection.unwrap_or_default();
+            let module = create_module(*settings.module, &connection).await?;
+            let authentication = module.get_authentication().await?;
+
+            anyhow::Ok((authentication, module.required_scopes()))
+        };
+        let (authentication, required_scopes) = match result.await {
+            Ok(authentication) => authentication,
+            Err(err) => {
+                let error = ApiError::from(err);
+                return ProviderTestReport {
+                    reachable: !matches!(error, ApiError::Network { .. } | ApiError::InvalidConfig { .. }),
+                    authenticated: false,
+                    user: None,
+                    scopes: None,
+                    missing_scopes: vec![],
+                    error: Some(error),
                 };
+            }
+        };
+        // without reported scopes only the requests themselves tell whether the token is sufficient
+        let missing_scopes = match &authentication.scopes {
+            Some(scopes) => required_scopes
+                .iter()
+                .filter(|required| !scopes.iter().any(|scope| scope == *required))
+                .map(|scope| scope.to_string())
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        let error = (!missing_scopes.is_empty()).then(|| ApiError::Forbidden {
+            message: format!("The token lacks the scopes {}", missing_scopes.join(", ")),
+        });
 
-                Ok((provider.id, module))
-            })
-            .collect::<anyhow::Result<_>>()?;
-        let mut modules_ref = self.modules.write();
-        *modules_ref = modules;
+        ProviderTestReport {
+            reachable: true,
+            authenticated: true,
+            user: Some(authentication.user),
+            scopes: authentication.scopes,
+            missing_scopes,
+            error,
+        }
+    }
+
+    /// Enables offline mode, responses are persisted in the database at `path`.
+    pub fn open_offline_store(&self, path: String) -> anyhow::Result<()> {
+        let store = OfflineStore::open(path).context("Opening offline store")?;
+        *self.store.write() = Some(Arc::new(store));
+
+        Ok(())
+    }
+
+    /// Keeps the read files of providers which don't track them in the database at `path`.
+    pub fn open_read_state_store(&self, path: String) -> anyhow::Result<()> {
+        let store = ReadStateStore::open(path).context("Opening read state store")?;
+        *self.read_state.write() = Some(Arc::new(store));
 
         Ok(())
     }
+
+    /// Derives the key of the secrets at `path` from the passphrase, tokens of saved settings are kept there.
+    pub fn unlock_secrets(&self, path: String, passphrase: String) -> anyhow::Result<()> {
+        let secrets = SecretStore::open(&path, &passphrase)?;
+        *self.secrets.write() = Some(secrets);
+        *self.secrets_path.write() = Some(PathBuf::from(path));
+
+        Ok(())
+    }
+
+    pub fn lock_secrets(&self) {
+        *self.secrets.write() = None;
+    }
+
+    pub fn load_provider_settings(&self, path: String) -> anyhow::Result<Vec<ProviderSettings>> {
+        settings::load(path, self.secrets.read().as_ref())
+    }
+
+    pub fn save_provider_settings(&self, path: String, providers: Vec<ProviderSettings>) -> anyhow::Result<()> {
+        let secrets_path = self.secrets_path.read().clone();
+        settings::save(path, providers, self.secrets.write().as_mut(), secrets_path.as_deref())
+    }
+
+    pub fn get_rate_limit(&self, provider_id: &str) -> anyhow::Result<RateLimit> {
+        Ok(self.get_provider(provider_id)?.rate_limit())
+    }
+
+    /// Runs `request`, which can be aborted with [`Self::cancel_request`] while a `token` is given.
+    pub async fn cancellable<T>(
+        &self,
+        token: Option<String>,
+        request: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let Some(token) = token else {
+            return request.await;
+        };
+        let (handle, registration) = AbortHandle::new_pair();
+        self.requests.lock().insert(token.clone(), handle);
+        let result = Abortable::new(request, registration).await;
+        self.requests.lock().remove(&token);
+
+        result.unwrap_or_else(|Aborted| {
+            Err(ApiError::Cancelled {
+                message: "The request was cancelled".into(),
+            }
+            .into())
+        })
+    }
+
+    /// Aborts the request started with `token`, finished or unknown requests are ignored.
+    pub fn cancel_request(&self, token: &str) {
+        if let Some(handle) = self.requests.lock().remove(token) {
+            handle.abort();
+        }
+    }
+
+    pub fn provider_ids(&self) -> Vec<String> {
+        self.modules.read().keys().cloned().collect()
+    }
+
+    pub fn get_pending_write_count(&self) -> anyhow::Result<u32> {
+        match self.store() {
+            Some(store) => store.pending_write_count(),
+            None => Ok(0),
+        }
+    }
 }
 
 impl ApiModules {
-    pub fn get_reviews(&self, provider_id: String) -> anyhow::Result<Vec<Review>> {
-        self.call_provider_method(provider_id, |provider| provider.get_reviews())
+    pub async fn get_reviews(&self, provider_id: String) -> anyhow::Result<Vec<Review>> {
+        let module = self.get_provider(&provider_id)?;
+        self.fetch(&provider_id, ResponseKind::Reviews, "", module.get_reviews())
+            .await
     }
 
-    pub fn get_review_discussions(
+    /// Queries all configured providers concurrently, a failing provider doesn't fail the whole inbox.
+    pub async fn get_all_reviews(&self) -> Inbox {
+        let providers = self
+            .modules
+            .read()
+            .iter()
+            .map(|(id, module)| (id.clone(), module.clone()))
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(providers.into_iter().map(
+            |(provider_id, module)| async move {
+                let reviews = self
+                    .fetch(&provider_id, ResponseKind::Reviews, "", module.get_reviews())
+                    .await;
+
+                (provider_id, reviews)
+            },
+        ))
+        .await;
+
+        let mut inbox = Inbox {
+            reviews: vec![],
+            errors: vec![],
+        };
+        for (provider_id, result) in results {
+            match result {
+                Ok(reviews) => inbox
+                    .reviews
+                    .extend(reviews.into_iter().map(|review| InboxReview {
+                        provider_id: provider_id.clone(),
+                        review,
+                    })),
+                Err(err) => inbox.errors.push(ProviderError {
+                    provider_id,
+                    error: err.into(),
+                }),
+            }
+        }
+
+        inbox
+    }
+
+    pub async fn get_review_discussions(
         &self,
         provider_id: String,
         review_id: String,
     ) -> anyhow::Result<Vec<ReviewDiscussion>> {
-        self.call_provider_method(provider_id, |provider| {
-            provider.get_review_discussions(review_id)
-        })
+        let module = self.get_provider(&provider_id)?;
+        self.fetch(
+            &provider_id,
+            ResponseKind::Discussions,
+            &review_id,
+            module.get_review_discussions(review_id.clone()),
+        )
+        .await
     }
 
-    pub fn get_review_file_summaries(
+    pub async fn get_review_file_summaries(
         &self,
         provider_id: String,
         review_id: String,
     ) -> anyhow::Result<Vec<ReviewFileSummary>> {
-        self.call_provider_method(provider_id, |provider| {
-            provider.get_review_file_summaries(review_id)
-        })
+        let module = self.get_provider(&provider_id)?;
+        let mut summaries = self
+            .fetch(
+                &provider_id,
+                ResponseKind::FileSummaries,
+                &review_id,
+                module.get_review_file_summaries(review_id.clone()),
+            )
+            .await?;
+        if !module.tracks_read_state() {
+            let read_files = self.read_files(&provider_id, &review_id)?;
+            for summary in summaries.iter_mut() {
+                summary.is_read = read_files
+                    .contains(&(summary.file_path.clone(), summary.revision_id.clone()));
+            }
+        }
+
+        Ok(summaries)
     }
 
-    pub fn get_review_file_changes(
+    pub async fn get_review_file_changes(
         &self,
         provider_id: String,
         review_id: String,
         file_path: String,
         revision: String,
     ) -> anyhow::Result<ReviewFileChanges> {
-        self.call_provider_method(provider_id, |provider| {
-            provider.get_review_file_changes(review_id, file_path, revision)
-        })
+        let module = self.get_provider(&provider_id)?;
+        let key = format!("{review_id}\n{file_path}\n{revision}");
+        self.fetch(
+            &provider_id,
+            ResponseKind::FileChanges,
+            &key,
+            module.get_review_file_changes(review_id, file_path, revision),
+        )
+        .await
     }
 
-    pub fn mark_file_read(
+    pub async fn mark_file_read(
         &self,
         provider_id: String,
         review_id: String,
@@ -94,52 +337,335 @@ impl ApiModules {
         revision: String,
         read: bool,
     ) -> anyhow::Result<()> {
-        self.call_provider_method(provider_id, |provider| {
-            provider.mark_file_read(review_id, file_path, revision, read)
-        })
+        if !self.get_provider(&provider_id)?.tracks_read_state() {
+            return self.set_file_read(provider_id, review_id, file_path, revision, read);
+        }
+        self.write(
+            &provider_id,
+            PendingWrite::MarkFileRead {
+                review_id,
+                file_path,
+                revision,
+                read,
+            },
+        )
+        .await
+    }
+
+    pub async fn submit_review_verdict(
+        &self,
+        provider_id: String,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.write(
+            &provider_id,
+            PendingWrite::SubmitReviewVerdict {
+                review_id,
+                verdict,
+                body,
+            },
+        )
+        .await
+    }
+
+    pub async fn set_discussion_resolved(
+        &self,
+        provider_id: String,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        self.write(
+            &provider_id,
+            PendingWrite::SetDiscussionResolved {
+                review_id,
+                discussion_id,
+                resolved,
+            },
+        )
+        .await
     }
 
-    fn call_provider_method<TResult>(
+    pub async fn create_file_discussion(
         &self,
         provider_id: String,
-        callback: impl FnOnce(&ApiModule) -> anyhow::Result<TResult>,
-    ) -> anyhow::Result<TResult> {
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.write(
+            &provider_id,
+            PendingWrite::CreateFileDiscussion {
+                review_id,
+                file_path,
+                revision,
+                line,
+                text,
+            },
+        )
+        .await
+    }
+
+    pub async fn create_review_discussion(
+        &self,
+        provider_id: String,
+        review_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.write(
+            &provider_id,
+            PendingWrite::CreateReviewDiscussion { review_id, text },
+        )
+        .await
+    }
+
+    pub async fn reply_to_discussion(
+        &self,
+        provider_id: String,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.write(
+            &provider_id,
+            PendingWrite::ReplyToDiscussion {
+                review_id,
+                discussion_id,
+                text,
+            },
+        )
+        .await
+    }
+
+    /// Sends the writes queued while the provider was unreachable, in the order they were made.
+    ///
+    /// Returns right away while another task is replaying them.
+    pub async fn replay_pending_writes(&self, provider_id: &str) -> anyhow::Result<()> {
+        let Some(store) = self.store() else {
+            return Ok(());
+        };
+        if !self.replaying.lock().insert(provider_id.to_string()) {
+            return Ok(());
+        }
+        let result = self.replay(provider_id, &store).await;
+        self.replaying.lock().remove(provider_id);
+
+        result
+    }
+
+    async fn replay(&self, provider_id: &str, store: &OfflineStore) -> anyhow::Result<()> {
+        let module = self.get_provider(provider_id)?;
+        while let Some((id, write)) = store.next_pending_write(provider_id)? {
+            match write.apply(module.as_ref()).await {
+                Ok(()) => {}
+                Err(err) if ApiError::is_network_error(&err) => return Err(err),
+                // the provider rejected the write, retrying it won't change that
+                Err(err) => log::warn!("Dropping queued write for {provider_id}: {err:#}"),
+            }
+            store.remove_pending_write(id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores successful responses and falls back to the last stored one when the provider can't be reached.
+    async fn fetch<T: Serialize + DeserializeOwned>(
+        &self,
+        provider_id: &str,
+        kind: ResponseKind,
+        key: &str,
+        request: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        let result = request.await;
+        let Some(store) = self.store() else {
+            return result;
+        };
+        match result {
+            Ok(value) => {
+                if let Err(err) = store.save(provider_id, kind, key, &value) {
+                    log::warn!("Storing {kind:?} for offline use failed: {err:#}");
+                }
+                // being able to fetch means we're online again
+                if let Err(err) = self.replay_pending_writes(provider_id).await {
+                    log::warn!("Replaying queued writes for {provider_id} failed: {err:#}");
+                }
+
+                Ok(value)
+            }
+            Err(err) if ApiError::is_network_error(&err) => {
+                store.load(provider_id, kind, key)?.ok_or(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Applies the write or queues it for later when the provider can't be reached.
+    async fn write(&self, provider_id: &str, write: PendingWrite) -> anyhow::Result<()> {
+        let module = self.get_provider(provider_id)?;
+        let Some(store) = self.store() else {
+            return write.apply(module.as_ref()).await;
+        };
+        // keep the order of writes made while offline, they go behind queued ones until those are sent
+        let replayed = self.replay_pending_writes(provider_id).await;
+        let replaying = self.replaying.lock().contains(provider_id);
+        if replayed.is_err() || replaying || store.next_pending_write(provider_id)?.is_some() {
+            return store.enqueue(provider_id, &write);
+        }
+        match write.clone().apply(module.as_ref()).await {
+            Err(err) if ApiError::is_network_error(&err) => store.enqueue(provider_id, &write),
+            result => result,
+        }
+    }
+
+    fn store(&self) -> Option<Arc<OfflineStore>> {
+        self.store.read().clone()
+    }
+
+    /// Path and revision of the files marked as read locally, none without a read state store.
+    fn read_files(&self, provider_id: &str, review_id: &str) -> anyhow::Result<HashSet<(String, String)>> {
+        match self.read_state.read().clone() {
+            Some(store) => store.read_files(provider_id, review_id),
+            None => Ok(HashSet::new()),
+        }
+    }
+
+    fn set_file_read(
+        &self,
+        provider_id: String,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        read: bool,
+    ) -> anyhow::Result<()> {
+        let store = self.read_state.read().clone().ok_or_else(|| ApiError::InvalidConfig {
+            message: format!("{provider_id} doesn't track read files, open the read state store to keep them"),
+        })?;
+
+        store.set_file_read(&provider_id, &review_id, &file_path, &revision, read)
+    }
+
+    fn get_provider(&self, provider_id: &str) -> anyhow::Result<Arc<CachedModule>> {
         let modules_ref = self.modules.read();
 
-        if let Some(provider) = modules_ref.get(&provider_id) {
-            callback(provider)
+        if let Some(provider) = modules_ref.get(provider_id) {
+            Ok(provider.clone())
         } else {
-            anyhow::bail!("Unknown provider id")
+            Err(ApiError::InvalidConfig {
+                message: format!("Unknown provider id {provider_id}"),
+            }
+            .into())
         }
     }
 }
 
+async fn create_module(module: ProviderModule, connection: &ConnectionSettings) -> anyhow::Result<ApiModule> {
+    let module = match module {
+        ProviderModule::Github(github) => {
+            GithubModule::new(github.token, github.query, connection).context("Creating Github module")?.into()
+        }
+        ProviderModule::Upsource(upsource) => {
+            UpsourceModule::new(upsource.url, upsource.token, connection).into()
+        }
+        ProviderModule::Gitlab(gitlab) => {
+            GitlabModule::new(gitlab.url, gitlab.token, connection).await.context("Creating Gitlab module")?.into()
+        }
+        ProviderModule::Gitea(gitea) => {
+            GiteaModule::new(gitea.url, gitea.token, connection).context("Creating Gitea module")?.into()
+        }
+        ProviderModule::Bitbucket(bitbucket) => {
+            BitbucketModule::new(bitbucket.url, bitbucket.token, connection).context("Creating Bitbucket module")?.into()
+        }
+        ProviderModule::Gerrit(gerrit) => {
+            GerritModule::new(gerrit.url, gerrit.username, gerrit.token, connection).context("Creating Gerrit module")?.into()
+        }
+        ProviderModule::Mock(mock) => {
+            MockModule::new(mock.path).context("Creating Mock module")?.into()
+        }
+    };
+
+    Ok(module)
+}
+
 #[enum_dispatch]
+#[allow(clippy::enum_variant_names)]
 enum ApiModule {
     UpsourceModule,
     GithubModule,
     GitlabModule,
+    GiteaModule,
+    BitbucketModule,
+    GerritModule,
+    MockModule,
 }
 
+#[async_trait]
 #[enum_dispatch(ApiModule)]
-pub trait ReviewModule {
-    fn get_reviews(&self) -> anyhow::Result<Vec<Review>>;
-    fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>>;
-    fn get_review_file_summaries(
+pub trait ReviewModule: Send + Sync {
+    /// Whether the provider keeps track of read files itself, otherwise they are stored locally.
+    fn tracks_read_state(&self) -> bool {
+        true
+    }
+    /// Scopes the token needs for everything the module does.
+    fn required_scopes(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// Remaining request budget at the provider, as reported by its last response.
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit::default()
+    }
+    /// Verifies the token and returns the user it belongs to.
+    async fn get_authentication(&self) -> anyhow::Result<Authentication>;
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>>;
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>>;
+    async fn get_review_file_summaries(
         &self,
         review_id: String,
     ) -> anyhow::Result<Vec<ReviewFileSummary>>;
-    fn get_review_file_changes(
+    async fn get_review_file_changes(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
     ) -> anyhow::Result<ReviewFileChanges>;
-    fn mark_file_read(
+    async fn mark_file_read(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
         read: bool,
     ) -> anyhow::Result<()>;
+    async fn set_discussion_resolved(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()>;
+    /// Finishes the review of the current user, `body` is posted as summary comment.
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()>;
+    /// Starts a new discussion on the given line of the file in `revision`.
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()>;
+    /// Starts a new discussion which is not attached to any file.
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()>;
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()>;
 }
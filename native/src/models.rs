@@ -1,4 +1,10 @@
-#[derive(Debug, Clone)]
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Review {
     pub id: String,
     pub title: String,
@@ -7,22 +13,82 @@ pub struct Review {
     pub reviewers: Vec<User>,
     pub open: bool,
     pub state: ReviewState,
+    /// Unix timestamp in milliseconds of the last change, `None` when the provider doesn't report it
+    #[serde(default)]
+    pub updated_at: Option<u64>,
+}
+
+/// Reviews of all configured providers
+#[derive(Debug, Clone, Serialize)]
+pub struct Inbox {
+    pub reviews: Vec<InboxReview>,
+    /// Providers which failed to return their reviews
+    pub errors: Vec<ProviderError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InboxReview {
+    pub provider_id: String,
+    pub review: Review,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderError {
+    pub provider_id: String,
+    pub error: ApiError,
 }
 
+/// Change of a review noticed by the watcher
 #[derive(Debug, Clone)]
+pub enum ReviewEvent {
+    ReviewAdded {
+        provider_id: String,
+        review: Review,
+    },
+    /// The review was closed or isn't returned by the provider anymore
+    ReviewClosed {
+        provider_id: String,
+        review_id: String,
+    },
+    StateChanged {
+        provider_id: String,
+        review_id: String,
+        state: ReviewState,
+    },
+    NewComment {
+        provider_id: String,
+        review_id: String,
+        discussion_id: String,
+        comment: ReviewComment,
+    },
+    NewRevision {
+        provider_id: String,
+        review_id: String,
+        revision: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub name: String,
     pub avatar_url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ReviewState {
     Pending,
     Approved,
     Rejected,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReviewVerdict {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewDiscussion {
     pub id: String,
     pub comments: Vec<ReviewComment>,
@@ -30,15 +96,31 @@ pub struct ReviewDiscussion {
     pub file: Option<ReviewFileDiscussion>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewFileDiscussion {
     pub file_name: String,
     pub file_path: String,
     pub file_path_segments: Vec<String>,
     pub revision: Option<String>,
+    /// Lines the discussion is attached to, `None` when it refers to the whole file
+    pub lines: Option<LineRange>,
+    pub side: DiffSide,
 }
 
-#[derive(Debug, Clone)]
+/// 1-based, inclusive range of lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DiffSide {
+    Old,
+    New,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewComment {
     pub id: String,
     pub user: User,
@@ -46,7 +128,7 @@ pub struct ReviewComment {
     pub timestamp: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewFileSummary {
     pub file_name: String,
     pub file_path: String,
@@ -58,46 +140,228 @@ pub struct ReviewFileSummary {
     pub is_read: bool,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ChangeType {
     Added,
     Removed,
     Modified,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewFileChanges {
+    /// Path of the file before the change, `None` for added files
+    pub old_path: Option<String>,
+    /// Path of the file after the change, `None` for removed files
+    pub new_path: Option<String>,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub line_type: DiffLineType,
+    pub old_line_number: Option<u32>,
+    pub new_line_number: Option<u32>,
     pub text: String,
+    /// Parts of `text` which changed compared to the paired line on the other side
+    pub changed_ranges: Vec<DiffRange>,
+}
+
+/// Range in UTF-16 code units, matching string indices in Dart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DiffLineType {
+    Added,
+    Removed,
+    Context,
+}
+
+/// Request budget of a provider as last reported by it, unknown values are `None`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RateLimit {
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    /// Unix timestamp in seconds when the budget is refilled
+    pub reset_at: Option<i64>,
+}
+
+/// Outcome of checking provider settings before they are saved
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderTestReport {
+    /// Whether the provider answered at all
+    pub reachable: bool,
+    pub authenticated: bool,
+    /// The user the token belongs to
+    pub user: Option<User>,
+    /// Scopes granted to the token, `None` when the provider doesn't report them
+    pub scopes: Option<Vec<String>>,
+    /// Scopes the module needs which the token lacks
+    pub missing_scopes: Vec<String>,
+    /// Why the provider can't be used with these settings
+    pub error: Option<ApiError>,
 }
 
+/// The user a module is authenticated as
 #[derive(Debug, Clone)]
+pub struct Authentication {
+    pub user: User,
+    /// Scopes granted to the token, `None` when the provider doesn't report them
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderSettings {
     pub id: String,
     pub name: String,
     pub module: Box<ProviderModule>,
+    /// `None` uses the default timeouts and retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<ConnectionSettings>,
 }
 
-#[derive(Debug, Clone)]
+/// Timeouts and retries of the requests sent to a provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionSettings {
+    /// Seconds a single request may take before it's aborted
+    pub timeout_seconds: u32,
+    /// How often reads failing because of the network are repeated, writes are never repeated
+    pub retries: u32,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            retries: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum ProviderModule {
     Upsource(UpsourceProviderSettings),
     Github(GithubProviderSettings),
     Gitlab(GitlabProviderSettings),
+    Gitea(GiteaProviderSettings),
+    Bitbucket(BitbucketProviderSettings),
+    Gerrit(GerritProviderSettings),
+    Mock(MockProviderSettings),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UpsourceProviderSettings {
     pub url: String,
     pub token: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GithubProviderSettings {
     pub token: String,
     pub query: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GitlabProviderSettings {
     pub url: String,
     pub token: String,
 }
+
+/// Also used for Forgejo, which keeps the api of Gitea
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GiteaProviderSettings {
+    pub url: String,
+    pub token: String,
+}
+
+/// Bitbucket Server or Data Center, `token` is a personal or project access token
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BitbucketProviderSettings {
+    pub url: String,
+    pub token: String,
+}
+
+/// `token` is the http password generated in the settings of the Gerrit account named by `username`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GerritProviderSettings {
+    pub url: String,
+    pub username: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockProviderSettings {
+    /// Directory containing the fixtures
+    pub path: String,
+}
+
+/// Placeholder printed instead of tokens so they don't end up in logs.
+const REDACTED: &str = "<redacted>";
+
+impl fmt::Debug for UpsourceProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpsourceProviderSettings")
+            .field("url", &self.url)
+            .field("token", &format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl fmt::Debug for GithubProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GithubProviderSettings")
+            .field("token", &format_args!("{REDACTED}"))
+            .field("query", &self.query)
+            .finish()
+    }
+}
+
+impl fmt::Debug for GitlabProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GitlabProviderSettings")
+            .field("url", &self.url)
+            .field("token", &format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl fmt::Debug for GiteaProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GiteaProviderSettings")
+            .field("url", &self.url)
+            .field("token", &format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl fmt::Debug for BitbucketProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BitbucketProviderSettings")
+            .field("url", &self.url)
+            .field("token", &format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl fmt::Debug for GerritProviderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GerritProviderSettings")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("token", &format_args!("{REDACTED}"))
+            .finish()
+    }
+}
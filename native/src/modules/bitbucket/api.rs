@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use reqwest::header::AUTHORIZATION;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use super::messages::*;
+use super::ReviewId;
+use crate::error::ApiError;
+use crate::retry::{RetryMiddleware, RetryPolicy};
+use crate::scheduler::{Scheduler, SchedulerMiddleware};
+
+/// Items requested per page
+const PAGE_SIZE: u64 = 100;
+/// Size in pixels of the avatars linked in user objects
+const AVATAR_SIZE: &str = "64";
+/// Header with the name of the user a request was authenticated as
+const USERNAME_HEADER: &str = "x-ausername";
+
+pub struct BitbucketApi {
+    client: ClientWithMiddleware,
+    url: Url,
+    token: String,
+}
+
+impl BitbucketApi {
+    pub fn new(url: &str, token: String, scheduler: Arc<Scheduler>, policy: RetryPolicy) -> anyhow::Result<Self> {
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| ApiError::InvalidConfig { message: format!("Invalid Bitbucket url: {url}") })?;
+        let http = reqwest::Client::builder().timeout(policy.timeout).build()?;
+        let client = reqwest_middleware::ClientBuilder::new(http)
+            .with(SchedulerMiddleware(scheduler))
+            .with(RetryMiddleware(policy))
+            .build();
+
+        Ok(Self { client, url, token })
+    }
+
+    /// Bitbucket Server has no endpoint for the current user, every response names it in a header.
+    pub async fn get_current_user(&self) -> anyhow::Result<UserDTO> {
+        let response = self.send(self.client.get(self.endpoint(["inbox", "pull-requests", "count"]))).await?;
+        let name = response
+            .headers()
+            .get(USERNAME_HEADER)
+            .and_then(|name| name.to_str().ok())
+            .ok_or_else(|| ApiError::Unauthorized { message: "The request wasn't authenticated".into() })?
+            .to_string();
+        let mut url = self.endpoint(["users"]);
+        url.query_pairs_mut()
+            .append_pair("filter", &name)
+            .append_pair("avatarSize", AVATAR_SIZE);
+        let users: Vec<UserDTO> = self.get_paged(url).await?;
+
+        users
+            .into_iter()
+            .find(|user| user.name == name)
+            .ok_or_else(|| ApiError::NotFound { message: format!("Unknown user {name}") }.into())
+    }
+
+    /// Open pull requests the current user is asked to review.
+    pub async fn get_inbox(&self) -> anyhow::Result<Vec<PullRequestDTO>> {
+        let mut url = self.endpoint(["inbox", "pull-requests"]);
+        url.query_pairs_mut().append_pair("avatarSize", AVATAR_SIZE);
+
+        self.get_paged(url).await
+    }
+
+    pub async fn get_pull_request(&self, pull_request: &ReviewId) -> anyhow::Result<PullRequestDTO> {
+        self.get(self.pull_request_endpoint(pull_request, [])).await
+    }
+
+    pub async fn get_activities(&self, pull_request: &ReviewId) -> anyhow::Result<Vec<ActivityDTO>> {
+        let mut url = self.pull_request_endpoint(pull_request, ["activities"]);
+        url.query_pairs_mut().append_pair("avatarSize", AVATAR_SIZE);
+
+        self.get_paged(url).await
+    }
+
+    pub async fn get_comment(&self, pull_request: &ReviewId, comment: u64) -> anyhow::Result<CommentDTO> {
+        self.get(self.pull_request_endpoint(pull_request, ["comments", &comment.to_string()])).await
+    }
+
+    pub async fn get_changes(&self, pull_request: &ReviewId) -> anyhow::Result<Vec<ChangeDTO>> {
+        self.get_paged(self.pull_request_endpoint(pull_request, ["changes"])).await
+    }
+
+    pub async fn get_diff(&self, pull_request: &ReviewId) -> anyhow::Result<DiffDTO> {
+        let mut url = self.pull_request_endpoint(pull_request, ["diff"]);
+        url.query_pairs_mut()
+            .append_pair("contextLines", "0")
+            .append_pair("withComments", "false");
+
+        self.get(url).await
+    }
+
+    pub async fn get_raw_file(&self, review_id: &ReviewId, file_path: &str, revision: &str) -> anyhow::Result<Vec<u8>> {
+        let mut url = self.endpoint(["projects", &review_id.project, "repos", &review_id.repo, "raw"]);
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.extend(file_path.split('/'));
+        }
+        url.query_pairs_mut().append_pair("at", revision);
+        let response = self.send(self.client.get(url)).await?;
+
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    pub async fn create_comment(&self, pull_request: &ReviewId, request: CreateCommentDTO) -> anyhow::Result<()> {
+        let url = self.pull_request_endpoint(pull_request, ["comments"]);
+        self.send(self.client.post(url).json(&request)).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_comment(
+        &self,
+        pull_request: &ReviewId,
+        comment: u64,
+        request: UpdateCommentDTO,
+    ) -> anyhow::Result<()> {
+        let url = self.pull_request_endpoint(pull_request, ["comments", &comment.to_string()]);
+        self.send(self.client.put(url).json(&request)).await?;
+
+        Ok(())
+    }
+
+    pub async fn update_participant(
+        &self,
+        pull_request: &ReviewId,
+        user_slug: &str,
+        request: UpdateParticipantDTO,
+    ) -> anyhow::Result<()> {
+        let url = self.pull_request_endpoint(pull_request, ["participants", user_slug]);
+        self.send(self.client.put(url).json(&request)).await?;
+
+        Ok(())
+    }
+
+    /// Links to avatars are relative unless they are hosted elsewhere.
+    pub fn absolute_url(&self, url: &str) -> Option<String> {
+        self.url.join(url).ok().map(String::from)
+    }
+
+    /// Url of an endpoint below `/rest/api/1.0`, the segments are escaped.
+    fn endpoint<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> Url {
+        let mut url = self.url.clone();
+        // urls which can't be a base are rejected in `new`
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(["rest", "api", "1.0"]).extend(segments);
+        }
+
+        url
+    }
+
+    fn pull_request_endpoint<const N: usize>(&self, pull_request: &ReviewId, segments: [&str; N]) -> Url {
+        let id = pull_request.id.to_string();
+        let path = ["projects", &pull_request.project, "repos", &pull_request.repo, "pull-requests", &id];
+
+        self.endpoint(path.into_iter().chain(segments))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<T> {
+        let response = self.send(self.client.get(url)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    async fn get_paged<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<Vec<T>> {
+        let mut items = vec![];
+        let mut start = 0;
+        loop {
+            let mut url = url.clone();
+            url.query_pairs_mut()
+                .append_pair("start", &start.to_string())
+                .append_pair("limit", &PAGE_SIZE.to_string());
+            let page: PageDTO<T> = self.get(url).await?;
+            items.extend(page.values);
+            match page.next_page_start {
+                Some(next) if !page.is_last_page => start = next,
+                _ => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let response = request
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::from_status(status.as_u16(), format!("{status}: {body}"), None).into());
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+
+/// One page of a paged resource
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageDTO<T> {
+    pub values: Vec<T>,
+    pub is_last_page: bool,
+    pub next_page_start: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDTO {
+    pub name: String,
+    pub slug: String,
+    #[serde(default)]
+    pub display_name: String,
+    /// Only set when the avatar was requested, may be relative to the server
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestDTO {
+    pub id: u64,
+    pub title: String,
+    pub open: bool,
+    pub from_ref: RefDTO,
+    pub to_ref: RefDTO,
+    pub author: ParticipantDTO,
+    #[serde(default)]
+    pub reviewers: Vec<ParticipantDTO>,
+    /// Users who took part without being asked to review
+    #[serde(default)]
+    pub participants: Vec<ParticipantDTO>,
+    pub updated_date: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefDTO {
+    pub display_id: String,
+    pub latest_commit: String,
+    pub repository: RepositoryDTO,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RepositoryDTO {
+    pub slug: String,
+    pub project: ProjectDTO,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectDTO {
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParticipantDTO {
+    pub user: UserDTO,
+    pub status: ParticipantStatusDTO,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ParticipantStatusDTO {
+    Approved,
+    NeedsWork,
+    Unapproved,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateParticipantDTO {
+    pub status: ParticipantStatusDTO,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityDTO {
+    pub action: String,
+    pub comment_action: Option<String>,
+    pub comment: Option<CommentDTO>,
+    pub comment_anchor: Option<CommentAnchorDTO>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentDTO {
+    pub id: u64,
+    pub version: u64,
+    pub text: String,
+    pub author: UserDTO,
+    /// Unix timestamp in milliseconds
+    pub created_date: u64,
+    /// Replies, which can have replies of their own
+    #[serde(default)]
+    pub comments: Vec<CommentDTO>,
+    #[serde(default)]
+    pub thread_resolved: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentAnchorDTO {
+    pub path: String,
+    /// Missing for comments on the whole file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_type: Option<LineTypeDTO>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<FileTypeDTO>,
+    pub from_hash: String,
+    pub to_hash: String,
+    #[serde(default = "effective_diff")]
+    pub diff_type: String,
+}
+
+fn effective_diff() -> String {
+    "EFFECTIVE".into()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LineTypeDTO {
+    Added,
+    Removed,
+    Context,
+}
+
+/// Side of the diff a line is on, `From` is the old version
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FileTypeDTO {
+    From,
+    To,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateCommentDTO {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<CommentAnchorDTO>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<CommentParentDTO>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentParentDTO {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCommentDTO {
+    pub version: u64,
+    pub thread_resolved: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeDTO {
+    pub path: PathDTO,
+    /// Previous path of moved and copied files
+    pub src_path: Option<PathDTO>,
+    #[serde(rename = "type")]
+    pub change_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PathDTO {
+    #[serde(rename = "toString")]
+    pub path: String,
+}
+
+/// Diff of the whole pull request, used for line counts and the commit it starts from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffDTO {
+    /// Commit the changes are compared against
+    pub from_hash: String,
+    #[serde(default)]
+    pub diffs: Vec<FileDiffDTO>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileDiffDTO {
+    pub source: Option<PathDTO>,
+    pub destination: Option<PathDTO>,
+    #[serde(default)]
+    pub hunks: Vec<HunkDTO>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HunkDTO {
+    #[serde(default)]
+    pub segments: Vec<SegmentDTO>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SegmentDTO {
+    #[serde(rename = "type")]
+    pub segment_type: LineTypeDTO,
+    #[serde(default)]
+    pub lines: Vec<serde_json::Value>,
+}
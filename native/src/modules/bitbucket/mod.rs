@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::cache::Cache;
+use crate::diff::diff_files;
+use crate::error::ApiError;
+use crate::models::*;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::util::split_file_name;
+use crate::ReviewModule;
+
+use self::api::BitbucketApi;
+use self::messages::*;
+
+mod api;
+mod messages;
+
+/// Works with Bitbucket Server and Data Center, Bitbucket Cloud has a different api.
+pub struct BitbucketModule {
+    api: BitbucketApi,
+    scheduler: Arc<Scheduler>,
+    /// The user the token belongs to, needed to set the status of the review
+    current_user: Cache<(), UserDTO>,
+    /// File contents by path and commit, `None` for binary files
+    file_contents: Cache<(String, String), Option<String>>,
+}
+
+impl BitbucketModule {
+    pub fn new(url: String, token: String, connection: &ConnectionSettings) -> anyhow::Result<Self> {
+        let scheduler = Arc::new(Scheduler::new());
+        let api = BitbucketApi::new(&url, token, scheduler.clone(), RetryPolicy::from(connection))?;
+
+        Ok(Self {
+            api,
+            scheduler,
+            current_user: Cache::permanent(),
+            file_contents: Cache::permanent(),
+        })
+    }
+
+    async fn get_current_user(&self) -> anyhow::Result<UserDTO> {
+        self.current_user.get_or_fetch((), self.api.get_current_user()).await
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        let pull_requests = self.api.get_inbox().await?;
+        let reviews = pull_requests
+            .into_iter()
+            .map(|pull_request| {
+                let participants = pull_request.reviewers.iter().chain(&pull_request.participants);
+                let state = review_state(participants.map(|participant| participant.status));
+                let repository = &pull_request.to_ref.repository;
+                let id = ReviewId {
+                    project: repository.project.key.clone(),
+                    repo: repository.slug.clone(),
+                    id: pull_request.id,
+                };
+
+                Review {
+                    id: id.to_string(),
+                    title: pull_request.title,
+                    branch_name: pull_request.from_ref.display_id,
+                    authors: vec![self.user(pull_request.author.user)],
+                    reviewers: pull_request
+                        .reviewers
+                        .into_iter()
+                        .map(|reviewer| self.user(reviewer.user))
+                        .collect(),
+                    open: pull_request.open,
+                    state,
+                    updated_at: pull_request.updated_date,
+                }
+            })
+            .collect();
+
+        Ok(reviews)
+    }
+
+    async fn get_review_discussions(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        let activities = self.api.get_activities(&review_id).await?;
+        // the activity adding a comment stays after it is deleted, a separate one records the deletion
+        let deleted = activities
+            .iter()
+            .filter(|activity| activity.action == "COMMENTED" && activity.comment_action.as_deref() == Some("DELETED"))
+            .filter_map(|activity| Some(activity.comment.as_ref()?.id))
+            .collect::<HashSet<_>>();
+        // replies are part of the comment they answer, only the activities adding a thread are needed
+        let discussions = activities
+            .into_iter()
+            .filter(|activity| activity.action == "COMMENTED" && activity.comment_action.as_deref() == Some("ADDED"))
+            .filter_map(|activity| {
+                let comment = activity.comment.filter(|comment| !deleted.contains(&comment.id))?;
+                let file = activity.comment_anchor.map(|anchor| {
+                    let (file_path_segments, file_name) = split_file_name(&anchor.path);
+                    let (side, revision) = match anchor.file_type {
+                        Some(FileTypeDTO::From) => (DiffSide::Old, anchor.from_hash),
+                        _ => (DiffSide::New, anchor.to_hash),
+                    };
+                    ReviewFileDiscussion {
+                        file_name,
+                        file_path: anchor.path,
+                        file_path_segments,
+                        revision: Some(revision),
+                        lines: anchor.line.map(|line| LineRange { start: line, end: line }),
+                        side,
+                    }
+                });
+                let id = comment.id.to_string();
+                let resolved = comment.thread_resolved;
+                let mut comments = vec![];
+                flatten_thread(comment, &mut comments);
+                comments.retain(|comment| !deleted.contains(&comment.id));
+                comments.sort_by_key(|comment| comment.created_date);
+
+                Some(ReviewDiscussion {
+                    id,
+                    resolved,
+                    file,
+                    comments: comments
+                        .into_iter()
+                        .map(|comment| ReviewComment {
+                            id: comment.id.to_string(),
+                            user: self.user(comment.author),
+                            text: comment.text,
+                            timestamp: comment.created_date,
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Ok(discussions)
+    }
+
+    async fn get_review_file_summaries(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        let pull_request = self.api.get_pull_request(&review_id);
+        let changes = self.api.get_changes(&review_id);
+        let diff = self.api.get_diff(&review_id);
+        let (pull_request, changes, diff) = futures::future::try_join3(pull_request, changes, diff).await?;
+
+        // the changes don't include line counts, the diff without context lines has them
+        let line_counts = diff
+            .diffs
+            .into_iter()
+            .filter_map(|file| {
+                let path = file.destination.or(file.source)?.path;
+                let count = |line_type| {
+                    file.hunks
+                        .iter()
+                        .flat_map(|hunk| &hunk.segments)
+                        .filter(|segment| segment.segment_type == line_type)
+                        .map(|segment| segment.lines.len() as u32)
+                        .sum::<u32>()
+                };
+
+                Some((path, (count(LineTypeDTO::Added), count(LineTypeDTO::Removed))))
+            })
+            .collect::<HashMap<_, _>>();
+        let files = changes
+            .into_iter()
+            .map(|change| {
+                let (file_path_segments, file_name) = split_file_name(&change.path.path);
+                let (added_lines, removed_lines) = line_counts.get(&change.path.path).copied().unwrap_or_default();
+                ReviewFileSummary {
+                    file_name,
+                    file_path_segments,
+                    change_type: match change.change_type.as_str() {
+                        "ADD" => ChangeType::Added,
+                        "DELETE" => ChangeType::Removed,
+                        _ => ChangeType::Modified,
+                    },
+                    file_path: change.path.path,
+                    added_lines,
+                    removed_lines,
+                    is_read: false,
+                    revision_id: pull_request.from_ref.latest_commit.clone(),
+                }
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    /// Returns the changes with the commit they are compared against.
+    async fn get_review_file_changes(
+        &self,
+        review_id: &ReviewId,
+        file_path: &str,
+        revision: &str,
+    ) -> anyhow::Result<(String, ReviewFileChanges)> {
+        let changes = self.api.get_changes(review_id);
+        let diff = self.api.get_diff(review_id);
+        let (changes, diff) = futures::future::try_join(changes, diff).await?;
+        let change = changes
+            .into_iter()
+            .find(|change| change.path.path == file_path)
+            .ok_or_else(|| ApiError::NotFound { message: "File is not part of the review".into() })?;
+        let old_path = match change.change_type.as_str() {
+            "ADD" => None,
+            _ => Some(change.src_path.unwrap_or_else(|| change.path.clone()).path),
+        };
+        let new_path = (change.change_type != "DELETE").then_some(change.path.path);
+        let base_revision = diff.from_hash;
+
+        let old_content = async {
+            match old_path.as_ref() {
+                Some(path) => self.get_file_content(review_id, path, &base_revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let new_content = async {
+            match new_path.as_ref() {
+                Some(path) => self.get_file_content(review_id, path, revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (old_content, new_content) = futures::future::try_join(old_content, new_content).await?;
+        let hunks = match (old_content, new_content) {
+            (Some(None), _) | (_, Some(None)) => vec![],
+            (old, new) => diff_files(old.flatten().as_deref(), new.flatten().as_deref()),
+        };
+        let changes = ReviewFileChanges {
+            old_path,
+            new_path,
+            hunks,
+        };
+
+        Ok((base_revision, changes))
+    }
+
+    /// Returns `None` for binary files
+    async fn get_file_content(&self, review_id: &ReviewId, file_path: &str, revision: &str) -> anyhow::Result<Option<String>> {
+        let key = (file_path.to_string(), revision.to_string());
+        let content = async {
+            let content = self.api.get_raw_file(review_id, file_path, revision).await?;
+
+            anyhow::Ok(String::from_utf8(content).ok())
+        };
+
+        self.file_contents.get_or_fetch(key, content).await
+    }
+
+    async fn set_discussion_resolved(&self, review_id: ReviewId, discussion_id: String, resolved: bool) -> anyhow::Result<()> {
+        let discussion_id: u64 = discussion_id.parse()?;
+        // updates have to name the version they are based on
+        let comment = self.api.get_comment(&review_id, discussion_id).await?;
+        let request = UpdateCommentDTO {
+            version: comment.version,
+            thread_resolved: resolved,
+        };
+
+        self.api.update_comment(&review_id, discussion_id, request).await
+    }
+
+    async fn submit_review_verdict(&self, review_id: ReviewId, verdict: ReviewVerdict, body: Option<String>) -> anyhow::Result<()> {
+        let status = match verdict {
+            ReviewVerdict::Approve => Some(ParticipantStatusDTO::Approved),
+            ReviewVerdict::RequestChanges => Some(ParticipantStatusDTO::NeedsWork),
+            ReviewVerdict::Comment => None,
+        };
+        if let Some(status) = status {
+            let user = self.get_current_user().await?;
+            self.api
+                .update_participant(&review_id, &user.slug, UpdateParticipantDTO { status })
+                .await?;
+        }
+        if let Some(body) = body.filter(|body| !body.is_empty()) {
+            self.create_review_discussion(review_id, body).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        // Bitbucket rejects anchors whose line type doesn't match the diff
+        let (base_revision, changes) = self.get_review_file_changes(&review_id, &file_path, &revision).await?;
+        let line_type = changes
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .find(|diff_line| diff_line.new_line_number == Some(line))
+            .map(|diff_line| match diff_line.line_type {
+                DiffLineType::Added => LineTypeDTO::Added,
+                DiffLineType::Removed => LineTypeDTO::Removed,
+                DiffLineType::Context => LineTypeDTO::Context,
+            })
+            .unwrap_or(LineTypeDTO::Context);
+        let request = CreateCommentDTO {
+            text,
+            anchor: Some(CommentAnchorDTO {
+                path: file_path,
+                line: Some(line),
+                line_type: Some(line_type),
+                file_type: Some(FileTypeDTO::To),
+                from_hash: base_revision,
+                to_hash: revision,
+                diff_type: "EFFECTIVE".into(),
+            }),
+            parent: None,
+        };
+
+        self.api.create_comment(&review_id, request).await
+    }
+
+    async fn create_review_discussion(&self, review_id: ReviewId, text: String) -> anyhow::Result<()> {
+        let request = CreateCommentDTO {
+            text,
+            anchor: None,
+            parent: None,
+        };
+
+        self.api.create_comment(&review_id, request).await
+    }
+
+    async fn reply_to_discussion(&self, review_id: ReviewId, discussion_id: String, text: String) -> anyhow::Result<()> {
+        let request = CreateCommentDTO {
+            text,
+            anchor: None,
+            parent: Some(CommentParentDTO { id: discussion_id.parse()? }),
+        };
+
+        self.api.create_comment(&review_id, request).await
+    }
+
+    fn user(&self, user: UserDTO) -> User {
+        User {
+            name: if user.display_name.is_empty() { user.name } else { user.display_name },
+            avatar_url: user.avatar_url.and_then(|url| self.api.absolute_url(&url)),
+        }
+    }
+}
+
+#[async_trait]
+impl ReviewModule for BitbucketModule {
+    fn tracks_read_state(&self) -> bool {
+        false
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.scheduler.rate_limit()
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let user = self.get_current_user().await?;
+
+        // access tokens have permissions per project or repository instead of scopes
+        Ok(Authentication {
+            user: self.user(user),
+            scopes: None,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.get_reviews().await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.get_review_discussions(review_id.parse()?).await
+    }
+
+    async fn get_review_file_summaries(&self, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        self.get_review_file_summaries(review_id.parse()?).await
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        let (_, changes) = self.get_review_file_changes(&review_id.parse()?, &file_path, &revision).await?;
+
+        Ok(changes)
+    }
+
+    async fn mark_file_read(&self, _review_id: String, _file_path: String, _revision: String, _read: bool) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Bitbucket has no api for viewed files, read files are tracked locally"))
+    }
+
+    async fn set_discussion_resolved(&self, review_id: String, discussion_id: String, resolved: bool) -> anyhow::Result<()> {
+        self.set_discussion_resolved(review_id.parse()?, discussion_id, resolved).await
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.submit_review_verdict(review_id.parse()?, verdict, body).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.create_file_discussion(review_id.parse()?, file_path, revision, line, text).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.create_review_discussion(review_id.parse()?, text).await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.reply_to_discussion(review_id.parse()?, discussion_id, text).await
+    }
+}
+
+/// A review needs work as soon as one participant says so, otherwise a single approval approves it.
+fn review_state(statuses: impl Iterator<Item = ParticipantStatusDTO>) -> ReviewState {
+    let statuses = statuses.collect::<Vec<_>>();
+    if statuses.contains(&ParticipantStatusDTO::NeedsWork) {
+        ReviewState::Rejected
+    } else if statuses.contains(&ParticipantStatusDTO::Approved) {
+        ReviewState::Approved
+    } else {
+        ReviewState::Pending
+    }
+}
+
+/// Collects a comment and all replies below it.
+fn flatten_thread(mut comment: CommentDTO, comments: &mut Vec<CommentDTO>) {
+    let replies = std::mem::take(&mut comment.comments);
+    comments.push(comment);
+    for reply in replies {
+        flatten_thread(reply, comments);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ReviewId {
+    project: String,
+    repo: String,
+    id: u64,
+}
+
+impl FromStr for ReviewId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let result = s.split('/').collect::<Vec<_>>();
+        anyhow::ensure!(result.len() == 3, "Invalid review id format");
+
+        Ok(ReviewId {
+            project: result[0].to_string(),
+            repo: result[1].to_string(),
+            id: result[2].parse()?,
+        })
+    }
+}
+
+impl Display for ReviewId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.project, self.repo, self.id)
+    }
+}
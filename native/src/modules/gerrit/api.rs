@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::prelude::*;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::Url;
+
+use super::messages::*;
+use super::ReviewId;
+use crate::error::ApiError;
+use crate::retry::{RetryMiddleware, RetryPolicy};
+use crate::scheduler::{Scheduler, SchedulerMiddleware};
+
+/// Changes requested per page
+const PAGE_SIZE: usize = 100;
+/// Prepended to every json response to prevent XSSI, it's not valid json
+const JSON_PREFIX: &[u8] = b")]}'";
+/// Changes shown in the inbox
+const INBOX_QUERY: &str = "is:open (owner:self OR reviewer:self)";
+
+pub struct GerritApi {
+    client: ClientWithMiddleware,
+    url: Url,
+    username: String,
+    token: String,
+}
+
+impl GerritApi {
+    pub fn new(
+        url: &str,
+        username: String,
+        token: String,
+        scheduler: Arc<Scheduler>,
+        policy: RetryPolicy,
+    ) -> anyhow::Result<Self> {
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| ApiError::InvalidConfig { message: format!("Invalid Gerrit url: {url}") })?;
+        let http = reqwest::Client::builder().timeout(policy.timeout).build()?;
+        let client = reqwest_middleware::ClientBuilder::new(http)
+            .with(SchedulerMiddleware(scheduler))
+            .with(RetryMiddleware(policy))
+            .build();
+
+        Ok(Self {
+            client,
+            url,
+            username,
+            token,
+        })
+    }
+
+    pub async fn get_current_account(&self) -> anyhow::Result<AccountInfo> {
+        self.get(self.endpoint(["accounts", "self"])).await
+    }
+
+    pub async fn get_inbox(&self) -> anyhow::Result<Vec<ChangeInfo>> {
+        let mut changes = vec![];
+        loop {
+            let mut url = self.endpoint(["changes", ""]);
+            url.query_pairs_mut()
+                .append_pair("q", INBOX_QUERY)
+                .append_pair("o", "DETAILED_ACCOUNTS")
+                .append_pair("o", "DETAILED_LABELS")
+                .append_pair("n", &PAGE_SIZE.to_string())
+                .append_pair("S", &changes.len().to_string());
+            let page: Vec<ChangeInfo> = self.get(url).await?;
+            let more = page.last().is_some_and(|change| change.more_changes);
+            changes.extend(page);
+            if !more {
+                break;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// The change with its votes and current patch set.
+    pub async fn get_change(&self, change: &ReviewId) -> anyhow::Result<ChangeInfo> {
+        let mut url = self.change_endpoint(change, []);
+        url.query_pairs_mut()
+            .append_pair("o", "DETAILED_ACCOUNTS")
+            .append_pair("o", "DETAILED_LABELS")
+            .append_pair("o", "CURRENT_REVISION");
+
+        self.get(url).await
+    }
+
+    /// Comments by the file they are on.
+    pub async fn get_comments(&self, change: &ReviewId) -> anyhow::Result<HashMap<String, Vec<CommentInfo>>> {
+        self.get(self.change_endpoint(change, ["comments"])).await
+    }
+
+    pub async fn get_files(&self, change: &ReviewId, revision: &str) -> anyhow::Result<HashMap<String, FileInfo>> {
+        self.get(self.change_endpoint(change, ["revisions", revision, "files"])).await
+    }
+
+    /// Paths of the files the current user marked as reviewed.
+    pub async fn get_reviewed_files(&self, change: &ReviewId, revision: &str) -> anyhow::Result<Vec<String>> {
+        let mut url = self.change_endpoint(change, ["revisions", revision, "files"]);
+        url.query_pairs_mut().append_key_only("reviewed");
+
+        self.get(url).await
+    }
+
+    /// Content of the file in the patch set, or in the commit it's based on with `parent`.
+    pub async fn get_file_content(
+        &self,
+        change: &ReviewId,
+        revision: &str,
+        file_path: &str,
+        parent: bool,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut url = self.change_endpoint(change, ["revisions", revision, "files", file_path, "content"]);
+        if parent {
+            url.query_pairs_mut().append_pair("parent", "1");
+        }
+        let response = self.send(self.client.get(url)).await?;
+        let content = response.bytes().await?;
+
+        // file contents are sent as base64 without the json prefix
+        Ok(BASE64_STANDARD.decode(content.trim_ascii())?)
+    }
+
+    pub async fn set_file_reviewed(
+        &self,
+        change: &ReviewId,
+        revision: &str,
+        file_path: &str,
+        reviewed: bool,
+    ) -> anyhow::Result<()> {
+        let url = self.change_endpoint(change, ["revisions", revision, "files", file_path, "reviewed"]);
+        let request = match reviewed {
+            true => self.client.put(url),
+            false => self.client.delete(url),
+        };
+        self.send(request).await?;
+
+        Ok(())
+    }
+
+    pub async fn set_review(&self, change: &ReviewId, revision: &str, review: ReviewInput) -> anyhow::Result<()> {
+        self.post(self.change_endpoint(change, ["revisions", revision, "review"]), &review).await
+    }
+
+    /// Url of an endpoint below `/a`, which requires authentication, the segments are escaped.
+    fn endpoint<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> Url {
+        let mut url = self.url.clone();
+        // urls which can't be a base are rejected in `new`
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().push("a").extend(segments);
+        }
+
+        url
+    }
+
+    /// Changes are identified by project and number, the project may contain slashes which end up escaped.
+    fn change_endpoint<const N: usize>(&self, change: &ReviewId, segments: [&str; N]) -> Url {
+        let id = format!("{}~{}", change.project, change.number);
+
+        self.endpoint(["changes", &id].into_iter().chain(segments))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<T> {
+        let response = self.send(self.client.get(url)).await?;
+
+        parse(&response.bytes().await?)
+    }
+
+    async fn post(&self, url: Url, body: &impl Serialize) -> anyhow::Result<()> {
+        self.send(self.client.post(url).json(body)).await?;
+
+        Ok(())
+    }
+
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let response = request
+            .basic_auth(&self.username, Some(&self.token))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::from_status(status.as_u16(), format!("{status}: {}", body.trim()), None).into());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Strips the prefix Gerrit puts in front of json responses.
+fn parse<T: DeserializeOwned>(body: &[u8]) -> anyhow::Result<T> {
+    let body = body.strip_prefix(JSON_PREFIX).unwrap_or(body);
+
+    Ok(serde_json::from_slice(body)?)
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Label voted on during code review
+pub const CODE_REVIEW: &str = "Code-Review";
+/// Pseudo file of comments on the whole patch set
+pub const PATCHSET_LEVEL: &str = "/PATCHSET_LEVEL";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountInfo {
+    #[serde(rename = "_account_id")]
+    pub account_id: u64,
+    pub name: Option<String>,
+    pub username: Option<String>,
+    #[serde(default)]
+    pub avatars: Vec<AvatarInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvatarInfo {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChangeInfo {
+    pub project: String,
+    pub subject: String,
+    pub status: String,
+    #[serde(rename = "_number")]
+    pub number: u64,
+    pub branch: String,
+    pub owner: AccountInfo,
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub updated: i64,
+    #[serde(default)]
+    pub labels: HashMap<String, LabelInfo>,
+    /// Votes the current user may give, by label
+    #[serde(default)]
+    pub permitted_labels: HashMap<String, Vec<String>>,
+    /// Accounts by their state, e.g. `REVIEWER` or `CC`
+    #[serde(default)]
+    pub reviewers: HashMap<String, Vec<AccountInfo>>,
+    pub current_revision: Option<String>,
+    #[serde(default)]
+    pub revisions: HashMap<String, RevisionInfo>,
+    /// Set on the last change of a page when there are more
+    #[serde(rename = "_more_changes", default)]
+    pub more_changes: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabelInfo {
+    /// Votes of all reviewers, `0` when they didn't vote yet
+    #[serde(default)]
+    pub all: Vec<ApprovalInfo>,
+    /// Descriptions by vote, e.g. `+2` or ` 0`
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApprovalInfo {
+    pub value: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevisionInfo {
+    #[serde(rename = "_number")]
+    pub number: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileInfo {
+    /// `A`dded, `D`eleted, `R`enamed, `C`opied or re`W`ritten, missing for modified files
+    pub status: Option<char>,
+    pub old_path: Option<String>,
+    #[serde(default)]
+    pub lines_inserted: u32,
+    #[serde(default)]
+    pub lines_deleted: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommentInfo {
+    pub id: String,
+    pub patch_set: Option<u32>,
+    /// Missing for comments on the whole file
+    pub line: Option<u32>,
+    pub range: Option<CommentRange>,
+    /// `PARENT` for comments on the old version, missing for the new one
+    pub side: Option<String>,
+    pub in_reply_to: Option<String>,
+    #[serde(default)]
+    pub message: String,
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub updated: i64,
+    pub author: Option<AccountInfo>,
+    #[serde(default)]
+    pub unresolved: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentRange {
+    pub start_line: u32,
+    pub start_character: u32,
+    pub end_line: u32,
+    pub end_character: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReviewInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, i32>,
+    /// New comments by the file they are on
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub comments: HashMap<String, Vec<CommentInput>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommentInput {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<CommentRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    /// Replies take over the state of the comment they answer when missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unresolved: Option<bool>,
+}
+
+/// Gerrit timestamps are UTC in the format `2013-02-26 15:40:43.986000000`, returned in milliseconds.
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let timestamp = NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S%.f").map_err(serde::de::Error::custom)?;
+
+    Ok(Utc.from_utc_datetime(&timestamp).timestamp_millis())
+}
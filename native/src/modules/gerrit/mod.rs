@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::cache::Cache;
+use crate::diff::diff_files;
+use crate::error::ApiError;
+use crate::models::*;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::util::split_file_name;
+use crate::ReviewModule;
+
+use self::api::GerritApi;
+use self::messages::*;
+
+mod api;
+mod messages;
+
+/// Changes are the reviews, their patch sets the revisions.
+pub struct GerritModule {
+    api: GerritApi,
+    scheduler: Arc<Scheduler>,
+    /// File contents by change, path, patch set and whether it's the parent version, `None` for binary files
+    file_contents: Cache<(String, String, String, bool), Option<String>>,
+}
+
+/// Comments answering each other, starting with the one they answer
+struct Thread {
+    path: String,
+    comments: Vec<CommentInfo>,
+}
+
+impl GerritModule {
+    pub fn new(url: String, username: String, token: String, connection: &ConnectionSettings) -> anyhow::Result<Self> {
+        let scheduler = Arc::new(Scheduler::new());
+        let api = GerritApi::new(&url, username, token, scheduler.clone(), RetryPolicy::from(connection))?;
+
+        Ok(Self {
+            api,
+            scheduler,
+            file_contents: Cache::permanent(),
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        let changes = self.api.get_inbox().await?;
+        let reviews = changes
+            .into_iter()
+            .map(|mut change| {
+                let id = ReviewId {
+                    project: change.project.clone(),
+                    number: change.number,
+                };
+                let owner = change.owner.account_id;
+
+                Review {
+                    id: id.to_string(),
+                    state: code_review_state(change.labels.get(CODE_REVIEW)),
+                    title: change.subject,
+                    branch_name: change.branch,
+                    authors: vec![change.owner.into()],
+                    reviewers: change
+                        .reviewers
+                        .remove("REVIEWER")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|reviewer| reviewer.account_id != owner)
+                        .map(User::from)
+                        .collect(),
+                    open: change.status == "NEW",
+                    updated_at: Some(change.updated as u64),
+                }
+            })
+            .collect();
+
+        Ok(reviews)
+    }
+
+    async fn get_threads(&self, review_id: &ReviewId) -> anyhow::Result<Vec<Thread>> {
+        let comments = self.api.get_comments(review_id).await?;
+
+        Ok(comments
+            .into_iter()
+            .flat_map(|(path, comments)| build_threads(path, comments))
+            .collect())
+    }
+
+    async fn get_review_discussions(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        let threads = self.get_threads(&review_id).await?;
+        let discussions = threads
+            .into_iter()
+            .filter_map(|thread| {
+                let root = thread.comments.first()?;
+                let last = thread.comments.last()?;
+                let file = (thread.path != PATCHSET_LEVEL).then(|| {
+                    let (file_path_segments, file_name) = split_file_name(&thread.path);
+                    let lines = match (&root.range, root.line) {
+                        (Some(range), _) => Some(LineRange {
+                            start: range.start_line,
+                            end: range.end_line,
+                        }),
+                        (None, Some(line)) => Some(LineRange { start: line, end: line }),
+                        (None, None) => None,
+                    };
+                    ReviewFileDiscussion {
+                        file_name,
+                        file_path: thread.path.clone(),
+                        file_path_segments,
+                        revision: root.patch_set.map(|patch_set| patch_set.to_string()),
+                        lines,
+                        side: match root.side.as_deref() {
+                            Some("PARENT") => DiffSide::Old,
+                            _ => DiffSide::New,
+                        },
+                    }
+                });
+
+                Some(ReviewDiscussion {
+                    id: root.id.clone(),
+                    // the latest comment decides whether the thread is resolved
+                    resolved: !last.unresolved,
+                    file,
+                    comments: thread.comments.into_iter().map(ReviewComment::from).collect(),
+                })
+            })
+            .collect();
+
+        Ok(discussions)
+    }
+
+    async fn get_review_file_summaries(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        let change = self.api.get_change(&review_id).await?;
+        let revision = current_patch_set(&change)?;
+        let files = self.api.get_files(&review_id, &revision);
+        let reviewed = self.api.get_reviewed_files(&review_id, &revision);
+        let (files, reviewed) = futures::future::try_join(files, reviewed).await?;
+
+        let files = files
+            .into_iter()
+            // magic files like `/COMMIT_MSG` start with a slash
+            .filter(|(path, _)| !path.starts_with('/'))
+            .map(|(path, file)| {
+                let (file_path_segments, file_name) = split_file_name(&path);
+                ReviewFileSummary {
+                    file_name,
+                    file_path_segments,
+                    is_read: reviewed.contains(&path),
+                    file_path: path,
+                    change_type: match file.status {
+                        Some('A') => ChangeType::Added,
+                        Some('D') => ChangeType::Removed,
+                        _ => ChangeType::Modified,
+                    },
+                    added_lines: file.lines_inserted,
+                    removed_lines: file.lines_deleted,
+                    revision_id: revision.clone(),
+                }
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        let mut files = self.api.get_files(&review_id, &revision).await?;
+        let file = files
+            .remove(&file_path)
+            .ok_or_else(|| ApiError::NotFound { message: "File is not part of the review".into() })?;
+        let old_path = match file.status {
+            Some('A') => None,
+            _ => Some(file.old_path.unwrap_or_else(|| file_path.clone())),
+        };
+        let new_path = (file.status != Some('D')).then_some(file_path);
+
+        // the old version is the one in the commit the patch set is based on
+        let old_content = async {
+            match old_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &revision, true).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let new_content = async {
+            match new_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &revision, false).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (old_content, new_content) = futures::future::try_join(old_content, new_content).await?;
+        let hunks = match (old_content, new_content) {
+            (Some(None), _) | (_, Some(None)) => vec![],
+            (old, new) => diff_files(old.flatten().as_deref(), new.flatten().as_deref()),
+        };
+
+        Ok(ReviewFileChanges {
+            old_path,
+            new_path,
+            hunks,
+        })
+    }
+
+    /// Returns `None` for binary files
+    async fn get_file_content(
+        &self,
+        review_id: &ReviewId,
+        file_path: &str,
+        revision: &str,
+        parent: bool,
+    ) -> anyhow::Result<Option<String>> {
+        // patch set numbers are only unique within a change
+        let key = (review_id.to_string(), file_path.to_string(), revision.to_string(), parent);
+        let content = async {
+            let content = self.api.get_file_content(review_id, revision, file_path, parent).await?;
+
+            anyhow::Ok(String::from_utf8(content).ok())
+        };
+
+        self.file_contents.get_or_fetch(key, content).await
+    }
+
+    async fn submit_review_verdict(&self, review_id: ReviewId, verdict: ReviewVerdict, body: Option<String>) -> anyhow::Result<()> {
+        let mut labels = HashMap::new();
+        match verdict {
+            ReviewVerdict::Approve => {
+                // the highest vote the user may give, not everybody can approve on their own
+                let change = self.api.get_change(&review_id).await?;
+                let vote = change
+                    .permitted_labels
+                    .get(CODE_REVIEW)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|value| value.trim().parse::<i32>().ok())
+                    .max()
+                    .filter(|vote| *vote > 0)
+                    .ok_or_else(|| ApiError::Forbidden { message: "Not allowed to approve the change".into() })?;
+                labels.insert(CODE_REVIEW.to_string(), vote);
+            }
+            // -2 blocks the change, which is left to the Gerrit ui
+            ReviewVerdict::RequestChanges => {
+                labels.insert(CODE_REVIEW.to_string(), -1);
+            }
+            ReviewVerdict::Comment => {}
+        }
+        let review = ReviewInput {
+            message: body.filter(|body| !body.is_empty()),
+            labels,
+            comments: HashMap::new(),
+        };
+
+        self.api.set_review(&review_id, "current", review).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let comment = CommentInput {
+            message: text,
+            line: Some(line),
+            unresolved: Some(true),
+            ..Default::default()
+        };
+        let review = ReviewInput {
+            comments: HashMap::from([(file_path, vec![comment])]),
+            ..Default::default()
+        };
+
+        self.api.set_review(&review_id, &revision, review).await
+    }
+
+    async fn create_review_discussion(&self, review_id: ReviewId, text: String) -> anyhow::Result<()> {
+        // patch set level comments form threads, unlike the messages of a review
+        let comment = CommentInput {
+            message: text,
+            unresolved: Some(false),
+            ..Default::default()
+        };
+        let review = ReviewInput {
+            comments: HashMap::from([(PATCHSET_LEVEL.to_string(), vec![comment])]),
+            ..Default::default()
+        };
+
+        self.api.set_review(&review_id, "current", review).await
+    }
+
+    /// Answers the latest comment of the thread, `unresolved` keeps its state when not given.
+    async fn reply(&self, review_id: ReviewId, discussion_id: String, text: String, unresolved: Option<bool>) -> anyhow::Result<()> {
+        let threads = self.get_threads(&review_id).await?;
+        let thread = threads
+            .into_iter()
+            .find(|thread| thread.comments.first().is_some_and(|root| root.id == discussion_id))
+            .ok_or_else(|| ApiError::NotFound { message: "Unknown discussion id".into() })?;
+        let root = &thread.comments[0];
+        let last = &thread.comments[thread.comments.len() - 1];
+        let revision = root.patch_set.map(|patch_set| patch_set.to_string()).unwrap_or("current".into());
+        let comment = CommentInput {
+            message: text,
+            line: root.line,
+            range: root.range.clone(),
+            side: root.side.clone(),
+            in_reply_to: Some(last.id.clone()),
+            unresolved,
+        };
+        let review = ReviewInput {
+            comments: HashMap::from([(thread.path, vec![comment])]),
+            ..Default::default()
+        };
+
+        self.api.set_review(&review_id, &revision, review).await
+    }
+}
+
+#[async_trait]
+impl ReviewModule for GerritModule {
+    fn rate_limit(&self) -> RateLimit {
+        self.scheduler.rate_limit()
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let account = self.api.get_current_account().await?;
+
+        // http passwords grant everything the account may do
+        Ok(Authentication {
+            user: account.into(),
+            scopes: None,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.get_reviews().await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.get_review_discussions(review_id.parse()?).await
+    }
+
+    async fn get_review_file_summaries(&self, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        self.get_review_file_summaries(review_id.parse()?).await
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        self.get_review_file_changes(review_id.parse()?, file_path, revision).await
+    }
+
+    async fn mark_file_read(&self, review_id: String, file_path: String, revision: String, read: bool) -> anyhow::Result<()> {
+        self.api
+            .set_file_reviewed(&review_id.parse()?, &revision, &file_path, read)
+            .await
+    }
+
+    async fn set_discussion_resolved(&self, review_id: String, discussion_id: String, resolved: bool) -> anyhow::Result<()> {
+        // threads are resolved by answering them
+        let text = if resolved { "Done" } else { "Unresolved" };
+
+        self.reply(review_id.parse()?, discussion_id, text.into(), Some(!resolved)).await
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.submit_review_verdict(review_id.parse()?, verdict, body).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.create_file_discussion(review_id.parse()?, file_path, revision, line, text).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.create_review_discussion(review_id.parse()?, text).await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.reply(review_id.parse()?, discussion_id, text, None).await
+    }
+}
+
+/// Any negative vote rejects the change, the highest vote of the label approves it.
+fn code_review_state(label: Option<&LabelInfo>) -> ReviewState {
+    let Some(label) = label else {
+        return ReviewState::Pending;
+    };
+    let max_vote = label
+        .values
+        .keys()
+        .filter_map(|value| value.trim().parse::<i32>().ok())
+        .max()
+        .unwrap_or(2);
+    let votes = label.all.iter().filter_map(|approval| approval.value).collect::<Vec<_>>();
+    if votes.iter().any(|vote| *vote < 0) {
+        ReviewState::Rejected
+    } else if votes.iter().any(|vote| *vote >= max_vote) {
+        ReviewState::Approved
+    } else {
+        ReviewState::Pending
+    }
+}
+
+/// Number of the current patch set, as the revision of the review.
+fn current_patch_set(change: &ChangeInfo) -> anyhow::Result<String> {
+    change
+        .current_revision
+        .as_ref()
+        .and_then(|revision| change.revisions.get(revision))
+        .map(|revision| revision.number.to_string())
+        .ok_or_else(|| anyhow::anyhow!("Change has no current patch set"))
+}
+
+/// Groups the comments on a file by the comment their `in_reply_to` chain starts at.
+fn build_threads(path: String, comments: Vec<CommentInfo>) -> Vec<Thread> {
+    let parents = comments
+        .iter()
+        .map(|comment| (comment.id.clone(), comment.in_reply_to.clone()))
+        .collect::<HashMap<_, _>>();
+    let root_of = |id: &str| {
+        let mut root = id.to_string();
+        // bounded in case of a cycle
+        for _ in 0..parents.len() {
+            match parents.get(&root).cloned().flatten() {
+                Some(parent) if parents.contains_key(&parent) => root = parent,
+                _ => break,
+            }
+        }
+        root
+    };
+    let mut threads = HashMap::<String, Vec<CommentInfo>>::new();
+    for comment in comments {
+        threads.entry(root_of(&comment.id)).or_default().push(comment);
+    }
+
+    threads
+        .into_iter()
+        .map(|(root, mut comments)| {
+            comments.sort_by_key(|comment| (comment.id != root, comment.updated));
+            Thread {
+                path: path.clone(),
+                comments,
+            }
+        })
+        .collect()
+}
+
+impl From<CommentInfo> for ReviewComment {
+    fn from(comment: CommentInfo) -> Self {
+        Self {
+            id: comment.id,
+            user: comment.author.map(User::from).unwrap_or_else(|| User {
+                name: "Gerrit".into(),
+                avatar_url: None,
+            }),
+            text: comment.message,
+            timestamp: comment.updated as u64,
+        }
+    }
+}
+
+impl From<AccountInfo> for User {
+    fn from(account: AccountInfo) -> Self {
+        Self {
+            name: account
+                .name
+                .or(account.username)
+                .unwrap_or_else(|| format!("Account {}", account.account_id)),
+            // avatars are sorted by size, the largest one scales down best
+            avatar_url: account.avatars.last().map(|avatar| avatar.url.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ReviewId {
+    project: String,
+    number: u64,
+}
+
+impl FromStr for ReviewId {
+    type Err = anyhow::Error;
+
+    /// Project names may contain slashes, the number is after the last one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (project, number) = s.rsplit_once('/').ok_or_else(|| anyhow::anyhow!("Invalid review id format"))?;
+        anyhow::ensure!(!project.is_empty(), "Invalid review id format");
+
+        Ok(ReviewId {
+            project: project.to_string(),
+            number: number.parse()?,
+        })
+    }
+}
+
+impl Display for ReviewId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.project, self.number)
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use reqwest::header::AUTHORIZATION;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::Url;
+
+use super::messages::*;
+use crate::error::ApiError;
+use crate::retry::{RetryMiddleware, RetryPolicy};
+use crate::scheduler::{Scheduler, SchedulerMiddleware};
+
+/// Items requested per page, the default maximum of Gitea
+const PAGE_SIZE: usize = 50;
+
+pub struct GiteaApi {
+    client: ClientWithMiddleware,
+    url: Url,
+    token: String,
+}
+
+impl GiteaApi {
+    pub fn new(url: &str, token: String, scheduler: Arc<Scheduler>, policy: RetryPolicy) -> anyhow::Result<Self> {
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| ApiError::InvalidConfig { message: format!("Invalid Gitea url: {url}") })?;
+        let http = reqwest::Client::builder().timeout(policy.timeout).build()?;
+        let client = reqwest_middleware::ClientBuilder::new(http)
+            .with(SchedulerMiddleware(scheduler))
+            .with(RetryMiddleware(policy))
+            .build();
+
+        Ok(Self { client, url, token })
+    }
+
+    pub async fn get_current_user(&self) -> anyhow::Result<UserDTO> {
+        self.get(self.endpoint(["user"])).await
+    }
+
+    pub async fn get_repositories(&self) -> anyhow::Result<Vec<RepositoryDTO>> {
+        self.get_paged(self.endpoint(["user", "repos"])).await
+    }
+
+    pub async fn get_pull_requests(&self, owner: &str, repo: &str) -> anyhow::Result<Vec<PullRequestDTO>> {
+        let mut url = self.endpoint(["repos", owner, repo, "pulls"]);
+        url.query_pairs_mut().append_pair("state", "open");
+
+        self.get_paged(url).await
+    }
+
+    pub async fn get_pull_request(&self, owner: &str, repo: &str, index: u64) -> anyhow::Result<PullRequestDTO> {
+        self.get(self.endpoint(["repos", owner, repo, "pulls", &index.to_string()])).await
+    }
+
+    pub async fn get_pull_reviews(&self, owner: &str, repo: &str, index: u64) -> anyhow::Result<Vec<PullReviewDTO>> {
+        self.get_paged(self.endpoint(["repos", owner, repo, "pulls", &index.to_string(), "reviews"])).await
+    }
+
+    pub async fn get_pull_review_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        index: u64,
+        review: u64,
+    ) -> anyhow::Result<Vec<PullReviewCommentDTO>> {
+        let url = self.endpoint(["repos", owner, repo, "pulls", &index.to_string(), "reviews", &review.to_string(), "comments"]);
+
+        self.get(url).await
+    }
+
+    pub async fn get_pull_files(&self, owner: &str, repo: &str, index: u64) -> anyhow::Result<Vec<ChangedFileDTO>> {
+        self.get_paged(self.endpoint(["repos", owner, repo, "pulls", &index.to_string(), "files"])).await
+    }
+
+    pub async fn get_issue_comments(&self, owner: &str, repo: &str, index: u64) -> anyhow::Result<Vec<IssueCommentDTO>> {
+        self.get(self.endpoint(["repos", owner, repo, "issues", &index.to_string(), "comments"])).await
+    }
+
+    pub async fn get_raw_file(&self, owner: &str, repo: &str, file_path: &str, revision: &str) -> anyhow::Result<Vec<u8>> {
+        let mut url = self.endpoint(["repos", owner, repo, "raw"]);
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.extend(file_path.split('/'));
+        }
+        url.query_pairs_mut().append_pair("ref", revision);
+        let response = self.send(self.client.get(url)).await?;
+
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    pub async fn create_pull_review(
+        &self,
+        owner: &str,
+        repo: &str,
+        index: u64,
+        request: CreatePullReviewDTO,
+    ) -> anyhow::Result<()> {
+        self.post(self.endpoint(["repos", owner, repo, "pulls", &index.to_string(), "reviews"]), &request).await
+    }
+
+    pub async fn create_issue_comment(
+        &self,
+        owner: &str,
+        repo: &str,
+        index: u64,
+        request: CreateIssueCommentDTO,
+    ) -> anyhow::Result<()> {
+        self.post(self.endpoint(["repos", owner, repo, "issues", &index.to_string(), "comments"]), &request).await
+    }
+
+    /// Url of an endpoint below `/api/v1`, the segments are escaped.
+    fn endpoint<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> Url {
+        let mut url = self.url.clone();
+        // urls which can't be a base are rejected in `new`
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(["api", "v1"]).extend(segments);
+        }
+
+        url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<T> {
+        let response = self.send(self.client.get(url)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Requests all pages until an empty one, Gitea may cap the limit below the requested one.
+    async fn get_paged<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<Vec<T>> {
+        let mut items = vec![];
+        for page in 1.. {
+            let mut url = url.clone();
+            url.query_pairs_mut()
+                .append_pair("page", &page.to_string())
+                .append_pair("limit", &PAGE_SIZE.to_string());
+            let page: Vec<T> = self.get(url).await?;
+            if page.is_empty() {
+                break;
+            }
+            items.extend(page);
+        }
+
+        Ok(items)
+    }
+
+    async fn post(&self, url: Url, body: &impl Serialize) -> anyhow::Result<()> {
+        self.send(self.client.post(url).json(body)).await?;
+
+        Ok(())
+    }
+
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let response = request
+            .header(AUTHORIZATION, format!("token {}", self.token))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError::from_status(status.as_u16(), format!("{status}: {body}"), None).into());
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserDTO {
+    pub login: String,
+    #[serde(default)]
+    pub full_name: String,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RepositoryDTO {
+    pub name: String,
+    pub owner: UserDTO,
+    #[serde(default)]
+    pub has_pull_requests: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullRequestDTO {
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub user: UserDTO,
+    pub head: BranchDTO,
+    pub base: BranchDTO,
+    /// Commit the changes are compared against
+    pub merge_base: Option<String>,
+    #[serde(default)]
+    pub requested_reviewers: Option<Vec<UserDTO>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BranchDTO {
+    #[serde(rename = "ref")]
+    pub name: String,
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewStateDTO {
+    Approved,
+    Pending,
+    Comment,
+    RequestChanges,
+    RequestReview,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullReviewDTO {
+    pub id: u64,
+    pub user: Option<UserDTO>,
+    pub state: ReviewStateDTO,
+    #[serde(default)]
+    pub dismissed: bool,
+    #[serde(default)]
+    pub comments_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullReviewCommentDTO {
+    pub id: u64,
+    pub body: String,
+    pub user: Option<UserDTO>,
+    /// Set once the conversation of the comment was resolved
+    pub resolver: Option<UserDTO>,
+    pub path: String,
+    pub commit_id: String,
+    /// Line in the new version of the file, `0` for comments on the old version
+    pub position: u64,
+    /// Line in the old version of the file, `0` for comments on the new version
+    pub original_position: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IssueCommentDTO {
+    pub id: u64,
+    pub body: String,
+    pub user: Option<UserDTO>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChangedFileDTO {
+    pub filename: String,
+    #[serde(default)]
+    pub previous_filename: Option<String>,
+    pub status: String,
+    pub additions: u32,
+    pub deletions: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatePullReviewDTO {
+    pub event: ReviewStateDTO,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    pub comments: Vec<CreatePullReviewCommentDTO>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatePullReviewCommentDTO {
+    pub path: String,
+    pub body: String,
+    pub new_position: u64,
+    pub old_position: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateIssueCommentDTO {
+    pub body: String,
+}
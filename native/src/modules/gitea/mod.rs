@@ -0,0 +1,524 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use itertools::Itertools;
+
+use crate::cache::Cache;
+use crate::diff::diff_files;
+use crate::error::ApiError;
+use crate::models::*;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::util::split_file_name;
+use crate::ReviewModule;
+
+use self::api::GiteaApi;
+use self::messages::*;
+
+mod api;
+mod messages;
+
+/// Works with Gitea and its forks like Forgejo, which keep the v1 api.
+///
+/// The api doesn't tell which review comment answers which, so all comments on the same line of a
+/// file form one conversation, resolved when its first comment is. Separate threads on one line are
+/// merged. General comments of the pull request are discussions of their own which can't be replied to.
+///
+/// Verdicts need a request per pull request, so they are only fetched for pull requests the user
+/// wrote or is asked to review. Others are listed as pending with their requested reviewers.
+pub struct GiteaModule {
+    api: GiteaApi,
+    scheduler: Arc<Scheduler>,
+    /// File contents by path and commit, `None` for binary files
+    file_contents: Cache<(String, String), Option<String>>,
+}
+
+impl GiteaModule {
+    pub fn new(url: String, token: String, connection: &ConnectionSettings) -> anyhow::Result<Self> {
+        let scheduler = Arc::new(Scheduler::new());
+        let api = GiteaApi::new(&url, token, scheduler.clone(), RetryPolicy::from(connection))?;
+
+        Ok(Self {
+            api,
+            scheduler,
+            file_contents: Cache::permanent(),
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        let (user, repositories) =
+            futures::future::try_join(self.api.get_current_user(), self.api.get_repositories()).await?;
+        // the scheduler keeps the amount of concurrent requests in check
+        let pull_requests = repositories
+            .iter()
+            .filter(|repository| repository.has_pull_requests)
+            .map(|repository| async move {
+                let pull_requests = self
+                    .api
+                    .get_pull_requests(&repository.owner.login, &repository.name)
+                    .await?;
+
+                anyhow::Ok(pull_requests.into_iter().map(|pull_request| {
+                    let review_id = ReviewId {
+                        owner: repository.owner.login.clone(),
+                        repo: repository.name.clone(),
+                        id: pull_request.number,
+                    };
+                    (review_id, pull_request)
+                }))
+            });
+        let pull_requests = futures::future::try_join_all(pull_requests).await?;
+        let user = &user;
+        let reviews = pull_requests
+            .into_iter()
+            .flatten()
+            .map(|(review_id, pull_request)| async move {
+                let involved = pull_request.user.login == user.login
+                    || pull_request
+                        .requested_reviewers
+                        .iter()
+                        .flatten()
+                        .any(|reviewer| reviewer.login == user.login);
+                let reviews = if involved {
+                    self.api
+                        .get_pull_reviews(&review_id.owner, &review_id.repo, review_id.id)
+                        .await?
+                } else {
+                    vec![]
+                };
+
+                anyhow::Ok(review_from(review_id, pull_request, &reviews))
+            });
+
+        futures::future::try_join_all(reviews).await
+    }
+
+    async fn get_review_comments(&self, review_id: &ReviewId) -> anyhow::Result<Vec<PullReviewCommentDTO>> {
+        let reviews = self
+            .api
+            .get_pull_reviews(&review_id.owner, &review_id.repo, review_id.id)
+            .await?;
+        let comments = reviews
+            .iter()
+            .filter(|review| review.comments_count > 0)
+            .map(|review| {
+                self.api
+                    .get_pull_review_comments(&review_id.owner, &review_id.repo, review_id.id, review.id)
+            });
+        let comments = futures::future::try_join_all(comments).await?;
+
+        Ok(comments.into_iter().flatten().collect())
+    }
+
+    async fn get_review_discussions(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        let review_comments = self.get_review_comments(&review_id);
+        let issue_comments = self
+            .api
+            .get_issue_comments(&review_id.owner, &review_id.repo, review_id.id);
+        let (review_comments, issue_comments) =
+            futures::future::try_join(review_comments, issue_comments).await?;
+
+        // Gitea has no replies, comments on the same line form a conversation
+        let mut conversations = BTreeMap::<(String, u64, u64), Vec<PullReviewCommentDTO>>::new();
+        for comment in review_comments {
+            let key = (comment.path.clone(), comment.position, comment.original_position);
+            conversations.entry(key).or_default().push(comment);
+        }
+        let file_discussions = conversations.into_values().filter_map(|mut comments| {
+            comments.sort_by_key(|comment| comment.id);
+            let root = comments.first()?.clone();
+            let (file_path_segments, file_name) = split_file_name(&root.path);
+            let (line, side) = match root.position {
+                0 => (root.original_position, DiffSide::Old),
+                line => (line, DiffSide::New),
+            };
+
+            Some(ReviewDiscussion {
+                id: root.id.to_string(),
+                resolved: root.resolver.is_some(),
+                file: Some(ReviewFileDiscussion {
+                    file_name,
+                    file_path: root.path,
+                    file_path_segments,
+                    revision: Some(root.commit_id),
+                    lines: (line > 0).then_some(LineRange {
+                        start: line as u32,
+                        end: line as u32,
+                    }),
+                    side,
+                }),
+                comments: comments
+                    .into_iter()
+                    .map(|comment| comment_from(comment.id, comment.user, comment.body, comment.created_at))
+                    .collect(),
+            })
+        });
+        let discussions = file_discussions
+            .chain(issue_comments.into_iter().map(|comment| ReviewDiscussion {
+                id: comment.id.to_string(),
+                resolved: false,
+                file: None,
+                comments: vec![comment_from(comment.id, comment.user, comment.body, comment.created_at)],
+            }))
+            .collect();
+
+        Ok(discussions)
+    }
+
+    async fn get_review_file_summaries(&self, review_id: ReviewId) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        let pull_request = self
+            .api
+            .get_pull_request(&review_id.owner, &review_id.repo, review_id.id);
+        let files = self
+            .api
+            .get_pull_files(&review_id.owner, &review_id.repo, review_id.id);
+        let (pull_request, files) = futures::future::try_join(pull_request, files).await?;
+
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let (file_path_segments, file_name) = split_file_name(&file.filename);
+                ReviewFileSummary {
+                    file_name,
+                    file_path_segments,
+                    change_type: match file.status.as_str() {
+                        "added" => ChangeType::Added,
+                        "deleted" | "removed" => ChangeType::Removed,
+                        _ => ChangeType::Modified,
+                    },
+                    file_path: file.filename,
+                    added_lines: file.additions,
+                    removed_lines: file.deletions,
+                    is_read: false,
+                    revision_id: pull_request.head.sha.clone(),
+                }
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        let pull_request = self
+            .api
+            .get_pull_request(&review_id.owner, &review_id.repo, review_id.id);
+        let files = self
+            .api
+            .get_pull_files(&review_id.owner, &review_id.repo, review_id.id);
+        let (pull_request, files) = futures::future::try_join(pull_request, files).await?;
+        let file = files
+            .into_iter()
+            .find(|file| file.filename == file_path)
+            .ok_or_else(|| ApiError::NotFound { message: "File is not part of the review".into() })?;
+        let old_path = match file.status.as_str() {
+            "added" => None,
+            _ => Some(file.previous_filename.filter(|path| !path.is_empty()).unwrap_or_else(|| file.filename.clone())),
+        };
+        let new_path = (!matches!(file.status.as_str(), "deleted" | "removed")).then_some(file.filename);
+        // like Github, the changes are shown since the merge base
+        let base_revision = pull_request.merge_base.unwrap_or(pull_request.base.sha);
+
+        let old_content = async {
+            match old_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &base_revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let new_content = async {
+            match new_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (old_content, new_content) = futures::future::try_join(old_content, new_content).await?;
+        let hunks = match (old_content, new_content) {
+            (Some(None), _) | (_, Some(None)) => vec![],
+            (old, new) => diff_files(old.flatten().as_deref(), new.flatten().as_deref()),
+        };
+
+        Ok(ReviewFileChanges {
+            old_path,
+            new_path,
+            hunks,
+        })
+    }
+
+    /// Returns `None` for binary files
+    async fn get_file_content(&self, review_id: &ReviewId, file_path: &str, revision: &str) -> anyhow::Result<Option<String>> {
+        let key = (file_path.to_string(), revision.to_string());
+        let content = async {
+            let content = self
+                .api
+                .get_raw_file(&review_id.owner, &review_id.repo, file_path, revision)
+                .await?;
+
+            anyhow::Ok(String::from_utf8(content).ok())
+        };
+
+        self.file_contents.get_or_fetch(key, content).await
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: ReviewId,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        let request = CreatePullReviewDTO {
+            event: match verdict {
+                ReviewVerdict::Approve => ReviewStateDTO::Approved,
+                ReviewVerdict::RequestChanges => ReviewStateDTO::RequestChanges,
+                ReviewVerdict::Comment => ReviewStateDTO::Comment,
+            },
+            body: body.unwrap_or_default(),
+            commit_id: None,
+            comments: vec![],
+        };
+
+        self.api
+            .create_pull_review(&review_id.owner, &review_id.repo, review_id.id, request)
+            .await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let request = CreatePullReviewDTO {
+            event: ReviewStateDTO::Comment,
+            body: String::new(),
+            commit_id: Some(revision),
+            comments: vec![CreatePullReviewCommentDTO {
+                path: file_path,
+                body: text,
+                new_position: line.into(),
+                old_position: 0,
+            }],
+        };
+
+        self.api
+            .create_pull_review(&review_id.owner, &review_id.repo, review_id.id, request)
+            .await
+    }
+
+    async fn create_review_discussion(&self, review_id: ReviewId, text: String) -> anyhow::Result<()> {
+        self.api
+            .create_issue_comment(&review_id.owner, &review_id.repo, review_id.id, CreateIssueCommentDTO { body: text })
+            .await
+    }
+
+    /// Comments on the same line continue its conversation, general comments have no threads and are rejected.
+    async fn reply_to_discussion(&self, review_id: ReviewId, discussion_id: String, text: String) -> anyhow::Result<()> {
+        let discussion_id: u64 = discussion_id.parse()?;
+        let comments = self.get_review_comments(&review_id).await?;
+        let Some(root) = comments.into_iter().find(|comment| comment.id == discussion_id) else {
+            let issue_comments = self
+                .api
+                .get_issue_comments(&review_id.owner, &review_id.repo, review_id.id)
+                .await?;
+            if issue_comments.iter().any(|comment| comment.id == discussion_id) {
+                return Err(ApiError::Provider {
+                    message: "Gitea can't reply to general comments, start a new discussion instead".into(),
+                }
+                .into());
+            }
+            return Err(ApiError::NotFound { message: "Unknown discussion id".into() }.into());
+        };
+        let request = CreatePullReviewDTO {
+            event: ReviewStateDTO::Comment,
+            body: String::new(),
+            commit_id: Some(root.commit_id),
+            comments: vec![CreatePullReviewCommentDTO {
+                path: root.path,
+                body: text,
+                new_position: root.position,
+                old_position: root.original_position,
+            }],
+        };
+
+        self.api
+            .create_pull_review(&review_id.owner, &review_id.repo, review_id.id, request)
+            .await
+    }
+}
+
+#[async_trait]
+impl ReviewModule for GiteaModule {
+    fn tracks_read_state(&self) -> bool {
+        false
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.scheduler.rate_limit()
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let user = self.api.get_current_user().await?;
+
+        // Gitea doesn't report the scopes of a token
+        Ok(Authentication {
+            user: user.into(),
+            scopes: None,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.get_reviews().await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.get_review_discussions(review_id.parse()?).await
+    }
+
+    async fn get_review_file_summaries(&self, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        self.get_review_file_summaries(review_id.parse()?).await
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        self.get_review_file_changes(review_id.parse()?, file_path, revision).await
+    }
+
+    async fn mark_file_read(&self, _review_id: String, _file_path: String, _revision: String, _read: bool) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Gitea has no api for viewed files, read files are tracked locally"))
+    }
+
+    async fn set_discussion_resolved(&self, _review_id: String, _discussion_id: String, _resolved: bool) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Gitea has no api to resolve conversations"))
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.submit_review_verdict(review_id.parse()?, verdict, body).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.create_file_discussion(review_id.parse()?, file_path, revision, line, text).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.create_review_discussion(review_id.parse()?, text).await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.reply_to_discussion(review_id.parse()?, discussion_id, text).await
+    }
+}
+
+fn review_from(review_id: ReviewId, pull_request: PullRequestDTO, reviews: &[PullReviewDTO]) -> Review {
+    // the latest verdict of every reviewer counts, comments don't change it
+    let verdicts = reviews
+        .iter()
+        .filter(|review| !review.dismissed)
+        .filter(|review| matches!(review.state, ReviewStateDTO::Approved | ReviewStateDTO::RequestChanges))
+        .filter_map(|review| Some((review.user.as_ref()?.login.as_str(), review.state)))
+        .collect::<HashMap<_, _>>();
+    let state = if verdicts.values().any(|state| *state == ReviewStateDTO::RequestChanges) {
+        ReviewState::Rejected
+    } else if verdicts.values().any(|state| *state == ReviewStateDTO::Approved) {
+        ReviewState::Approved
+    } else {
+        ReviewState::Pending
+    };
+    let reviewers = reviews
+        .iter()
+        .filter_map(|review| review.user.clone())
+        .chain(pull_request.requested_reviewers.into_iter().flatten())
+        .filter(|user| user.login != pull_request.user.login)
+        .unique_by(|user| user.login.clone())
+        .map(User::from)
+        .collect();
+
+    Review {
+        id: review_id.to_string(),
+        title: pull_request.title,
+        branch_name: pull_request.head.name,
+        authors: vec![pull_request.user.into()],
+        reviewers,
+        open: pull_request.state == "open",
+        state,
+        updated_at: pull_request.updated_at.map(|updated_at| updated_at.timestamp_millis() as u64),
+    }
+}
+
+fn comment_from(id: u64, user: Option<UserDTO>, text: String, created_at: chrono::DateTime<chrono::Utc>) -> ReviewComment {
+    ReviewComment {
+        id: id.to_string(),
+        // comments of deleted users have no author
+        user: user.map(User::from).unwrap_or_else(|| User {
+            name: "Ghost".into(),
+            avatar_url: None,
+        }),
+        text,
+        timestamp: created_at.timestamp_millis() as u64,
+    }
+}
+
+impl From<UserDTO> for User {
+    fn from(user: UserDTO) -> Self {
+        Self {
+            name: if user.full_name.is_empty() { user.login } else { user.full_name },
+            avatar_url: user.avatar_url,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ReviewId {
+    owner: String,
+    repo: String,
+    id: u64,
+}
+
+impl FromStr for ReviewId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let result = s.split('/').collect::<Vec<_>>();
+        anyhow::ensure!(result.len() == 3, "Invalid review id format");
+
+        Ok(ReviewId {
+            owner: result[0].to_string(),
+            repo: result[1].to_string(),
+            id: result[2].parse()?,
+        })
+    }
+}
+
+impl Display for ReviewId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.owner, self.repo, self.id)
+    }
+}
@@ -15,3 +15,19 @@ pub struct MarkFileAsViewed;
     response_derives = "Debug, PartialEq",
 )]
 pub struct MarkFileAsUnviewed;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/modules/github/graphql/schema.graphql",
+    query_path = "src/modules/github/graphql/commands/set_review_thread_resolved.graphql",
+    response_derives = "Debug, PartialEq",
+)]
+pub struct ResolveReviewThread;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/modules/github/graphql/schema.graphql",
+    query_path = "src/modules/github/graphql/commands/set_review_thread_resolved.graphql",
+    response_derives = "Debug, PartialEq",
+)]
+pub struct UnresolveReviewThread;
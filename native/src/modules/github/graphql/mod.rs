@@ -1,26 +1,42 @@
 use std::str::FromStr;
-use graphql_client::{GraphQLQuery, Response};
+use std::sync::Arc;
+use graphql_client::{GraphQLQuery, QueryBody};
+use serde::Deserialize;
 use surf::http::headers::HeaderValue;
+use crate::error::ApiError;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
 
 pub mod queries;
 pub mod commands;
 
 pub struct GraphqlClient {
     client: surf::Client,
+    scheduler: Arc<Scheduler>,
+    policy: RetryPolicy,
 }
 
 impl GraphqlClient {
-    pub fn new(token: &str) -> anyhow::Result<Self> {
+    pub fn new(api_url: &str, token: &str, scheduler: Arc<Scheduler>, policy: RetryPolicy) -> anyhow::Result<Self> {
         let config = surf::Config::new()
-            .set_base_url(surf::Url::parse("https://api.github.com")?)
-            .add_header("Authorization", HeaderValue::from_str(&format!("Bearer {token}")).map_err(|err| anyhow::anyhow!("{err:?}"))?).map_err(|err| anyhow::anyhow!("{err:?}"))?;
+            .set_base_url(surf::Url::parse(api_url)?)
+            .add_header("Authorization", HeaderValue::from_str(&format!("Bearer {token}")).map_err(|err| ApiError::InvalidConfig { message: err.to_string() })?).map_err(|err| ApiError::InvalidConfig { message: err.to_string() })?;
         let client = config.try_into()?;
 
         Ok(Self {
-            client
+            client,
+            scheduler,
+            policy,
         })
     }
 
+    /// The authenticated user and the scopes of the token, which Github only reports for classic tokens.
+    pub async fn get_viewer(&self) -> anyhow::Result<(queries::get_viewer::GetViewerViewer, Option<Vec<String>>)> {
+        let (response, scopes) = self.send::<queries::GetViewer>(queries::get_viewer::Variables {}, true).await?;
+
+        Ok((response.viewer, scopes))
+    }
+
     pub async fn get_reviews(&self, query: String) -> anyhow::Result<Vec<queries::get_reviews::GetReviewsSearchNodesOnPullRequest>> {
         use queries::get_reviews::*;
         let response = self.query::<queries::GetReviews>(Variables {
@@ -57,12 +73,12 @@ impl GraphqlClient {
 
     pub async fn mark_file_viewed_state(&self, pr: String, file_path: String, viewed: bool) -> anyhow::Result<()> {
         if viewed {
-            self.query::<commands::MarkFileAsViewed>(commands::mark_file_as_viewed::Variables {
+            self.mutate::<commands::MarkFileAsViewed>(commands::mark_file_as_viewed::Variables {
                 pr,
                 file: file_path
             }).await?;
         }else {
-            self.query::<commands::MarkFileAsUnviewed>(commands::mark_file_as_unviewed::Variables {
+            self.mutate::<commands::MarkFileAsUnviewed>(commands::mark_file_as_unviewed::Variables {
                 pr,
                 file: file_path
             }).await?;
@@ -71,15 +87,128 @@ impl GraphqlClient {
         Ok(())
     }
 
+    /// All review threads of the pull request, fetched a page of 100 at a time.
+    pub async fn get_review_threads(&self, owner: String, repo: String, pr: i64) -> anyhow::Result<Vec<queries::get_review_threads::GetReviewThreadsRepositoryPullRequestReviewThreadsNodes>> {
+        let mut threads = vec![];
+        let mut after = None;
+        loop {
+            let response = self.query::<queries::GetReviewThreads>(queries::get_review_threads::Variables {
+                owner: owner.clone(),
+                repo: repo.clone(),
+                pr,
+                after,
+            }).await?;
+            let Some(review_threads) = response.repository
+                .and_then(|repository| repository.pull_request)
+                .map(|pull_request| pull_request.review_threads) else {
+                break;
+            };
+            threads.extend(review_threads.nodes.unwrap_or_default().into_iter().flatten());
+            if !review_threads.page_info.has_next_page {
+                break;
+            }
+            after = review_threads.page_info.end_cursor;
+        }
+
+        Ok(threads)
+    }
+
+    pub async fn set_review_thread_resolved(&self, thread: String, resolved: bool) -> anyhow::Result<()> {
+        if resolved {
+            self.mutate::<commands::ResolveReviewThread>(commands::resolve_review_thread::Variables {
+                thread
+            }).await?;
+        }else {
+            self.mutate::<commands::UnresolveReviewThread>(commands::unresolve_review_thread::Variables {
+                thread
+            }).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn query<Q: GraphQLQuery>(&self, variables: Q::Variables) -> anyhow::Result<Q::ResponseData> {
+        let (data, _) = self.send::<Q>(variables, true).await?;
+
+        Ok(data)
+    }
+
+    /// Like `query`, but never repeated because the failed attempt might have been applied already.
+    pub async fn mutate<Q: GraphQLQuery>(&self, variables: Q::Variables) -> anyhow::Result<Q::ResponseData> {
+        let (data, _) = self.send::<Q>(variables, false).await?;
+
+        Ok(data)
+    }
+
+    /// Runs the query and returns its data together with the scopes listed in `x-oauth-scopes`.
+    async fn send<Q: GraphQLQuery>(&self, variables: Q::Variables, idempotent: bool) -> anyhow::Result<(Q::ResponseData, Option<Vec<String>>)> {
         let request = Q::build_query(variables);
-        let response = self.client.post("/graphql")
-            .body_json(&request)
+        let _permit = self.scheduler.acquire().await?;
+
+        self.policy.run(idempotent, || self.post::<Q>(&request)).await
+    }
+
+    async fn post<Q: GraphQLQuery>(&self, request: &QueryBody<Q::Variables>) -> anyhow::Result<(Q::ResponseData, Option<Vec<String>>)> {
+        let mut response = self.client.post("/graphql")
+            .body_json(request)
             .map_err(|err| anyhow::anyhow!("{err:?}"))?
-            .recv_json::<Response<Q::ResponseData>>()
+            .await
+            .map_err(|err| ApiError::Network { message: err.to_string() })?;
+        self.scheduler.record(|name| response.header(name).map(|value| value.as_str().to_string()));
+
+        if !response.status().is_success() {
+            let reset_at = response.header("x-ratelimit-remaining")
+                .filter(|remaining| remaining.as_str() == "0")
+                .and_then(|_| response.header("x-ratelimit-reset"))
+                .and_then(|reset| reset.as_str().parse().ok());
+            let body = response.body_string().await.unwrap_or_default();
+            return Err(ApiError::from_status(response.status().into(), format!("{}: {body}", response.status()), reset_at).into());
+        }
+
+        let scopes = response.header("x-oauth-scopes").map(|scopes| {
+            scopes.as_str()
+                .split(',')
+                .map(|scope| scope.trim().to_string())
+                .filter(|scope| !scope.is_empty())
+                .collect()
+        });
+        let response = response
+            .body_json::<GraphqlResponse<Q::ResponseData>>()
             .await
             .map_err(|err| anyhow::anyhow!("{err:?}"))?;
 
-        response.data.ok_or_else(|| anyhow::anyhow!("{:?}", response.errors))
+        match response.data {
+            Some(data) => Ok((data, scopes)),
+            None => Err(GraphqlError::into_api_error(response.errors).into()),
+        }
+    }
+}
+
+/// Like `graphql_client::Response` but keeping the error type Github adds to every error.
+#[derive(Debug, Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: Option<String>,
+}
+
+impl GraphqlError {
+    fn into_api_error(errors: Vec<GraphqlError>) -> ApiError {
+        let error_type = errors.first().and_then(|error| error.error_type.clone());
+        let message = errors.into_iter().map(|error| error.message).collect::<Vec<_>>().join(", ");
+
+        match error_type.as_deref() {
+            Some("NOT_FOUND") => ApiError::NotFound { message },
+            Some("FORBIDDEN") | Some("INSUFFICIENT_SCOPES") => ApiError::Forbidden { message },
+            Some("RATE_LIMITED") => ApiError::RateLimited { reset_at: None, message },
+            _ => ApiError::Provider { message },
+        }
     }
 }
@@ -20,3 +20,19 @@ pub struct GetReviewFileSummaries;
     response_derives = "Clone, Debug, PartialEq",
 )]
 pub struct GetReviews;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/modules/github/graphql/schema.graphql",
+    query_path = "src/modules/github/graphql/queries/get_review_threads.graphql",
+    response_derives = "Debug, PartialEq",
+)]
+pub struct GetReviewThreads;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/modules/github/graphql/schema.graphql",
+    query_path = "src/modules/github/graphql/queries/get_viewer.graphql",
+    response_derives = "Debug, PartialEq",
+)]
+pub struct GetViewer;
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use octorust::http_cache::HttpCache;
+use parking_lot::RwLock;
+
+/// Keeps the etags of Github REST responses in memory so octorust can send conditional requests.
+///
+/// Responses answered with `304 Not Modified` don't count against the rate limit.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryHttpCache {
+    entries: Arc<RwLock<HashMap<String, CacheEntry>>>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    etag: String,
+    body: String,
+    next_link: Option<String>,
+}
+
+impl MemoryHttpCache {
+    fn lookup<T>(&self, uri: &str, map: impl FnOnce(&CacheEntry) -> T) -> anyhow::Result<T> {
+        let entries = self.entries.read();
+        let entry = entries
+            .get(uri)
+            .ok_or_else(|| anyhow::anyhow!("No response cached for {uri}"))?;
+
+        Ok(map(entry))
+    }
+}
+
+impl HttpCache for MemoryHttpCache {
+    fn cache_response(
+        &self,
+        uri: &str,
+        body: &[u8],
+        etag: &[u8],
+        next_link: &Option<String>,
+    ) -> anyhow::Result<()> {
+        let entry = CacheEntry {
+            etag: String::from_utf8(etag.to_vec())?,
+            body: String::from_utf8(body.to_vec())?,
+            next_link: next_link.clone(),
+        };
+        self.entries.write().insert(uri.to_string(), entry);
+
+        Ok(())
+    }
+
+    fn lookup_etag(&self, uri: &str) -> anyhow::Result<String> {
+        self.lookup(uri, |entry| entry.etag.clone())
+    }
+
+    fn lookup_body(&self, uri: &str) -> anyhow::Result<String> {
+        self.lookup(uri, |entry| entry.body.clone())
+    }
+
+    fn lookup_next_link(&self, uri: &str) -> anyhow::Result<Option<String>> {
+        self.lookup(uri, |entry| entry.next_link.clone())
+    }
+}
@@ -1,35 +1,90 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use std::sync::Arc;
 
-use async_compat::CompatExt;
+use async_trait::async_trait;
 use base64::prelude::*;
 use itertools::Itertools;
 use octorust::auth::Credentials;
-use octorust::types::{Order, PrivateUser, PublicUser, PullRequestReviewComment, Sort};
+use octorust::types::{
+    DiffEntry, Order, PrivateUser, PublicUser, PullRequestReviewComment,
+    PullsCreateReviewCommentRequest, PullsCreateReviewRequest, PullsCreateReviewRequestEvent,
+    PullsUpdateReviewRequest, Side, Sort,
+};
 use octorust::Client;
 use url::Url;
 
+use crate::cache::Cache;
+use crate::diff::{diff_files, parse_unified_diff};
+use crate::error::ApiError;
 use crate::models::*;
+use crate::retry::{RetryMiddleware, RetryPolicy};
+use crate::scheduler::{Scheduler, SchedulerMiddleware};
 use crate::util::split_file_name;
 use crate::ReviewModule;
 
+use self::http_cache::MemoryHttpCache;
+
 mod graphql;
+mod http_cache;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
 
 pub struct GithubModule {
     client: Client,
+    /// Shared by the REST and the GraphQL client
+    scheduler: Arc<Scheduler>,
     graphql_client: graphql::GraphqlClient,
     query: String,
+    /// File contents by path and commit
+    file_contents: Cache<(String, String), FileContent>,
+    /// Changed files and merge base by review and revision
+    revisions: Cache<(String, String), PullRequestRevision>,
+}
+
+#[derive(Clone)]
+struct PullRequestRevision {
+    base_revision: String,
+    files: Vec<DiffEntry>,
 }
 
 impl GithubModule {
-    pub fn new(token: String, query: String) -> anyhow::Result<Self> {
-        let graphql_client = graphql::GraphqlClient::new(&token)?;
-        let client = Client::new("review-tool", Credentials::Token(token))?;
+    pub fn new(token: String, query: String, connection: &ConnectionSettings) -> anyhow::Result<Self> {
+        Self::with_api_url(token, query, GITHUB_API_URL.to_string(), connection)
+    }
+
+    /// Connects to another api than the one of github.com, e.g. a local stand-in for tests.
+    pub fn with_api_url(
+        token: String,
+        query: String,
+        api_url: String,
+        connection: &ConnectionSettings,
+    ) -> anyhow::Result<Self> {
+        let policy = RetryPolicy::from(connection);
+        let scheduler = Arc::new(Scheduler::new());
+        let graphql_client = graphql::GraphqlClient::new(&api_url, &token, scheduler.clone(), policy)?;
+        let http = reqwest::Client::builder().timeout(policy.timeout).build()?;
+        // retries keep the slot of the scheduler, like the ones of the GraphQL client
+        let http = reqwest_middleware::ClientBuilder::new(http)
+            .with(SchedulerMiddleware(scheduler.clone()))
+            .with(RetryMiddleware(policy))
+            .build();
+        let mut client = Client::custom(
+            "review-tool",
+            Credentials::Token(token),
+            http,
+            Box::new(MemoryHttpCache::default()),
+        );
+        client.with_host_override(api_url.trim_end_matches('/'));
 
         Ok(Self {
             client,
+            scheduler,
             graphql_client,
             query,
+            file_contents: Cache::permanent(),
+            revisions: Cache::permanent(),
         })
     }
 
@@ -51,7 +106,7 @@ impl GithubModule {
             })
             .unique();
 
-        let user_cache = self.get_user_info_cache(user_ids).await?;
+        let user_cache = self.get_user_info_cache(user_ids.collect()).await?;
 
         let reviews = prs
             .into_iter()
@@ -71,6 +126,9 @@ impl GithubModule {
                         _ => ReviewState::Pending,
                     },
                     open: pr.closed_at.is_none(),
+                    updated_at: chrono::DateTime::parse_from_rfc3339(&pr.updated_at)
+                        .ok()
+                        .map(|updated_at| updated_at.timestamp_millis() as u64),
                     branch_name: pr.base_ref.unwrap().name,
                     reviewers: pr
                         .reviews
@@ -98,24 +156,27 @@ impl GithubModule {
         &self,
         review_id: ReviewId,
     ) -> anyhow::Result<Vec<ReviewDiscussion>> {
-        let mut comments = self
-            .client
-            .pulls()
-            .list_all_review_comments(
-                &review_id.owner,
-                &review_id.repo,
-                review_id.id,
-                Sort::Created,
-                Order::Asc,
-                None,
-            )
-            .await?;
+        let pulls = self.client.pulls();
+        let comments = pulls.list_all_review_comments(
+            &review_id.owner,
+            &review_id.repo,
+            review_id.id,
+            Sort::Created,
+            Order::Asc,
+            None,
+        );
+        let threads = self.graphql_client.get_review_threads(
+            review_id.owner.clone(),
+            review_id.repo.clone(),
+            review_id.id,
+        );
+        let (mut comments, threads) = futures::future::try_join(comments, threads).await?;
 
         let user_ids = comments
             .iter()
             .filter_map(|comment| comment.user.clone())
             .map(|user| user.login);
-        let user_cache = self.get_user_info_cache(user_ids).await?;
+        let user_cache = self.get_user_info_cache(user_ids.collect()).await?;
 
         let mut grouped_discussions =
             HashMap::<i64, (PullRequestReviewComment, Vec<PullRequestReviewComment>)>::new();
@@ -130,7 +191,7 @@ impl GithubModule {
             {
                 comments.push(discussion);
             } else {
-                println!("Missing parent discussion for {}", discussion.id);
+                log::warn!("Missing parent discussion for {}", discussion.id);
             }
         }
 
@@ -140,18 +201,42 @@ impl GithubModule {
                 comments.sort_by_key(|comment| comment.created_at);
                 comments.reverse();
                 let (file_path_segments, file_name) = split_file_name(&parent.path);
+                // Outdated comments are only anchored in the commit they were created on
+                let (revision, start_line, end_line) = if parent.line > 0 {
+                    (parent.commit_id.clone(), parent.start_line, parent.line)
+                } else {
+                    (
+                        parent.original_commit_id.clone(),
+                        parent.original_start_line,
+                        parent.original_line,
+                    )
+                };
+                let lines = (end_line > 0).then_some(LineRange {
+                    start: if start_line > 0 { start_line } else { end_line } as u32,
+                    end: end_line as u32,
+                });
+                let resolved = threads
+                    .iter()
+                    .find(|thread| thread_root_comment_id(thread) == Some(id))
+                    .map(|thread| thread.is_resolved)
+                    .unwrap_or_default();
                 ReviewDiscussion {
                     id: id.to_string(),
-                    resolved: false,
+                    resolved,
                     file: Some(ReviewFileDiscussion {
                         file_name,
                         file_path: parent.path.clone(),
                         file_path_segments,
-                        revision: Some(parent.commit_id.clone()),
+                        revision: Some(revision),
+                        lines,
+                        side: match parent.side {
+                            Some(Side::Left) => DiffSide::Old,
+                            _ => DiffSide::New,
+                        },
                     }),
                     comments: comments
                         .into_iter()
-                        .chain([parent].into_iter())
+                        .chain([parent])
                         .map(|comment| ReviewComment {
                             id: comment.id.to_string(),
                             user: user_cache.get_user(&comment.user.unwrap().login),
@@ -178,7 +263,7 @@ impl GithubModule {
         let gql_files = self.graphql_client.get_review_file_summaries(
             review_id.owner.clone(),
             review_id.repo.clone(),
-            review_id.id.clone(),
+            review_id.id,
         );
         let (files, gql_files) = futures::future::try_join(files, gql_files).await?;
 
@@ -188,7 +273,7 @@ impl GithubModule {
                 let (file_path_segments, file_name) = split_file_name(&file.filename);
                 let is_read = gql_files
                     .iter()
-                    .find(|gql_file| &gql_file.path == &file.filename)
+                    .find(|gql_file| gql_file.path == file.filename)
                     .map(|file| {
                         file.viewer_viewed_state
                             == graphql::queries::get_review_file_summaries::FileViewedState::VIEWED
@@ -223,9 +308,9 @@ impl GithubModule {
 
     async fn get_user_info_cache(
         &self,
-        user_ids: impl Iterator<Item = String>,
+        user_ids: Vec<String>,
     ) -> anyhow::Result<UserCache> {
-        let user_ids = user_ids.sorted().dedup().collect::<Vec<String>>();
+        let user_ids = user_ids.into_iter().sorted().dedup().collect::<Vec<String>>();
 
         let users_api = self.client.users();
         let users = user_ids
@@ -254,10 +339,104 @@ impl GithubModule {
         file_path: String,
         revision: String,
     ) -> anyhow::Result<ReviewFileChanges> {
+        let key = (review_id.to_string(), revision.clone());
+        let PullRequestRevision { base_revision, files } = self
+            .revisions
+            .get_or_fetch(key, self.fetch_pull_request_revision(&review_id, &revision))
+            .await?;
+        let file = files
+            .into_iter()
+            .find(|file| file.filename == file_path)
+            .ok_or_else(|| ApiError::NotFound { message: "File is not part of the review".into() })?;
+
+        let old_path = match file.status.as_str() {
+            "added" => None,
+            _ if file.previous_filename.is_empty() => Some(file.filename.clone()),
+            _ => Some(file.previous_filename.clone()),
+        };
+        let new_path = (file.status != "removed").then(|| file.filename.clone());
+
+        let old_content = self.get_file_content(&review_id, old_path.as_deref(), &base_revision);
+        let new_content = self.get_file_content(&review_id, new_path.as_deref(), &revision);
+        let (old_content, new_content) =
+            futures::future::try_join(old_content, new_content).await?;
+
+        let hunks = match (old_content, new_content) {
+            (Some(FileContent::Text(old)), Some(FileContent::Text(new))) => {
+                diff_files(Some(&old), Some(&new))
+            }
+            (None, Some(FileContent::Text(new))) => diff_files(None, Some(&new)),
+            (Some(FileContent::Text(old)), None) => diff_files(Some(&old), None),
+            // The contents api doesn't return files larger than 1 MB, fall back to the patch in that case
+            _ => parse_unified_diff(&file.patch)?,
+        };
+
+        Ok(ReviewFileChanges {
+            old_path,
+            new_path,
+            hunks,
+        })
+    }
+
+    async fn fetch_pull_request_revision(
+        &self,
+        review_id: &ReviewId,
+        revision: &str,
+    ) -> anyhow::Result<PullRequestRevision> {
+        let pull_request = self
+            .client
+            .pulls()
+            .get(&review_id.owner, &review_id.repo, review_id.id)
+            .await?;
+
+        // Github shows the changes since the merge base, not since the current head of the base branch.
+        // The first page of a comparison lists all of its changed files.
+        let comparison = self
+            .client
+            .repos()
+            .compare_commits(
+                &review_id.owner,
+                &review_id.repo,
+                0,
+                1,
+                &format!("{}...{}", pull_request.base.sha, revision),
+            )
+            .await?;
+
+        Ok(PullRequestRevision {
+            base_revision: comparison.merge_base_commit.sha,
+            files: comparison.files,
+        })
+    }
+
+    async fn get_file_content(
+        &self,
+        review_id: &ReviewId,
+        file_path: Option<&str>,
+        revision: &str,
+    ) -> anyhow::Result<Option<FileContent>> {
+        let Some(file_path) = file_path else {
+            return Ok(None);
+        };
+        let key = (file_path.to_string(), revision.to_string());
+        let content = self
+            .file_contents
+            .get_or_fetch(key, self.fetch_file_content(review_id, file_path, revision))
+            .await?;
+
+        Ok(Some(content))
+    }
+
+    async fn fetch_file_content(
+        &self,
+        review_id: &ReviewId,
+        file_path: &str,
+        revision: &str,
+    ) -> anyhow::Result<FileContent> {
         let content_file = self
             .client
             .repos()
-            .get_content_file(&review_id.owner, &review_id.repo, &file_path, &revision)
+            .get_content_file(&review_id.owner, &review_id.repo, file_path, revision)
             .await?;
 
         if content_file.encoding == "base64" {
@@ -275,17 +454,13 @@ impl GithubModule {
                 .collect::<Vec<_>>();
 
             if let Ok(text) = String::from_utf8(buffer) {
-                Ok(ReviewFileChanges { text })
+                Ok(FileContent::Text(text))
             } else {
-                Ok(ReviewFileChanges {
-                    text: String::new(),
-                })
+                Ok(FileContent::Binary)
             }
         } else {
-            println!("Unknown encoding: {}", content_file.encoding);
-            Ok(ReviewFileChanges {
-                text: String::new(),
-            })
+            log::warn!("Unknown encoding: {}", content_file.encoding);
+            Ok(FileContent::Unavailable)
         }
     }
 
@@ -302,44 +477,224 @@ impl GithubModule {
 
         Ok(())
     }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: ReviewId,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.client
+            .pulls()
+            .create_review(
+                &review_id.owner,
+                &review_id.repo,
+                review_id.id,
+                &PullsCreateReviewRequest {
+                    body: body.unwrap_or_default(),
+                    comments: vec![],
+                    commit_id: String::new(),
+                    event: Some(match verdict {
+                        ReviewVerdict::Approve => PullsCreateReviewRequestEvent::Approve,
+                        ReviewVerdict::RequestChanges => {
+                            PullsCreateReviewRequestEvent::RequestChanges
+                        }
+                        ReviewVerdict::Comment => PullsCreateReviewRequestEvent::Comment,
+                    }),
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: ReviewId,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        let discussion_id: i64 = discussion_id.parse()?;
+        let threads = self
+            .graphql_client
+            .get_review_threads(review_id.owner, review_id.repo, review_id.id)
+            .await?;
+        let thread = threads
+            .into_iter()
+            .find(|thread| thread_root_comment_id(thread) == Some(discussion_id))
+            .ok_or_else(|| ApiError::NotFound { message: "Unknown discussion id".into() })?;
+
+        self.graphql_client
+            .set_review_thread_resolved(thread.id, resolved)
+            .await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: ReviewId,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.client
+            .pulls()
+            .create_review_comment(
+                &review_id.owner,
+                &review_id.repo,
+                review_id.id,
+                &PullsCreateReviewCommentRequest {
+                    body: text,
+                    commit_id: revision,
+                    path: file_path,
+                    line: line as i64,
+                    side: Some(Side::Right),
+                    in_reply_to: 0,
+                    position: 0,
+                    start_line: 0,
+                    start_side: None,
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_review_discussion(
+        &self,
+        review_id: ReviewId,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.client
+            .issues()
+            .create_comment(
+                &review_id.owner,
+                &review_id.repo,
+                review_id.id,
+                &PullsUpdateReviewRequest { body: text },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: ReviewId,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.client
+            .pulls()
+            .create_reply_for_review_comment(
+                &review_id.owner,
+                &review_id.repo,
+                review_id.id,
+                discussion_id.parse()?,
+                &PullsUpdateReviewRequest { body: text },
+            )
+            .await?;
+
+        Ok(())
+    }
 }
 
+#[async_trait]
 impl ReviewModule for GithubModule {
-    fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
-        smol::block_on(self.get_reviews().compat())
+    fn required_scopes(&self) -> &'static [&'static str] {
+        &["repo"]
     }
 
-    fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
-        smol::block_on(self.get_review_discussions(review_id.parse()?).compat())
+    fn rate_limit(&self) -> RateLimit {
+        self.scheduler.rate_limit()
     }
 
-    fn get_review_file_summaries(
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let (viewer, scopes) = self.graphql_client.get_viewer().await?;
+
+        Ok(Authentication {
+            user: User {
+                name: viewer.name.filter(|name| !name.is_empty()).unwrap_or(viewer.login),
+                avatar_url: Some(viewer.avatar_url),
+            },
+            scopes,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.get_reviews().await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.get_review_discussions(review_id.parse()?).await
+    }
+
+    async fn get_review_file_summaries(
         &self,
         review_id: String,
     ) -> anyhow::Result<Vec<ReviewFileSummary>> {
-        smol::block_on(self.get_review_file_summaries(review_id.parse()?).compat())
+        self.get_review_file_summaries(review_id.parse()?).await
     }
 
-    fn get_review_file_changes(
+    async fn get_review_file_changes(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
     ) -> anyhow::Result<ReviewFileChanges> {
-        smol::block_on(
-            self.get_review_file_changes(review_id.parse()?, file_path, revision)
-                .compat(),
-        )
+        self.get_review_file_changes(review_id.parse()?, file_path, revision).await
     }
 
-    fn mark_file_read(
+    async fn mark_file_read(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
         read: bool,
     ) -> anyhow::Result<()> {
-        smol::block_on(self.mark_file_read(review_id.parse()?, file_path, revision, read))
+        self.mark_file_read(review_id.parse()?, file_path, revision, read).await
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.submit_review_verdict(review_id.parse()?, verdict, body).await
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        self.set_discussion_resolved(review_id.parse()?, discussion_id, resolved).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.create_file_discussion(review_id.parse()?, file_path, revision, line, text).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.create_review_discussion(review_id.parse()?, text).await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.reply_to_discussion(review_id.parse()?, discussion_id, text).await
     }
 }
 
@@ -361,6 +716,28 @@ impl From<&PublicUser> for User {
     }
 }
 
+/// Review threads are only exposed via graphql, discussions are identified by the
+/// database id of their first comment.
+fn thread_root_comment_id(
+    thread: &graphql::queries::get_review_threads::GetReviewThreadsRepositoryPullRequestReviewThreadsNodes,
+) -> Option<i64> {
+    thread
+        .comments
+        .nodes
+        .as_ref()?
+        .iter()
+        .flatten()
+        .next()?
+        .database_id
+}
+
+#[derive(Clone)]
+enum FileContent {
+    Text(String),
+    Binary,
+    Unavailable,
+}
+
 struct UserCache(HashMap<String, User>);
 
 impl UserCache {
@@ -393,8 +770,8 @@ impl FromStr for ReviewId {
     }
 }
 
-impl ToString for ReviewId {
-    fn to_string(&self) -> String {
-        format!("{}/{}/{}/{}", self.owner, self.repo, self.id, self.node_id)
+impl Display for ReviewId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}/{}/{}", self.owner, self.repo, self.id, self.node_id)
     }
 }
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use gitlab::api::{ApiError, AsyncClient, RestClient};
+use http::header::{ETAG, IF_NONE_MATCH};
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode};
+use parking_lot::RwLock;
+use url::Url;
+
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+
+/// Wraps a gitlab client to send conditional requests using the etags of previous responses.
+///
+/// When Gitlab answers with `304 Not Modified` the previous response is replayed,
+/// including its headers so pagination keeps working.
+pub struct EtagClient<C> {
+    inner: C,
+    responses: RwLock<HashMap<String, CachedResponse>>,
+}
+
+struct CachedResponse {
+    etag: HeaderValue,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl<C> EtagClient<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            responses: Default::default(),
+        }
+    }
+}
+
+impl<C: RestClient> RestClient for EtagClient<C> {
+    type Error = C::Error;
+
+    fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.inner.rest_endpoint(endpoint)
+    }
+}
+
+#[async_trait]
+impl<C: AsyncClient + Send + Sync> AsyncClient for EtagClient<C> {
+    async fn rest_async(
+        &self,
+        mut request: http::request::Builder,
+        body: Vec<u8>,
+    ) -> Result<Response<Bytes>, ApiError<Self::Error>> {
+        let cache_key = (request.method_ref() == Some(&Method::GET))
+            .then(|| request.uri_ref().map(|uri| uri.to_string()))
+            .flatten();
+        if let Some(key) = cache_key.as_ref() {
+            if let Some(cached) = self.responses.read().get(key) {
+                request = request.header(IF_NONE_MATCH, cached.etag.clone());
+            }
+        }
+        let response = self.inner.rest_async(request, body).await?;
+        let Some(key) = cache_key else {
+            return Ok(response);
+        };
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = self.responses.read().get(&key) {
+                let mut replayed = Response::new(cached.body.clone());
+                *replayed.headers_mut() = cached.headers.clone();
+
+                return Ok(replayed);
+            }
+        }
+        if response.status().is_success() {
+            if let Some(etag) = response.headers().get(ETAG) {
+                let cached = CachedResponse {
+                    etag: etag.clone(),
+                    headers: response.headers().clone(),
+                    body: response.body().clone(),
+                };
+                self.responses.write().insert(key, cached);
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// Sends the requests of a gitlab client through the scheduler of the module,
+/// with the timeout and retries of its connection settings.
+pub struct ScheduledClient<C> {
+    inner: C,
+    scheduler: Arc<Scheduler>,
+    policy: RetryPolicy,
+}
+
+/// Failure of a [`ScheduledClient`], either of the gitlab client or of the scheduling around it.
+#[derive(Debug)]
+pub struct ClientError(anyhow::Error);
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.0.as_ref())
+    }
+}
+
+impl<C> ScheduledClient<C> {
+    pub fn new(inner: C, scheduler: Arc<Scheduler>, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            scheduler,
+            policy,
+        }
+    }
+}
+
+impl<C: RestClient> RestClient for ScheduledClient<C> {
+    type Error = ClientError;
+
+    fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        self.inner
+            .rest_endpoint(endpoint)
+            .map_err(|err| err.map_client(|err| ClientError(err.into())))
+    }
+}
+
+#[async_trait]
+impl<C: AsyncClient + Send + Sync> AsyncClient for ScheduledClient<C> {
+    async fn rest_async(
+        &self,
+        request: http::request::Builder,
+        body: Vec<u8>,
+    ) -> Result<Response<Bytes>, ApiError<Self::Error>> {
+        let client_error = |err: anyhow::Error| ApiError::client(ClientError(err));
+        // builders can't be cloned, every attempt gets a new one with the same parts
+        let (parts, ()) = request.body(()).map_err(|err| client_error(err.into()))?.into_parts();
+        let idempotent = parts.method == Method::GET;
+        let _permit = self.scheduler.acquire().await.map_err(client_error)?;
+        let (parts, body) = (&parts, &body);
+        let response = self
+            .policy
+            .run(idempotent, || async move {
+                let mut request = http::Request::builder()
+                    .method(parts.method.clone())
+                    .uri(parts.uri.clone())
+                    .version(parts.version);
+                if let Some(headers) = request.headers_mut() {
+                    headers.extend(parts.headers.clone());
+                }
+                let response = self.inner.rest_async(request, body.clone()).await?;
+
+                anyhow::Ok(response)
+            })
+            .await
+            .map_err(client_error)?;
+        self.scheduler.record(|name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(str::to_string)
+        });
+
+        Ok(response)
+    }
+}
+
+/// Classifies the failures of the gitlab client, `None` for causes which didn't come from it.
+pub(crate) fn classify_error(cause: &(dyn std::error::Error + 'static), message: &str) -> Option<crate::error::ApiError> {
+    if let Some(gitlab::GitlabError::Http { status }) = cause.downcast_ref::<gitlab::GitlabError>() {
+        return Some(crate::error::ApiError::from_status(status.as_u16(), message.to_string(), None));
+    }
+    if let Some(error) = cause.downcast_ref::<ApiError<gitlab::RestError>>() {
+        return classify_api_error(error, message);
+    }
+    if let Some(error) = cause.downcast_ref::<ApiError<ClientError>>() {
+        return classify_api_error(error, message);
+    }
+
+    None
+}
+
+fn classify_api_error<E: std::error::Error + Send + Sync + 'static>(
+    error: &ApiError<E>,
+    message: &str,
+) -> Option<crate::error::ApiError> {
+    match error {
+        ApiError::GitlabService { status, .. } => {
+            Some(crate::error::ApiError::from_status(status.as_u16(), message.to_string(), None))
+        }
+        // Gitlab error messages start with the status code, e.g. "404 Project Not Found"
+        ApiError::Gitlab { msg } => {
+            let status = msg.split_whitespace().next()?.parse().ok()?;
+            Some(crate::error::ApiError::from_status(status, message.to_string(), None))
+        }
+        _ => None,
+    }
+}
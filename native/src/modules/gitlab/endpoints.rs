@@ -0,0 +1,78 @@
+use gitlab::api::endpoint_prelude::*;
+
+/// Adds a note to an existing merge request discussion.
+///
+/// Not provided by the `gitlab` crate.
+pub struct CreateMergeRequestDiscussionNote<'a> {
+    pub project: u64,
+    pub merge_request: u64,
+    pub discussion: Cow<'a, str>,
+    pub body: Cow<'a, str>,
+}
+
+impl<'a> Endpoint for CreateMergeRequestDiscussionNote<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!(
+            "projects/{}/merge_requests/{}/discussions/{}/notes",
+            self.project, self.merge_request, self.discussion,
+        )
+        .into()
+    }
+
+    fn body(&self) -> Result<Option<(&'static str, Vec<u8>)>, BodyError> {
+        let mut params = FormParams::default();
+        params.push("body", self.body.as_ref());
+
+        params.into_body()
+    }
+}
+
+/// Resolves or unresolves a merge request discussion.
+///
+/// Not provided by the `gitlab` crate.
+pub struct ResolveMergeRequestDiscussion<'a> {
+    pub project: u64,
+    pub merge_request: u64,
+    pub discussion: Cow<'a, str>,
+    pub resolved: bool,
+}
+
+impl<'a> Endpoint for ResolveMergeRequestDiscussion<'a> {
+    fn method(&self) -> Method {
+        Method::PUT
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!(
+            "projects/{}/merge_requests/{}/discussions/{}",
+            self.project, self.merge_request, self.discussion,
+        )
+        .into()
+    }
+
+    fn body(&self) -> Result<Option<(&'static str, Vec<u8>)>, BodyError> {
+        let mut params = FormParams::default();
+        params.push("resolved", self.resolved);
+
+        params.into_body()
+    }
+}
+
+/// Details of the personal access token used for the request, including its scopes.
+///
+/// Not provided by the `gitlab` crate, Gitlab answers with 404 for other kinds of tokens.
+pub struct CurrentPersonalAccessToken;
+
+impl Endpoint for CurrentPersonalAccessToken {
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        "personal_access_tokens/self".into()
+    }
+}
@@ -1,119 +1,319 @@
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use gitlab::{api, Gitlab, MergeRequest, Project, ProjectId, UserBasic, MergeRequestState, Discussion, Note, MergeRequestChanges, RepoDiff};
+use std::sync::Arc;
+use gitlab::{api, AsyncGitlab, GitlabBuilder, MergeRequest, Project, ProjectId, UserBasic, MergeRequestState, Discussion, Note, MergeRequestChanges, RepoDiff};
 use gitlab::api::projects::Projects;
 use gitlab::api::projects::merge_requests::{self, MergeRequests};
-use gitlab::api::projects::merge_requests::discussions::MergeRequestDiscussions;
-use gitlab::api::Query;
+use gitlab::api::projects::merge_requests::discussions::{CreateMergeRequestDiscussion, MergeRequestDiscussions, Position, TextPosition};
+use gitlab::api::AsyncQuery;
+use async_trait::async_trait;
+use itertools::Itertools;
+use serde::Deserialize;
 use url::Url;
-use crate::{ChangeType, Review, ReviewComment, ReviewDiscussion, ReviewFileChanges, ReviewFileDiscussion, ReviewFileSummary, ReviewModule, ReviewState, User};
+use crate::{Authentication, ChangeType, ConnectionSettings, RateLimit, DiffSide, LineRange, Review, ReviewComment, ReviewDiscussion, ReviewFileChanges, ReviewFileDiscussion, ReviewFileSummary, ReviewModule, ReviewState, ReviewVerdict, User};
+use crate::cache::Cache;
+use crate::diff::diff_files;
+use crate::error::ApiError;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
 use crate::util::split_file_name;
 
+use self::client::{EtagClient, ScheduledClient};
+pub(crate) use self::client::classify_error;
+
+mod client;
+mod endpoints;
+
 pub struct GitlabModule {
-    client: Gitlab
+    client: EtagClient<ScheduledClient<AsyncGitlab>>,
+    scheduler: Arc<Scheduler>,
+    /// File contents by path and commit, `None` for binary files
+    file_contents: Cache<(String, String), Option<String>>,
 }
 
 impl GitlabModule {
-    pub fn new(url: String, token: String) -> anyhow::Result<Self> {
+    pub async fn new(url: String, token: String, connection: &ConnectionSettings) -> anyhow::Result<Self> {
+        let policy = RetryPolicy::from(connection);
         let scheme = {
-            let url = Url::parse(&url)?;
+            let url = Url::parse(&url).map_err(|err| ApiError::InvalidConfig { message: format!("Invalid Gitlab url: {err}") })?;
             url.scheme().to_string()
         };
         let host = url.strip_prefix(&format!("{scheme}://")).unwrap();
-        let client = if scheme == "http" {
-            Gitlab::new_insecure(host, token)?
-        }else {
-            Gitlab::new(host, token)?
-        };
+        let mut builder = GitlabBuilder::new(host, token);
+        if scheme == "http" {
+            builder.insecure();
+        }
+        // the builder already requests the current user
+        let client = tokio::time::timeout(policy.timeout, builder.build_async())
+            .await
+            .map_err(|_| policy.timeout_error())??;
+        let scheduler = Arc::new(Scheduler::new());
 
         Ok(Self {
-            client
+            client: EtagClient::new(ScheduledClient::new(client, scheduler.clone(), policy)),
+            scheduler,
+            file_contents: Cache::permanent(),
         })
     }
 
-    fn get_projects(&self) -> anyhow::Result<Vec<Project>> {
+    async fn get_projects(&self) -> anyhow::Result<Vec<Project>> {
         let endpoint = Projects::builder()
             .membership(true)
             .with_merge_requests_enabled(true)
             .build()?;
         let projects = api::paged(endpoint, api::Pagination::All)
-            .query(&self.client)?;
+            .query_async(&self.client).await?;
 
         Ok(projects)
     }
 
-    fn get_merge_requests_for_project(&self, project_id: ProjectId) -> anyhow::Result<Vec<MergeRequest>> {
+    async fn get_merge_requests_for_project(&self, project_id: ProjectId) -> anyhow::Result<Vec<MergeRequest>> {
         let endpoint = MergeRequests::builder()
             .project(project_id.value())
             .state(merge_requests::MergeRequestState::Opened)
             .build()?;
         let merge_requests = api::paged(endpoint, api::Pagination::All)
-            .query(&self.client)?;
+            .query_async(&self.client).await?;
 
         Ok(merge_requests)
     }
 
-    fn get_merge_request_discussions(&self, review_id: ReviewId) -> anyhow::Result<Vec<Discussion>> {
+    async fn get_merge_request_discussions(&self, review_id: ReviewId) -> anyhow::Result<Vec<Discussion>> {
         let endpoint = MergeRequestDiscussions::builder()
             .project(review_id.project)
             .merge_request(review_id.id)
             .build()?;
         let discussions = api::paged(endpoint, api::Pagination::All)
-            .query(&self.client)?;
+            .query_async(&self.client).await?;
 
         Ok(discussions)
     }
 
-    fn get_merge_request_changes(&self, review_id: ReviewId) -> anyhow::Result<MergeRequestChanges> {
+    async fn get_merge_request_changes(&self, review_id: ReviewId) -> anyhow::Result<MergeRequestChanges> {
         let endpoint = merge_requests::MergeRequestChanges::builder()
             .project(review_id.project)
             .merge_request(review_id.id)
             .build()?;
-        let changes = endpoint.query(&self.client)?;
+        let changes = endpoint.query_async(&self.client).await?;
 
         Ok(changes)
     }
 
-    fn get_review_file_changes(&self, review_id: ReviewId, file_path: String, revision: String) -> anyhow::Result<ReviewFileChanges> {
-        let endpoint = gitlab::api::projects::repository::files::FileRaw::builder()
+    async fn get_merge_request(&self, review_id: &ReviewId) -> anyhow::Result<MergeRequest> {
+        let endpoint = merge_requests::MergeRequest::builder()
             .project(review_id.project)
-            .file_path(&file_path)
-            .ref_(&revision)
+            .merge_request(review_id.id)
             .build()?;
-        let file = api::raw(endpoint).query(&self.client)?;
-        let file = String::from_utf8(file)?;
+        let merge_request = endpoint.query_async(&self.client).await?;
+
+        Ok(merge_request)
+    }
+
+    async fn get_review_file_changes(&self, review_id: ReviewId, file_path: String, revision: String) -> anyhow::Result<ReviewFileChanges> {
+        let changes = self.get_merge_request_changes(review_id.clone()).await?;
+        let base_revision = changes.diff_refs
+            .and_then(|diff_refs| diff_refs.base_sha)
+            .map(|sha| sha.value().clone())
+            .ok_or_else(|| anyhow::anyhow!("Merge request has no base revision"))?;
+        let diff = changes.changes.into_iter()
+            .find(|diff| diff.new_path == file_path)
+            .ok_or_else(|| ApiError::NotFound { message: "File is not part of the review".into() })?;
+        let old_path = (!diff.new_file).then_some(diff.old_path);
+        let new_path = (!diff.deleted_file).then_some(diff.new_path);
+
+        let old_content = async {
+            match old_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &base_revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let new_content = async {
+            match new_path.as_ref() {
+                Some(path) => self.get_file_content(&review_id, path, &revision).await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (old_content, new_content) = futures::future::try_join(old_content, new_content).await?;
+        let hunks = match (old_content, new_content) {
+            (Some(None), _) | (_, Some(None)) => vec![],
+            (old, new) => diff_files(old.flatten().as_deref(), new.flatten().as_deref()),
+        };
 
         Ok(ReviewFileChanges {
-            text: file
+            old_path,
+            new_path,
+            hunks,
         })
     }
+
+    /// Returns `None` for binary files
+    async fn get_file_content(&self, review_id: &ReviewId, file_path: &str, revision: &str) -> anyhow::Result<Option<String>> {
+        let key = (file_path.to_string(), revision.to_string());
+        self.file_contents.get_or_fetch(key, self.fetch_file_content(review_id, file_path, revision)).await
+    }
+
+    async fn fetch_file_content(&self, review_id: &ReviewId, file_path: &str, revision: &str) -> anyhow::Result<Option<String>> {
+        let endpoint = gitlab::api::projects::repository::files::FileRaw::builder()
+            .project(review_id.project)
+            .file_path(file_path)
+            .ref_(revision)
+            .build()?;
+        let file = api::raw(endpoint).query_async(&self.client).await?;
+
+        Ok(String::from_utf8(file).ok())
+    }
+
+    /// Gitlab has no state for requested changes, asking for them only withdraws the approval of the user.
+    async fn submit_review_verdict(&self, review_id: ReviewId, verdict: ReviewVerdict, body: Option<String>) -> anyhow::Result<()> {
+        let body = body.filter(|body| !body.is_empty());
+        match verdict {
+            ReviewVerdict::Approve => {
+                let endpoint = merge_requests::ApproveMergeRequest::builder()
+                    .project(review_id.project)
+                    .merge_request(review_id.id)
+                    .build()?;
+                api::ignore(endpoint).query_async(&self.client).await?;
+            }
+            ReviewVerdict::RequestChanges => {
+                let endpoint = merge_requests::UnapproveMergeRequest::builder()
+                    .project(review_id.project)
+                    .merge_request(review_id.id)
+                    .build()?;
+                api::ignore(endpoint).query_async(&self.client).await?;
+            }
+            // without a body there would be nothing to submit
+            ReviewVerdict::Comment if body.is_none() => {
+                return Err(ApiError::Provider { message: "A comment needs a body".into() }.into());
+            }
+            ReviewVerdict::Comment => {}
+        }
+        if let Some(body) = body {
+            self.create_review_discussion(review_id, body).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_discussion_resolved(&self, review_id: ReviewId, discussion_id: String, resolved: bool) -> anyhow::Result<()> {
+        let endpoint = endpoints::ResolveMergeRequestDiscussion {
+            project: review_id.project,
+            merge_request: review_id.id,
+            discussion: discussion_id.into(),
+            resolved,
+        };
+        api::ignore(endpoint).query_async(&self.client).await?;
+
+        Ok(())
+    }
+
+    async fn create_file_discussion(&self, review_id: ReviewId, file_path: String, line: u32, text: String) -> anyhow::Result<()> {
+        let diff_refs = self.get_merge_request(&review_id).await?
+            .diff_refs
+            .ok_or_else(|| anyhow::anyhow!("Merge request has no diff refs"))?;
+        let sha = |sha: Option<gitlab::ObjectId>| sha.map(|sha| sha.value().clone())
+            .ok_or_else(|| anyhow::anyhow!("Merge request diff refs are incomplete"));
+        let position = Position::builder()
+            .base_sha(sha(diff_refs.base_sha)?)
+            .start_sha(sha(diff_refs.start_sha)?)
+            .head_sha(sha(diff_refs.head_sha)?)
+            .text_position(TextPosition::builder()
+                .new_path(file_path.as_str())
+                .new_line(line as u64)
+                .build()?)
+            .build()?;
+        let endpoint = CreateMergeRequestDiscussion::builder()
+            .project(review_id.project)
+            .merge_request(review_id.id)
+            .body(text)
+            .position(position)
+            .build()?;
+        api::ignore(endpoint).query_async(&self.client).await?;
+
+        Ok(())
+    }
+
+    async fn create_review_discussion(&self, review_id: ReviewId, text: String) -> anyhow::Result<()> {
+        let endpoint = CreateMergeRequestDiscussion::builder()
+            .project(review_id.project)
+            .merge_request(review_id.id)
+            .body(text)
+            .build()?;
+        api::ignore(endpoint).query_async(&self.client).await?;
+
+        Ok(())
+    }
+
+    async fn reply_to_discussion(&self, review_id: ReviewId, discussion_id: String, text: String) -> anyhow::Result<()> {
+        let endpoint = endpoints::CreateMergeRequestDiscussionNote {
+            project: review_id.project,
+            merge_request: review_id.id,
+            discussion: discussion_id.into(),
+            body: text.into(),
+        };
+        api::ignore(endpoint).query_async(&self.client).await?;
+
+        Ok(())
+    }
 }
 
+#[async_trait]
 impl ReviewModule for GitlabModule {
-    fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
-        let projects = self.get_projects()?;
-        let mut reviews = vec![];
-        for project in projects {
-            let merge_requests = self.get_merge_requests_for_project(project.id)?;
-            for merge_request in merge_requests {
-                let review = merge_request.into();
-                reviews.push(review);
+    fn required_scopes(&self) -> &'static [&'static str] {
+        &["read_api"]
+    }
+
+    fn rate_limit(&self) -> RateLimit {
+        self.scheduler.rate_limit()
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let user: UserBasic = api::users::CurrentUser::builder()
+            .build()?
+            .query_async(&self.client).await?;
+        let token: Option<PersonalAccessToken> = endpoints::CurrentPersonalAccessToken
+            .query_async(&self.client).await
+            .ok();
+        let scopes = token.map(|token| {
+            let mut scopes = token.scopes;
+            // `api` grants everything `read_api` does
+            if scopes.iter().any(|scope| scope == "api") {
+                scopes.push("read_api".into());
             }
-        }
+            scopes.into_iter().unique().collect()
+        });
+
+        Ok(Authentication {
+            user: user.into(),
+            scopes,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        let projects = self.get_projects().await?;
+        // the scheduler keeps the amount of concurrent requests in check
+        let merge_requests = projects
+            .into_iter()
+            .map(|project| self.get_merge_requests_for_project(project.id));
+        let reviews = futures::future::try_join_all(merge_requests).await?
+            .into_iter()
+            .flatten()
+            .map(Review::from)
+            .collect();
 
         Ok(reviews)
     }
 
-    fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
         let review_id = ReviewId::from_str(&review_id)?;
-        let discussions = self.get_merge_request_discussions(review_id)?;
+        let discussions = self.get_merge_request_discussions(review_id).await?;
         let discussions = discussions.into_iter().map(ReviewDiscussion::from).collect();
 
         Ok(discussions)
     }
 
-    fn get_review_file_summaries(&self, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
+    async fn get_review_file_summaries(&self, review_id: String) -> anyhow::Result<Vec<ReviewFileSummary>> {
         let review_id = ReviewId::from_str(&review_id)?;
-        let changes = self.get_merge_request_changes(review_id)?;
+        let changes = self.get_merge_request_changes(review_id).await?;
 
         let revision = changes.diff_refs
             .and_then(|ref_| ref_.head_sha)
@@ -141,16 +341,54 @@ impl ReviewModule for GitlabModule {
             .collect())
     }
 
-    fn get_review_file_changes(&self, review_id: String, file_path: String, revision: String) -> anyhow::Result<ReviewFileChanges> {
+    async fn get_review_file_changes(&self, review_id: String, file_path: String, revision: String) -> anyhow::Result<ReviewFileChanges> {
         let review_id = ReviewId::from_str(&review_id)?;
 
-        self.get_review_file_changes(review_id, file_path, revision)
+        self.get_review_file_changes(review_id, file_path, revision).await
     }
 
-    fn mark_file_read(&self, review_id: String, file_path: String, revision: String, read: bool) -> anyhow::Result<()> {
-        // TODO
-        Ok(())
+    fn tracks_read_state(&self) -> bool {
+        false
+    }
+
+    async fn mark_file_read(&self, _review_id: String, _file_path: String, _revision: String, _read: bool) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Gitlab has no api for viewed files, read files are tracked locally"))
     }
+
+    async fn submit_review_verdict(&self, review_id: String, verdict: ReviewVerdict, body: Option<String>) -> anyhow::Result<()> {
+        let review_id = ReviewId::from_str(&review_id)?;
+
+        self.submit_review_verdict(review_id, verdict, body).await
+    }
+
+    async fn set_discussion_resolved(&self, review_id: String, discussion_id: String, resolved: bool) -> anyhow::Result<()> {
+        let review_id = ReviewId::from_str(&review_id)?;
+
+        self.set_discussion_resolved(review_id, discussion_id, resolved).await
+    }
+
+    async fn create_file_discussion(&self, review_id: String, file_path: String, _revision: String, line: u32, text: String) -> anyhow::Result<()> {
+        let review_id = ReviewId::from_str(&review_id)?;
+
+        self.create_file_discussion(review_id, file_path, line, text).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        let review_id = ReviewId::from_str(&review_id)?;
+
+        self.create_review_discussion(review_id, text).await
+    }
+
+    async fn reply_to_discussion(&self, review_id: String, discussion_id: String, text: String) -> anyhow::Result<()> {
+        let review_id = ReviewId::from_str(&review_id)?;
+
+        self.reply_to_discussion(review_id, discussion_id, text).await
+    }
+}
+
+#[derive(Deserialize)]
+struct PersonalAccessToken {
+    scopes: Vec<String>,
 }
 
 impl From<MergeRequest> for Review {
@@ -162,6 +400,7 @@ impl From<MergeRequest> for Review {
             }.to_string(),
             open: mr.state == MergeRequestState::Opened,
             state: ReviewState::Pending, // TODO
+            updated_at: Some(mr.updated_at.timestamp_millis() as u64),
             title: mr.title,
             authors: vec![mr.author.into()],
             branch_name: mr.source_branch,
@@ -181,11 +420,25 @@ impl From<Discussion> for ReviewDiscussion {
                 .and_then(|note| note.position.clone())
                 .map(|note_position| {
                     let (file_path_segments, file_name) = split_file_name(&note_position.new_path);
+                    let (line, side) = match (note_position.new_line, note_position.old_line) {
+                        (Some(line), _) => (Some(line), DiffSide::New),
+                        (None, Some(line)) => (Some(line), DiffSide::Old),
+                        (None, None) => (None, DiffSide::New),
+                    };
+                    let revision = match side {
+                        DiffSide::New => note_position.head_sha,
+                        DiffSide::Old => note_position.base_sha,
+                    };
                     ReviewFileDiscussion {
                         file_name,
                         file_path: note_position.new_path,
                         file_path_segments,
-                        revision: Some(note_position.head_sha.value().to_string()),
+                        revision: Some(revision.value().to_string()),
+                        lines: line.map(|line| LineRange {
+                            start: line as u32,
+                            end: line as u32,
+                        }),
+                        side,
                     }
                 }),
             resolved: discussion.notes.first().and_then(|note| note.resolved).unwrap_or_default(),
@@ -254,8 +507,8 @@ impl FromStr for ReviewId {
     }
 }
 
-impl ToString for ReviewId {
-    fn to_string(&self) -> String {
-        format!("{}/{}", self.project, self.id)
+impl Display for ReviewId {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.project, self.id)
     }
 }
@@ -0,0 +1,346 @@
+//! Provider serving reviews from a local fixture directory, for demos and ui development.
+//!
+//! The fixture directory is laid out as follows, all files except `reviews.json` are optional:
+//!
+//! ```text
+//! reviews.json                    list of reviews
+//! <review id>/discussions.json    discussions of the review
+//! <review id>/files.json          file summaries of the review
+//! <review id>/base/<file path>    file contents before the review, missing for added files
+//! <review id>/head/<file path>    file contents of the review, missing for removed files
+//! ```
+//!
+//! Fixtures are JSON only, there is no YAML parser among the dependencies.
+//! Writes are only kept in memory and are lost when the module is reconfigured, except for
+//! read files which are left to the read state store like for providers without an api for them.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+
+use crate::diff::diff_files;
+use crate::error::ApiError;
+use crate::models::*;
+use crate::util::split_file_name;
+use crate::ReviewModule;
+
+pub struct MockModule {
+    path: PathBuf,
+    state: RwLock<MockState>,
+}
+
+#[derive(Default)]
+struct MockState {
+    reviews: Vec<Review>,
+    discussions: HashMap<String, Vec<ReviewDiscussion>>,
+    files: HashMap<String, Vec<ReviewFileSummary>>,
+    next_id: u64,
+}
+
+impl MockModule {
+    pub fn new(path: String) -> anyhow::Result<Self> {
+        let path = PathBuf::from(path);
+        let reviews: Vec<Review> = read_json(&path.join("reviews.json"))?.ok_or_else(|| {
+            ApiError::InvalidConfig {
+                message: format!("{} contains no reviews.json", path.display()),
+            }
+        })?;
+        let mut state = MockState {
+            next_id: 1,
+            ..Default::default()
+        };
+        for review in reviews.iter() {
+            let review_path = fixture_path(&path, &[&review.id])?;
+            if let Some(discussions) = read_json(&review_path.join("discussions.json"))? {
+                state.discussions.insert(review.id.clone(), discussions);
+            }
+            if let Some(files) = read_json(&review_path.join("files.json"))? {
+                state.files.insert(review.id.clone(), files);
+            }
+        }
+        state.reviews = reviews;
+
+        Ok(Self {
+            path,
+            state: RwLock::new(state),
+        })
+    }
+
+    fn read_content(&self, review_id: &str, side: &str, file_path: &str) -> anyhow::Result<Option<String>> {
+        let path = fixture_path(&self.path, &[review_id, side, file_path])?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read_to_string(path)?))
+    }
+
+    fn update_discussion(
+        &self,
+        review_id: &str,
+        discussion_id: &str,
+        update: impl FnOnce(&mut ReviewDiscussion),
+    ) -> anyhow::Result<()> {
+        let mut state = self.state.write();
+        let discussion = state
+            .discussions
+            .get_mut(review_id)
+            .and_then(|discussions| discussions.iter_mut().find(|discussion| discussion.id == discussion_id))
+            .ok_or_else(|| ApiError::NotFound {
+                message: "Unknown discussion id".into(),
+            })?;
+        update(discussion);
+        state.touch(review_id);
+
+        Ok(())
+    }
+
+    fn add_discussion(&self, review_id: &str, text: String, file: Option<ReviewFileDiscussion>) {
+        let mut state = self.state.write();
+        let comment = state.comment(text);
+        let discussion = ReviewDiscussion {
+            id: state.next_id().to_string(),
+            comments: vec![comment],
+            resolved: false,
+            file,
+        };
+        state
+            .discussions
+            .entry(review_id.to_string())
+            .or_default()
+            .push(discussion);
+        state.touch(review_id);
+    }
+}
+
+impl MockState {
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        id
+    }
+
+    fn comment(&mut self, text: String) -> ReviewComment {
+        ReviewComment {
+            id: format!("mock-{}", self.next_id()),
+            user: mock_user(),
+            text,
+            timestamp: unix_now_millis(),
+        }
+    }
+
+    /// Marks the review as changed, like a provider does on every write.
+    fn touch(&mut self, review_id: &str) {
+        if let Some(review) = self.reviews.iter_mut().find(|review| review.id == review_id) {
+            review.updated_at = Some(unix_now_millis());
+        }
+    }
+}
+
+fn unix_now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Author of everything written through the module
+fn mock_user() -> User {
+    User {
+        name: "Mock User".into(),
+        avatar_url: None,
+    }
+}
+
+#[async_trait]
+impl ReviewModule for MockModule {
+    fn tracks_read_state(&self) -> bool {
+        false
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        Ok(Authentication {
+            user: mock_user(),
+            scopes: None,
+        })
+    }
+
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        Ok(self.state.read().reviews.clone())
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        Ok(self
+            .state
+            .read()
+            .discussions
+            .get(&review_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_review_file_summaries(
+        &self,
+        review_id: String,
+    ) -> anyhow::Result<Vec<ReviewFileSummary>> {
+        Ok(self
+            .state
+            .read()
+            .files
+            .get(&review_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_review_file_changes(
+        &self,
+        review_id: String,
+        file_path: String,
+        _revision: String,
+    ) -> anyhow::Result<ReviewFileChanges> {
+        let old = self.read_content(&review_id, "base", &file_path)?;
+        let new = self.read_content(&review_id, "head", &file_path)?;
+        if old.is_none() && new.is_none() {
+            return Err(ApiError::NotFound {
+                message: "File is not part of the review".into(),
+            }
+            .into());
+        }
+
+        Ok(ReviewFileChanges {
+            old_path: old.is_some().then(|| file_path.clone()),
+            new_path: new.is_some().then(|| file_path.clone()),
+            hunks: diff_files(old.as_deref(), new.as_deref()),
+        })
+    }
+
+    async fn mark_file_read(
+        &self,
+        _review_id: String,
+        _file_path: String,
+        _revision: String,
+        _read: bool,
+    ) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("Read files of the mock provider are tracked locally"))
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        self.update_discussion(&review_id, &discussion_id, |discussion| {
+            discussion.resolved = resolved;
+        })
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        {
+            let mut state = self.state.write();
+            let review = state
+                .reviews
+                .iter_mut()
+                .find(|review| review.id == review_id)
+                .ok_or_else(|| ApiError::NotFound {
+                    message: "Unknown review id".into(),
+                })?;
+            match verdict {
+                ReviewVerdict::Approve => review.state = ReviewState::Approved,
+                ReviewVerdict::RequestChanges => review.state = ReviewState::Rejected,
+                ReviewVerdict::Comment => {}
+            }
+            state.touch(&review_id);
+        }
+        if let Some(body) = body.filter(|body| !body.is_empty()) {
+            self.add_discussion(&review_id, body, None);
+        }
+
+        Ok(())
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let (file_path_segments, file_name) = split_file_name(&file_path);
+        let file = ReviewFileDiscussion {
+            file_name,
+            file_path,
+            file_path_segments,
+            revision: Some(revision),
+            lines: Some(LineRange {
+                start: line,
+                end: line,
+            }),
+            side: DiffSide::New,
+        };
+        self.add_discussion(&review_id, text, Some(file));
+
+        Ok(())
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.add_discussion(&review_id, text, None);
+
+        Ok(())
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let comment = self.state.write().comment(text);
+        self.update_discussion(&review_id, &discussion_id, |discussion| {
+            discussion.comments.push(comment);
+        })
+    }
+}
+
+/// Joins `components` onto the fixture directory, refusing paths which could lead outside of it.
+fn fixture_path(root: &Path, components: &[&str]) -> anyhow::Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in components {
+        let component = Path::new(component);
+        let is_inside = component.components().next().is_some()
+            && component.components().all(|component| matches!(component, Component::Normal(_)));
+        if !is_inside {
+            return Err(ApiError::NotFound {
+                message: format!("{} is not a fixture path", component.display()),
+            }
+            .into());
+        }
+        path.push(component);
+    }
+
+    Ok(path)
+}
+
+/// Returns `None` when the file doesn't exist
+fn read_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let value = serde_json::from_str(&content).map_err(|err| ApiError::InvalidConfig {
+        message: format!("Invalid fixture {}: {err}", path.display()),
+    })?;
+
+    Ok(Some(value))
+}
@@ -1,3 +1,7 @@
+pub mod bitbucket;
+pub mod gerrit;
+pub mod gitea;
 pub mod github;
 pub mod gitlab;
+pub mod mock;
 pub mod upsource;
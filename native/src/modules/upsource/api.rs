@@ -1,15 +1,25 @@
 use super::messages::*;
+use crate::error::ApiError;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 pub struct UpsourceApi {
     pub url: String,
     pub token: String,
+    pub scheduler: Scheduler,
+    policy: RetryPolicy,
 }
 
 impl UpsourceApi {
-    pub const fn new(url: String, token: String) -> Self {
-        Self { url, token }
+    pub fn new(url: String, token: String, policy: RetryPolicy) -> Self {
+        Self {
+            url,
+            token,
+            scheduler: Scheduler::new(),
+            policy,
+        }
     }
 
     pub async fn get_reviews(&self) -> anyhow::Result<Vec<ReviewDescriptorDTO>> {
@@ -94,36 +104,81 @@ impl UpsourceApi {
         Ok(())
     }
 
+    pub async fn get_file_content(
+        &self,
+        request: FileInRevisionDTO,
+    ) -> anyhow::Result<FileContentResponseDTO> {
+        self.rpc_request("getFileContent", request).await
+    }
+
+    pub async fn create_discussion(&self, request: CreateDiscussionRequestDTO) -> anyhow::Result<()> {
+        let _: VoidMessage = self.rpc_request("createDiscussion", request).await?;
+
+        Ok(())
+    }
+
+    pub async fn add_comment(&self, request: AddCommentRequestDTO) -> anyhow::Result<()> {
+        let _: VoidMessage = self.rpc_request("addComment", request).await?;
+
+        Ok(())
+    }
+
+    pub async fn resolve_discussion(
+        &self,
+        request: ResolveDiscussionRequestDTO,
+    ) -> anyhow::Result<()> {
+        let _: VoidMessage = self.rpc_request("resolveDiscussion", request).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_current_user(&self) -> anyhow::Result<CurrentUserResponseDTO> {
+        self.rpc_request("getCurrentUser", VoidMessage {}).await
+    }
+
+    pub async fn update_participant_in_review(
+        &self,
+        request: UpdateParticipantInReviewRequestDTO,
+    ) -> anyhow::Result<()> {
+        let _: VoidMessage = self
+            .rpc_request("updateParticipantInReview", request)
+            .await?;
+
+        Ok(())
+    }
+
     async fn rpc_request<TResponse: DeserializeOwned>(
         &self,
         method: &str,
         body: impl Serialize,
+    ) -> anyhow::Result<TResponse> {
+        // all rpc methods are sent as POST, only the reads start with `get`
+        let idempotent = method.starts_with("get");
+        let body = serde_json::to_value(body)?;
+        let _permit = self.scheduler.acquire().await?;
+
+        self.policy.run(idempotent, || self.post(method, &body)).await
+    }
+
+    async fn post<TResponse: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &serde_json::Value,
     ) -> anyhow::Result<TResponse> {
         let mut res = surf::post(format!("{}/~rpc/{method}", &self.url))
             .content_type("application/json")
             .header("Authorization", format!("Bearer {}", &self.token))
-            .body_json(&body)
+            .body_json(body)
             .map_err(|err| anyhow::anyhow!("{err:?}"))?
             .await
-            .map_err(|err| anyhow::anyhow!("{:?}", err))?;
+            .map_err(|err| ApiError::Network { message: err.to_string() })?;
+        self.scheduler.record(|name| res.header(name).map(|value| value.as_str().to_string()));
 
-        #[cfg(debug_assertions)]
         if !res.status().is_success() {
-            eprintln!("{:?}", res.status());
-            eprintln!(
-                "{:?}",
-                res.body_string()
-                    .await
-                    .map_err(|err| anyhow::anyhow!("{:?}", err))?
-            );
-            anyhow::bail!("Upsource api failure");
+            let body = res.body_string().await.unwrap_or_default();
+            return Err(ApiError::from_status(res.status().into(), format!("{}: {body}", res.status()), None).into());
         }
 
-        anyhow::ensure!(
-            res.status().is_success(),
-            "Upsource api returned non success status code"
-        );
-
         let res: ApiResult<TResponse> = res
             .body_json()
             .await
@@ -138,5 +193,5 @@ struct ApiResult<T> {
     pub result: T,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct VoidMessage {}
@@ -57,9 +57,9 @@ pub struct ReviewIdDTO {
     pub review_id: String,
 }
 
-impl ToString for ReviewIdDTO {
-    fn to_string(&self) -> String {
-        format!("{}/{}", self.project_id, self.review_id)
+impl Display for ReviewIdDTO {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.project_id, self.review_id)
     }
 }
 
@@ -243,7 +243,7 @@ pub struct DiscussionInFileDTO {
     pub sync_result: Option<SyncResultEnum>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AnchorDTO {
     pub range: Option<RangeDTO>,
@@ -252,7 +252,7 @@ pub struct AnchorDTO {
     pub inline_in_revision: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RangeDTO {
     pub start_offset: u32,
@@ -569,3 +569,53 @@ pub struct FileInReviewReadStatusRequestDTO {
     pub revisions: RevisionsSetDTO,
     pub mark_as_unread: Option<bool>,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileContentResponseDTO {
+    pub text: String,
+    pub content_type: Option<FileContentTypeDTO>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateDiscussionRequestDTO {
+    pub anchor: AnchorDTO,
+    pub review_id: Option<String>,
+    pub text: String,
+    pub project_id: String,
+    pub markup_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddCommentRequestDTO {
+    pub text: String,
+    pub discussion_id: String,
+    pub project_id: String,
+    pub parent_id: Option<String>,
+    pub markup_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveDiscussionRequestDTO {
+    pub project_id: String,
+    pub discussion_id: String,
+    pub is_resolved: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentUserResponseDTO {
+    pub user_id: String,
+    pub is_server_admin: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateParticipantInReviewRequestDTO {
+    pub review_id: ReviewIdDTO,
+    pub state: ParticipantStateEnum,
+    pub user_id: String,
+}
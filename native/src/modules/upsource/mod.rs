@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
 use itertools::Itertools;
@@ -7,9 +7,12 @@ use crate::api::{
     ChangeType, Review, ReviewComment, ReviewDiscussion, ReviewFileChanges, ReviewFileDiscussion,
     ReviewFileSummary,
 };
-use crate::models::{ReviewState, User};
+use crate::cache::Cache;
+use crate::diff;
+use crate::models::{Authentication, ConnectionSettings, DiffLineType, RateLimit, DiffRange, DiffSide, LineRange, ReviewState, ReviewVerdict, User};
 use crate::util::split_file_name;
 use crate::ReviewModule;
+use async_trait::async_trait;
 
 use self::api::UpsourceApi;
 use self::messages::*;
@@ -19,12 +22,15 @@ mod messages;
 
 pub struct UpsourceModule {
     api: UpsourceApi,
+    /// File contents by path and revision, `None` for binary files
+    file_contents: Cache<(String, String), Option<String>>,
 }
 
 impl UpsourceModule {
-    pub const fn new(url: String, token: String) -> Self {
+    pub fn new(url: String, token: String, connection: &ConnectionSettings) -> Self {
         Self {
-            api: UpsourceApi::new(url, token),
+            api: UpsourceApi::new(url, token, connection.into()),
+            file_contents: Cache::permanent(),
         }
     }
 
@@ -36,7 +42,7 @@ impl UpsourceModule {
             .flat_map(|review| review.participants.iter())
             .map(|participant| participant.user_id.clone());
 
-        let users = self.get_user_info_cache(user_ids).await?;
+        let users = self.get_user_info_cache(user_ids.collect()).await?;
 
         let reviews = reviews
             .into_iter()
@@ -51,6 +57,7 @@ impl UpsourceModule {
                     title: review.title,
                     open: review.state == ReviewStateEnum::Open,
                     state: ReviewState::Pending,
+                    updated_at: Some(review.updated_at),
                     branch_name: review.branch.first().cloned().unwrap_or_default(),
                     authors: authors
                         .into_iter()
@@ -71,19 +78,71 @@ impl UpsourceModule {
         &self,
         review_id: ReviewIdDTO,
     ) -> anyhow::Result<Vec<ReviewDiscussion>> {
-        let discussions = self.api.get_review_summary_discussions(review_id).await?;
+        let project_id = review_id.project_id.clone();
+        let (discussions, summary_changes) = futures::future::try_join(
+            self.api.get_review_summary_discussions(review_id.clone()),
+            self.api.get_review_summary_changes(review_id),
+        )
+        .await?;
+        let base_revisions = summary_changes
+            .diff
+            .iter()
+            .flat_map(|diff| diff.diff.iter())
+            .filter_map(|item| item.old_file.as_ref())
+            .map(|file| file.revision_id.as_str())
+            .collect::<HashSet<_>>();
+        let head_revisions = summary_changes
+            .diff
+            .iter()
+            .flat_map(|diff| diff.diff.iter())
+            .map(|item| item.new_file.revision_id.as_str())
+            .collect::<HashSet<_>>();
 
         let user_ids = discussions
             .iter()
             .flat_map(|discussion| discussion.discussion_in_file.comments.iter())
             .map(|comment| comment.author_id.clone());
 
-        let users = self.get_user_info_cache(user_ids).await?;
+        let users = self.get_user_info_cache(user_ids.collect()).await?;
+
+        // Anchors are text offsets, the file contents are required to map them to lines
+        let anchored_files = discussions
+            .iter()
+            .filter(|discussion| discussion.discussion_in_file.anchor.range.is_some())
+            .filter_map(|discussion| {
+                anchor_revision(discussion).map(|revision| (discussion.file_name.clone(), revision))
+            })
+            .unique()
+            .collect();
+        let file_contents = self.get_file_contents(project_id, anchored_files).await;
 
         let discussions = discussions
             .into_iter()
             .map(|discussion| {
                 let (file_path_segments, file_name) = split_file_name(&discussion.file_name);
+                let revision = anchor_revision(&discussion);
+                // discussions on a revision before the review are anchored in the old file
+                let side = match revision.as_deref() {
+                    Some(revision) if base_revisions.contains(revision) && !head_revisions.contains(revision) => {
+                        DiffSide::Old
+                    }
+                    _ => DiffSide::New,
+                };
+                let lines = discussion
+                    .discussion_in_file
+                    .anchor
+                    .range
+                    .as_ref()
+                    .zip(revision.as_ref())
+                    .and_then(|(range, revision)| {
+                        let text =
+                            file_contents.get(&(discussion.file_name.clone(), revision.clone()))?;
+
+                        Some(LineRange {
+                            start: line_at_offset(text, range.start_offset),
+                            end: line_at_offset(text, range.end_offset),
+                        })
+                    });
                 ReviewDiscussion {
                     id: discussion.discussion_in_file.discussion_id,
                     comments: discussion
@@ -105,7 +164,9 @@ impl UpsourceModule {
                         file_name,
                         file_path_segments,
                         file_path: discussion.file_name,
-                        revision: discussion.revision_id,
+                        revision,
+                        lines,
+                        side,
                     }),
                 }
             })
@@ -114,6 +175,48 @@ impl UpsourceModule {
         Ok(discussions)
     }
 
+    /// Contents of the files which could be loaded, binary files and failed requests are left out
+    /// so only the discussions on them lack their lines.
+    async fn get_file_contents(
+        &self,
+        project_id: String,
+        files: Vec<(String, String)>,
+    ) -> HashMap<(String, String), String> {
+        let requests = files.into_iter().map(|(file_name, revision_id)| {
+            let request = FileInRevisionDTO {
+                project_id: project_id.clone(),
+                revision_id: revision_id.clone(),
+                file_name: file_name.clone(),
+            };
+            async move {
+                let key = (file_name, revision_id);
+                let content = self
+                    .file_contents
+                    .get_or_fetch(key.clone(), async {
+                        let content = self.api.get_file_content(request).await?;
+                        let is_text = content.content_type.as_ref().is_none_or(|content_type| content_type.is_text);
+
+                        anyhow::Ok(is_text.then_some(content.text))
+                    })
+                    .await;
+
+                (key, content)
+            }
+        });
+        let contents = futures::future::join_all(requests).await;
+
+        contents
+            .into_iter()
+            .filter_map(|((file_name, revision_id), content)| match content {
+                Ok(content) => Some(((file_name, revision_id), content?)),
+                Err(err) => {
+                    log::warn!("Loading {file_name} in {revision_id} failed: {err:#}");
+                    None
+                }
+            })
+            .collect()
+    }
+
     async fn get_review_summaries(
         &self,
         review_id: ReviewIdDTO,
@@ -157,9 +260,9 @@ impl UpsourceModule {
 
     async fn get_user_info_cache(
         &self,
-        user_ids: impl Iterator<Item = String>,
+        user_ids: Vec<String>,
     ) -> anyhow::Result<UserCache> {
-        let user_ids = user_ids.sorted().dedup().collect();
+        let user_ids = user_ids.into_iter().sorted().dedup().collect();
 
         let users = self.api.get_user_info(user_ids).await?;
         let users = users
@@ -198,8 +301,44 @@ impl UpsourceModule {
             })
             .await?;
 
+        let content_type = &response.content_type;
+        let lines = response
+            .text
+            .lines()
+            .enumerate()
+            .map(|(index, text)| {
+                let index = index as u32;
+                let line_type = if content_type.added_lines.contains(&index) {
+                    DiffLineType::Added
+                } else if content_type.removed_lines.contains(&index) {
+                    DiffLineType::Removed
+                } else {
+                    DiffLineType::Context
+                };
+
+                (line_type, text.to_string())
+            });
+        let changed_ranges = line_changed_ranges(
+            &response.text,
+            content_type
+                .added_ranges
+                .iter()
+                .chain(content_type.removed_ranges.iter()),
+        );
+        let hunks = if response.is_identical {
+            vec![]
+        } else {
+            let mut lines = diff::number_lines(lines);
+            for (line, changed_ranges) in lines.iter_mut().zip(changed_ranges) {
+                line.changed_ranges = changed_ranges;
+            }
+            diff::group_into_hunks(lines, diff::CONTEXT_LINES)
+        };
+
         Ok(ReviewFileChanges {
-            text: response.text,
+            old_path: response.old_file.map(|file| file.file_name),
+            new_path: response.new_file.map(|file| file.file_name),
+            hunks,
         })
     }
 
@@ -222,45 +361,224 @@ impl UpsourceModule {
             })
             .await
     }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: ReviewIdDTO,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        let state = match verdict {
+            ReviewVerdict::Approve => Some(ParticipantStateEnum::Accepted),
+            ReviewVerdict::RequestChanges => Some(ParticipantStateEnum::Rejected),
+            ReviewVerdict::Comment => None,
+        };
+        if let Some(state) = state {
+            let user = self.api.get_current_user().await?;
+            self.api
+                .update_participant_in_review(UpdateParticipantInReviewRequestDTO {
+                    review_id: review_id.clone(),
+                    state,
+                    user_id: user.user_id,
+                })
+                .await?;
+        }
+        if let Some(body) = body.filter(|body| !body.is_empty()) {
+            self.create_review_discussion(review_id, body).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: ReviewIdDTO,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        self.api
+            .resolve_discussion(ResolveDiscussionRequestDTO {
+                project_id: review_id.project_id,
+                discussion_id,
+                is_resolved: resolved,
+            })
+            .await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: ReviewIdDTO,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        let file = self
+            .api
+            .get_file_content(FileInRevisionDTO {
+                project_id: review_id.project_id.clone(),
+                revision_id: revision.clone(),
+                file_name: file_path.clone(),
+            })
+            .await?;
+        let range = line_range(&file.text, line)
+            .ok_or_else(|| anyhow::anyhow!("Line {line} is out of range for {file_path}"))?;
+
+        self.api
+            .create_discussion(CreateDiscussionRequestDTO {
+                anchor: AnchorDTO {
+                    range: Some(range),
+                    file_id: Some(file_path),
+                    revision_id: Some(revision),
+                    inline_in_revision: None,
+                },
+                review_id: Some(review_id.review_id),
+                text,
+                project_id: review_id.project_id,
+                markup_type: None,
+            })
+            .await
+    }
+
+    async fn create_review_discussion(
+        &self,
+        review_id: ReviewIdDTO,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.api
+            .create_discussion(CreateDiscussionRequestDTO {
+                anchor: AnchorDTO {
+                    range: None,
+                    file_id: None,
+                    revision_id: None,
+                    inline_in_revision: None,
+                },
+                review_id: Some(review_id.review_id),
+                text,
+                project_id: review_id.project_id,
+                markup_type: None,
+            })
+            .await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: ReviewIdDTO,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.api
+            .add_comment(AddCommentRequestDTO {
+                text,
+                discussion_id,
+                project_id: review_id.project_id,
+                parent_id: None,
+                markup_type: None,
+            })
+            .await
+    }
 }
 
+#[async_trait]
 impl ReviewModule for UpsourceModule {
-    fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
-        smol::block_on(self.get_reviews())
+    fn rate_limit(&self) -> RateLimit {
+        self.api.scheduler.rate_limit()
+    }
+
+    async fn get_authentication(&self) -> anyhow::Result<Authentication> {
+        let current_user = self.api.get_current_user().await?;
+        let users = self.get_user_info_cache(vec![current_user.user_id.clone()]).await?;
+
+        Ok(Authentication {
+            user: users.get_user(&current_user.user_id),
+            scopes: None,
+        })
     }
 
-    fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
-        smol::block_on(self.get_review_discussions(ReviewIdDTO::from_str(&review_id)?))
+    async fn get_reviews(&self) -> anyhow::Result<Vec<Review>> {
+        self.get_reviews().await
+    }
+
+    async fn get_review_discussions(&self, review_id: String) -> anyhow::Result<Vec<ReviewDiscussion>> {
+        self.get_review_discussions(ReviewIdDTO::from_str(&review_id)?).await
     }
 
-    fn get_review_file_summaries(
+    async fn get_review_file_summaries(
         &self,
         review_id: String,
     ) -> anyhow::Result<Vec<ReviewFileSummary>> {
-        smol::block_on(self.get_review_summaries(ReviewIdDTO::from_str(&review_id)?))
+        self.get_review_summaries(ReviewIdDTO::from_str(&review_id)?).await
     }
 
-    fn get_review_file_changes(
+    async fn get_review_file_changes(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
     ) -> anyhow::Result<ReviewFileChanges> {
-        smol::block_on(self.get_review_file_changes(
+        self.get_review_file_changes(
             ReviewIdDTO::from_str(&review_id)?,
             file_path,
             revision,
-        ))
+        ).await
     }
 
-    fn mark_file_read(
+    async fn mark_file_read(
         &self,
         review_id: String,
         file_path: String,
         revision: String,
         read: bool,
     ) -> anyhow::Result<()> {
-        smol::block_on(self.mark_file_read(review_id.parse()?, file_path, revision, read))
+        self.mark_file_read(review_id.parse()?, file_path, revision, read).await
+    }
+
+    async fn submit_review_verdict(
+        &self,
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.submit_review_verdict(review_id.parse()?, verdict, body).await
+    }
+
+    async fn set_discussion_resolved(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    ) -> anyhow::Result<()> {
+        self.set_discussion_resolved(review_id.parse()?, discussion_id, resolved).await
+    }
+
+    async fn create_file_discussion(
+        &self,
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.create_file_discussion(
+            review_id.parse()?,
+            file_path,
+            revision,
+            line,
+            text,
+        ).await
+    }
+
+    async fn create_review_discussion(&self, review_id: String, text: String) -> anyhow::Result<()> {
+        self.create_review_discussion(review_id.parse()?, text).await
+    }
+
+    async fn reply_to_discussion(
+        &self,
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    ) -> anyhow::Result<()> {
+        self.reply_to_discussion(review_id.parse()?, discussion_id, text).await
     }
 }
 
@@ -282,3 +600,98 @@ impl UserCache {
         user.into()
     }
 }
+
+/// Upsource anchors text by UTF-16 offsets into the file, this maps a 1-based line
+/// number to the range covering that line.
+fn line_range(text: &str, line: u32) -> Option<RangeDTO> {
+    let mut offset = 0;
+    for (index, content) in text.split('\n').enumerate() {
+        let length = content.encode_utf16().count() as u32;
+        if index as u32 + 1 == line {
+            return Some(RangeDTO {
+                start_offset: offset,
+                end_offset: offset + length,
+            });
+        }
+        offset += length + 1;
+    }
+
+    None
+}
+
+/// Splits ranges relative to the whole text into ranges relative to each line.
+fn line_changed_ranges<'a>(
+    text: &str,
+    ranges: impl Iterator<Item = &'a RangeDTO> + Clone,
+) -> Vec<Vec<DiffRange>> {
+    let mut line_start = 0;
+    // offsets count the line breaks as they are in the text, including the `\r` of CRLF files
+    text.split_inclusive('\n')
+        .map(|raw_line| {
+            let line = raw_line.strip_suffix('\n').unwrap_or(raw_line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let line_end = line_start + line.encode_utf16().count() as u32;
+            let line_ranges = ranges
+                .clone()
+                .filter(|range| range.start_offset < line_end && range.end_offset > line_start)
+                .map(|range| DiffRange {
+                    start: range.start_offset.max(line_start) - line_start,
+                    end: range.end_offset.min(line_end) - line_start,
+                })
+                .sorted_by_key(|range| range.start)
+                .collect();
+            line_start += raw_line.encode_utf16().count() as u32;
+
+            line_ranges
+        })
+        .collect()
+}
+
+fn anchor_revision(discussion: &DiscussionInFileWithFileDTO) -> Option<String> {
+    discussion
+        .discussion_in_file
+        .anchor
+        .revision_id
+        .clone()
+        .or_else(|| discussion.revision_id.clone())
+}
+
+/// Maps an UTF-16 offset into `text` to the 1-based line containing it.
+fn line_at_offset(text: &str, offset: u32) -> u32 {
+    let line_breaks = text
+        .encode_utf16()
+        .take(offset as usize)
+        .filter(|unit| *unit == u16::from(b'\n'))
+        .count();
+
+    line_breaks as u32 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(text: &str, offsets: &[(u32, u32)]) -> Vec<Vec<(u32, u32)>> {
+        let offsets = offsets
+            .iter()
+            .map(|&(start_offset, end_offset)| RangeDTO { start_offset, end_offset })
+            .collect::<Vec<_>>();
+
+        line_changed_ranges(text, offsets.iter())
+            .into_iter()
+            .map(|line| line.into_iter().map(|range| (range.start, range.end)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn splits_ranges_into_lines() {
+        assert_eq!(ranges("abc\ndef\n", &[(1, 2), (5, 7)]), vec![vec![(1, 2)], vec![(1, 3)]]);
+        // spans over the line break are cut at the end of the line
+        assert_eq!(ranges("abc\ndef", &[(2, 6)]), vec![vec![(2, 3)], vec![(0, 2)]]);
+        assert_eq!(
+            ranges("abc\r\ndef\r\nghi", &[(6, 8), (10, 11)]),
+            vec![vec![], vec![(1, 3)], vec![(0, 1)]]
+        );
+        assert_eq!(ranges("\u{1F600}a\nb", &[(2, 3), (4, 5)]), vec![vec![(2, 3)], vec![(0, 1)]]);
+    }
+}
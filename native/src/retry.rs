@@ -0,0 +1,95 @@
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+
+use crate::error::ApiError;
+use crate::models::ConnectionSettings;
+
+/// Delay before the first retry, doubled for every following one.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Timeout of every request to a provider and how often reads failing because of the network are repeated.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl From<&ConnectionSettings> for RetryPolicy {
+    fn from(settings: &ConnectionSettings) -> Self {
+        Self {
+            timeout: Duration::from_secs(settings.timeout_seconds.into()),
+            retries: settings.retries,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry with the 0-based index `retry`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        INITIAL_BACKOFF * 2u32.saturating_pow(retry)
+    }
+
+    /// Runs `attempt` with the timeout, `idempotent` requests are repeated while they fail because of the network.
+    pub async fn run<T, F, Fut>(&self, idempotent: bool, mut attempt: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut retry = 0;
+        loop {
+            let result = match tokio::time::timeout(self.timeout, attempt()).await {
+                Ok(result) => result,
+                Err(_) => Err(self.timeout_error().into()),
+            };
+            match result {
+                Err(err) if idempotent && retry < self.retries && ApiError::is_network_error(&err) => {
+                    tokio::time::sleep(self.backoff(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn timeout_error(&self) -> ApiError {
+        ApiError::Network {
+            message: format!("The request timed out after {} seconds", self.timeout.as_secs()),
+        }
+    }
+}
+
+/// Repeats `GET` requests of a reqwest client which failed because of the network.
+///
+/// The timeout is set on the client itself.
+pub(crate) struct RetryMiddleware(pub RetryPolicy);
+
+#[async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        mut req: reqwest::Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let idempotent = req.method() == reqwest::Method::GET;
+        let mut retry = 0;
+        loop {
+            let copy = req.try_clone().filter(|_| idempotent && retry < self.0.retries);
+            let result = next.clone().run(req, extensions).await;
+            match (result, copy) {
+                (Err(reqwest_middleware::Error::Reqwest(err)), Some(copy))
+                    if err.is_connect() || err.is_timeout() =>
+                {
+                    tokio::time::sleep(self.0.backoff(retry)).await;
+                    req = copy;
+                    retry += 1;
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::future::Future;
+
+use lazy_static::lazy_static;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+lazy_static! {
+    static ref RUNTIME: Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("review-tool-worker")
+        .build()
+        .expect("Creating async runtime");
+}
+
+/// Runs the future on the shared runtime, blocking the calling thread until it completes.
+///
+/// Must not be called from within the runtime itself.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.block_on(future)
+}
+
+/// Runs the future in the background on the shared runtime.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    RUNTIME.spawn(future)
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::{unix_now, ApiError};
+use crate::models::RateLimit;
+
+/// Requests a provider gets from one module at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+/// Longest a request waits for the provider to accept requests again, beyond that it fails right away.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Schedules the requests of one provider.
+///
+/// At most [`MAX_CONCURRENT_REQUESTS`] run at once. Responses report the remaining budget
+/// through `X-RateLimit-*` (Github) or `RateLimit-*` (Gitlab) headers, once it's exhausted or
+/// the provider asks to come back later with `Retry-After` the following requests wait.
+pub(crate) struct Scheduler {
+    permits: Semaphore,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// Budgets by the resource they apply to, Github counts REST and GraphQL requests separately
+    budgets: HashMap<String, RateLimit>,
+    /// Unix timestamp in seconds before which the provider asked not to send requests
+    retry_at: Option<i64>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            permits: Semaphore::new(MAX_CONCURRENT_REQUESTS),
+            state: Mutex::new(SchedulerState::default()),
+        }
+    }
+
+    /// Waits for a free slot and until the provider accepts requests again.
+    ///
+    /// The request may be sent while the returned permit is held.
+    pub async fn acquire(&self) -> anyhow::Result<SemaphorePermit<'_>> {
+        let permit = self.permits.acquire().await?;
+        if let Some((resume_at, wait)) = self.resume_at() {
+            if wait > MAX_WAIT {
+                return Err(ApiError::RateLimited {
+                    reset_at: Some(resume_at),
+                    message: format!("The provider accepts requests again in {} seconds", wait.as_secs()),
+                }
+                .into());
+            }
+            tokio::time::sleep(wait).await;
+        }
+
+        Ok(permit)
+    }
+
+    /// Updates the budget from the headers of a response, `header` looks them up case insensitively.
+    pub fn record(&self, header: impl Fn(&str) -> Option<String>) {
+        let value = |names: &[&str]| names.iter().find_map(|name| header(name));
+        let number = |names: &[&str]| value(names).and_then(|value| value.trim().parse::<i64>().ok());
+        let mut state = self.state.lock();
+        if let Some(seconds) = number(&["retry-after"]) {
+            state.retry_at = Some(unix_now() + seconds);
+        }
+        let remaining = number(&["x-ratelimit-remaining", "ratelimit-remaining"]);
+        if remaining.is_none() {
+            return;
+        }
+        let resource = value(&["x-ratelimit-resource"]).unwrap_or_default();
+        let budget = state.budgets.entry(resource).or_default();
+        budget.remaining = remaining.map(|remaining| remaining as u32);
+        budget.limit = number(&["x-ratelimit-limit", "ratelimit-limit"]).map(|limit| limit as u32);
+        budget.reset_at = number(&["x-ratelimit-reset", "ratelimit-reset"]);
+    }
+
+    /// The budget closest to being exhausted.
+    pub fn rate_limit(&self) -> RateLimit {
+        self.state
+            .lock()
+            .budgets
+            .values()
+            .min_by_key(|budget| budget.remaining)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// When requests may be sent again and how long that is from now, `None` if they may be sent right away.
+    ///
+    /// It's not known which budget a request is counted against, so any exhausted one holds back all requests.
+    fn resume_at(&self) -> Option<(i64, Duration)> {
+        let state = self.state.lock();
+        let now = unix_now();
+        state
+            .budgets
+            .values()
+            .filter(|budget| budget.remaining == Some(0))
+            .filter_map(|budget| budget.reset_at)
+            .chain(state.retry_at)
+            .filter(|resume_at| *resume_at > now)
+            .max()
+            .map(|resume_at| (resume_at, Duration::from_secs((resume_at - now) as u64)))
+    }
+}
+
+/// Sends the requests of a reqwest client through the scheduler.
+pub(crate) struct SchedulerMiddleware(pub Arc<Scheduler>);
+
+#[async_trait]
+impl Middleware for SchedulerMiddleware {
+    async fn handle(
+        &self,
+        req: reqwest::Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let _permit = self.0.acquire().await.map_err(reqwest_middleware::Error::Middleware)?;
+        let response = next.run(req, extensions).await?;
+        self.0.record(|name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(str::to_string)
+        });
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use argon2::Argon2;
+use base64::prelude::*;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiError;
+
+const VERSION: u64 = 1;
+const SALT_LENGTH: usize = 16;
+/// Encrypted with every key so a wrong passphrase is noticed before any secret is read.
+const CHECK_VALUE: &str = "review-tool";
+
+/// Secrets like provider tokens, encrypted at rest with a key derived from a passphrase.
+///
+/// The key is derived with Argon2id, every secret is encrypted with XChaCha20-Poly1305
+/// using its own random nonce.
+pub(crate) struct SecretStore {
+    path: PathBuf,
+    cipher: XChaCha20Poly1305,
+    file: SecretsFile,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SecretsFile {
+    version: u64,
+    salt: String,
+    check: EncryptedValue,
+    secrets: HashMap<String, EncryptedValue>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedValue {
+    nonce: String,
+    ciphertext: String,
+}
+
+impl SecretStore {
+    /// Unlocks the store at `path`, a new store is created when the file doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>, passphrase: &str) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            return Self::create(path, passphrase);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Reading secrets {}", path.display()))?;
+        let file: SecretsFile = serde_json::from_str(&content).map_err(|err| ApiError::InvalidConfig {
+            message: format!("Invalid secrets file {}: {err}", path.display()),
+        })?;
+        if file.version > VERSION {
+            return Err(ApiError::InvalidConfig {
+                message: format!("Secrets version {} is newer than the supported version {VERSION}", file.version),
+            }
+            .into());
+        }
+        let salt = BASE64_STANDARD.decode(&file.salt)?;
+        let cipher = derive_cipher(passphrase, &salt)?;
+        let check = decrypt(&cipher, &file.check).map_err(|_| ApiError::Unauthorized {
+            message: "Wrong passphrase for the secrets".into(),
+        })?;
+        anyhow::ensure!(check == CHECK_VALUE, "Secrets are corrupted");
+
+        Ok(Self { path, cipher, file })
+    }
+
+    fn create(path: PathBuf, passphrase: &str) -> anyhow::Result<Self> {
+        let salt: [u8; SALT_LENGTH] = rand_bytes();
+        let cipher = derive_cipher(passphrase, &salt)?;
+        let file = SecretsFile {
+            version: VERSION,
+            salt: BASE64_STANDARD.encode(salt),
+            check: encrypt(&cipher, CHECK_VALUE)?,
+            secrets: HashMap::new(),
+        };
+        let store = Self { path, cipher, file };
+        store.write()?;
+
+        Ok(store)
+    }
+
+    pub fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        self.file
+            .secrets
+            .get(name)
+            .map(|value| decrypt(&self.cipher, value))
+            .transpose()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let value = encrypt(&self.cipher, value)?;
+        self.file.secrets.insert(name.to_string(), value);
+
+        self.write()
+    }
+
+    /// Removes all secrets whose name doesn't match `keep`.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) -> anyhow::Result<()> {
+        let count = self.file.secrets.len();
+        self.file.secrets.retain(|name, _| keep(name));
+        if self.file.secrets.len() == count {
+            return Ok(());
+        }
+
+        self.write()
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        if let Some(directory) = self.path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            std::fs::create_dir_all(directory)?;
+        }
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, serde_json::to_string_pretty(&self.file)?)
+            .with_context(|| format!("Writing secrets {}", temporary.display()))?;
+        std::fs::rename(&temporary, &self.path)
+            .with_context(|| format!("Replacing secrets {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> anyhow::Result<XChaCha20Poly1305> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow::anyhow!("Deriving the secrets key failed: {err}"))?;
+
+    Ok(XChaCha20Poly1305::new(&key))
+}
+
+fn encrypt(cipher: &XChaCha20Poly1305, value: &str) -> anyhow::Result<EncryptedValue> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Encrypting a secret failed"))?;
+
+    Ok(EncryptedValue {
+        nonce: BASE64_STANDARD.encode(nonce),
+        ciphertext: BASE64_STANDARD.encode(ciphertext),
+    })
+}
+
+fn decrypt(cipher: &XChaCha20Poly1305, value: &EncryptedValue) -> anyhow::Result<String> {
+    let nonce = BASE64_STANDARD.decode(&value.nonce)?;
+    anyhow::ensure!(nonce.len() == 24, "Invalid secret nonce");
+    let ciphertext = BASE64_STANDARD.decode(&value.ciphertext)?;
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("Decrypting a secret failed"))?;
+
+    Ok(String::from_utf8(plaintext)?)
+}
+
+fn rand_bytes<const N: usize>() -> [u8; N] {
+    use chacha20poly1305::aead::rand_core::RngCore;
+    let mut bytes = [0; N];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes
+}
@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::models::{ProviderModule, ProviderSettings};
+use crate::secrets::SecretStore;
+
+/// Version of the settings file written by this build.
+const VERSION: u64 = 1;
+
+/// Name of the secrets file the app and the command line tool keep next to the settings.
+const SECRETS_FILE_NAME: &str = "secrets.json";
+
+/// Upgrades a settings file by one version, the migration at index `n` reads version `n`.
+type Migration = fn(Value) -> anyhow::Result<Value>;
+
+const MIGRATIONS: [Migration; VERSION as usize] = [migrate_unversioned];
+
+/// Provider settings as stored on disk, shared by the app and the command line tool.
+///
+/// ```json
+/// { "version": 1, "providers": [{ "id": "...", "name": "...", "module": { "type": "github", ... } }] }
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsFile {
+    version: u64,
+    providers: Vec<ProviderSettings>,
+}
+
+/// Reads the providers from `path`, migrating files written by older versions.
+///
+/// A missing file contains no providers. Tokens kept in the unlocked `secrets` are filled in,
+/// without them the tokens of encrypted providers stay empty.
+pub(crate) fn load(
+    path: impl AsRef<Path>,
+    secrets: Option<&SecretStore>,
+) -> anyhow::Result<Vec<ProviderSettings>> {
+    let path = path.as_ref();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Reading settings {}", path.display()))
+        }
+    };
+    let value = serde_json::from_str(&content).map_err(|err| ApiError::InvalidConfig {
+        message: format!("Settings {} are no valid json: {err}", path.display()),
+    })?;
+    let settings: SettingsFile =
+        serde_json::from_value(migrate(value)?).map_err(|err| ApiError::InvalidConfig {
+            message: format!("Invalid settings {}: {err}", path.display()),
+        })?;
+
+    let mut providers = settings.providers;
+    if let Some(secrets) = secrets {
+        for provider in providers.iter_mut() {
+            let id = provider.id.clone();
+            if let Some(token) = token_mut(&mut provider.module).filter(|token| token.is_empty()) {
+                *token = secrets.get(&id)?.unwrap_or_default();
+            }
+        }
+    }
+
+    Ok(providers)
+}
+
+/// Writes the providers to `path` in the current version.
+///
+/// With unlocked `secrets` the tokens are moved into them and stored empty in the settings,
+/// empty tokens of providers loaded while locked keep their secret. Without unlocked secrets only
+/// providers without tokens can be saved, and only while there are no secrets at `secrets_path`,
+/// or next to the settings without one, as saving while locked would drop the encrypted tokens.
+/// The file is replaced at once so a crash doesn't leave partially written settings behind.
+pub(crate) fn save(
+    path: impl AsRef<Path>,
+    mut providers: Vec<ProviderSettings>,
+    secrets: Option<&mut SecretStore>,
+    secrets_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    match secrets {
+        Some(secrets) => {
+            for provider in providers.iter_mut() {
+                let id = provider.id.clone();
+                if let Some(token) = token_mut(&mut provider.module).filter(|token| !token.is_empty()) {
+                    secrets.set(&id, &std::mem::take(token))?;
+                }
+            }
+            secrets.retain(|id| providers.iter().any(|provider| provider.id == id))?;
+        }
+        None => {
+            let secrets_path = secrets_path
+                .map(PathBuf::from)
+                .unwrap_or_else(|| path.with_file_name(SECRETS_FILE_NAME));
+            if secrets_path.exists() {
+                return Err(ApiError::InvalidConfig {
+                    message: "The secrets are locked, unlock them before saving the provider settings".into(),
+                }
+                .into());
+            }
+            // tokens are never written in plaintext
+            if providers
+                .iter_mut()
+                .any(|provider| token_mut(&mut provider.module).is_some_and(|token| !token.is_empty()))
+            {
+                return Err(ApiError::InvalidConfig {
+                    message: "Tokens are stored in the secrets, unlock them before saving the provider settings".into(),
+                }
+                .into());
+            }
+        }
+    }
+    if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Creating settings directory {}", directory.display()))?;
+    }
+    let settings = SettingsFile {
+        version: VERSION,
+        providers,
+    };
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, serde_json::to_string_pretty(&settings)?)
+        .with_context(|| format!("Writing settings {}", temporary.display()))?;
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("Replacing settings {}", path.display()))?;
+
+    Ok(())
+}
+
+fn token_mut(module: &mut ProviderModule) -> Option<&mut String> {
+    match module {
+        ProviderModule::Upsource(upsource) => Some(&mut upsource.token),
+        ProviderModule::Github(github) => Some(&mut github.token),
+        ProviderModule::Gitlab(gitlab) => Some(&mut gitlab.token),
+        ProviderModule::Gitea(gitea) => Some(&mut gitea.token),
+        ProviderModule::Bitbucket(bitbucket) => Some(&mut bitbucket.token),
+        ProviderModule::Gerrit(gerrit) => Some(&mut gerrit.token),
+        ProviderModule::Mock(_) => None,
+    }
+}
+
+fn migrate(mut value: Value) -> anyhow::Result<Value> {
+    let version = match &value {
+        Value::Object(object) => match object.get("version") {
+            Some(version) => version.as_u64().ok_or_else(|| ApiError::InvalidConfig {
+                message: format!("Invalid settings version {version}"),
+            })?,
+            None => 0,
+        },
+        _ => 0,
+    };
+    if version > VERSION {
+        return Err(ApiError::InvalidConfig {
+            message: format!("Settings version {version} is newer than the supported version {VERSION}"),
+        }
+        .into());
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value)?;
+    }
+
+    Ok(value)
+}
+
+/// The first files had no version, either a list of providers or an object with a
+/// `providers` list next to settings of the command line tool.
+fn migrate_unversioned(value: Value) -> anyhow::Result<Value> {
+    let providers = match value {
+        Value::Array(providers) => providers,
+        Value::Object(mut object) => match object.remove("providers") {
+            Some(Value::Array(providers)) => providers,
+            _ => vec![],
+        },
+        _ => anyhow::bail!(ApiError::InvalidConfig {
+            message: "Settings are neither a list of providers nor an object".into(),
+        }),
+    };
+
+    Ok(serde_json::json!({
+        "version": 1,
+        "providers": providers,
+    }))
+}
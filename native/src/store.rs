@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::unix_now;
+use crate::models::ReviewVerdict;
+use crate::ReviewModule;
+
+/// Local database keeping the last known responses of every provider and the writes
+/// which couldn't be sent while a provider was unreachable.
+pub(crate) struct OfflineStore {
+    connection: Mutex<Connection>,
+}
+
+/// Local database keeping the read files of providers which don't track them.
+///
+/// Unlike the [`OfflineStore`] it's needed whether or not responses are kept for offline use.
+pub(crate) struct ReadStateStore {
+    connection: Mutex<Connection>,
+}
+
+/// The kind of response stored, together with the provider id and key it identifies a row.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ResponseKind {
+    Reviews,
+    Discussions,
+    FileSummaries,
+    FileChanges,
+}
+
+impl ResponseKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ResponseKind::Reviews => "reviews",
+            ResponseKind::Discussions => "discussions",
+            ResponseKind::FileSummaries => "file_summaries",
+            ResponseKind::FileChanges => "file_changes",
+        }
+    }
+}
+
+/// A write made while offline, replayed in order once the provider is reachable again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum PendingWrite {
+    MarkFileRead {
+        review_id: String,
+        file_path: String,
+        revision: String,
+        read: bool,
+    },
+    SetDiscussionResolved {
+        review_id: String,
+        discussion_id: String,
+        resolved: bool,
+    },
+    SubmitReviewVerdict {
+        review_id: String,
+        verdict: ReviewVerdict,
+        body: Option<String>,
+    },
+    CreateFileDiscussion {
+        review_id: String,
+        file_path: String,
+        revision: String,
+        line: u32,
+        text: String,
+    },
+    CreateReviewDiscussion {
+        review_id: String,
+        text: String,
+    },
+    ReplyToDiscussion {
+        review_id: String,
+        discussion_id: String,
+        text: String,
+    },
+}
+
+impl PendingWrite {
+    pub async fn apply(self, module: &impl ReviewModule) -> anyhow::Result<()> {
+        match self {
+            PendingWrite::MarkFileRead {
+                review_id,
+                file_path,
+                revision,
+                read,
+            } => {
+                module
+                    .mark_file_read(review_id, file_path, revision, read)
+                    .await
+            }
+            PendingWrite::SetDiscussionResolved {
+                review_id,
+                discussion_id,
+                resolved,
+            } => {
+                module
+                    .set_discussion_resolved(review_id, discussion_id, resolved)
+                    .await
+            }
+            PendingWrite::SubmitReviewVerdict {
+                review_id,
+                verdict,
+                body,
+            } => module.submit_review_verdict(review_id, verdict, body).await,
+            PendingWrite::CreateFileDiscussion {
+                review_id,
+                file_path,
+                revision,
+                line,
+                text,
+            } => {
+                module
+                    .create_file_discussion(review_id, file_path, revision, line, text)
+                    .await
+            }
+            PendingWrite::CreateReviewDiscussion { review_id, text } => {
+                module.create_review_discussion(review_id, text).await
+            }
+            PendingWrite::ReplyToDiscussion {
+                review_id,
+                discussion_id,
+                text,
+            } => {
+                module
+                    .reply_to_discussion(review_id, discussion_id, text)
+                    .await
+            }
+        }
+    }
+}
+
+impl OfflineStore {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS responses (
+                provider_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                key TEXT NOT NULL,
+                data TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (provider_id, kind, key)
+            );
+            CREATE TABLE IF NOT EXISTS pending_writes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                provider_id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn save<T: Serialize>(
+        &self,
+        provider_id: &str,
+        kind: ResponseKind,
+        key: &str,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        let data = serde_json::to_string(value)?;
+        self.connection.lock().execute(
+            "INSERT OR REPLACE INTO responses (provider_id, kind, key, data, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![provider_id, kind.as_str(), key, data, unix_now()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        provider_id: &str,
+        kind: ResponseKind,
+        key: &str,
+    ) -> anyhow::Result<Option<T>> {
+        let data: Option<String> = self
+            .connection
+            .lock()
+            .query_row(
+                "SELECT data FROM responses WHERE provider_id = ?1 AND kind = ?2 AND key = ?3",
+                params![provider_id, kind.as_str(), key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let value = data.map(|data| serde_json::from_str(&data)).transpose()?;
+
+        Ok(value)
+    }
+
+    pub fn enqueue(&self, provider_id: &str, write: &PendingWrite) -> anyhow::Result<()> {
+        let data = serde_json::to_string(write)?;
+        self.connection.lock().execute(
+            "INSERT INTO pending_writes (provider_id, data, created_at) VALUES (?1, ?2, ?3)",
+            params![provider_id, data, unix_now()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the oldest queued write of the provider together with its id.
+    pub fn next_pending_write(&self, provider_id: &str) -> anyhow::Result<Option<(i64, PendingWrite)>> {
+        let row: Option<(i64, String)> = self
+            .connection
+            .lock()
+            .query_row(
+                "SELECT id, data FROM pending_writes WHERE provider_id = ?1 ORDER BY id LIMIT 1",
+                params![provider_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((id, data)) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some((id, serde_json::from_str(&data)?)))
+    }
+
+    pub fn remove_pending_write(&self, id: i64) -> anyhow::Result<()> {
+        self.connection
+            .lock()
+            .execute("DELETE FROM pending_writes WHERE id = ?1", params![id])?;
+
+        Ok(())
+    }
+
+    pub fn pending_write_count(&self) -> anyhow::Result<u32> {
+        let count = self
+            .connection
+            .lock()
+            .query_row("SELECT COUNT(*) FROM pending_writes", [], |row| row.get(0))?;
+
+        Ok(count)
+    }
+}
+
+impl ReadStateStore {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS read_files (
+                provider_id TEXT NOT NULL,
+                review_id TEXT NOT NULL,
+                file_path TEXT NOT NULL,
+                revision TEXT NOT NULL,
+                PRIMARY KEY (provider_id, review_id, file_path, revision)
+            );",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// A file is only read in the revision it was marked in, new pushes make it unread again.
+    pub fn set_file_read(
+        &self,
+        provider_id: &str,
+        review_id: &str,
+        file_path: &str,
+        revision: &str,
+        read: bool,
+    ) -> anyhow::Result<()> {
+        let query = if read {
+            "INSERT OR IGNORE INTO read_files (provider_id, review_id, file_path, revision) VALUES (?1, ?2, ?3, ?4)"
+        } else {
+            "DELETE FROM read_files WHERE provider_id = ?1 AND review_id = ?2 AND file_path = ?3 AND revision = ?4"
+        };
+        self.connection
+            .lock()
+            .execute(query, params![provider_id, review_id, file_path, revision])?;
+
+        Ok(())
+    }
+
+    /// Returns the path and revision of all files marked as read in the review.
+    pub fn read_files(
+        &self,
+        provider_id: &str,
+        review_id: &str,
+    ) -> anyhow::Result<HashSet<(String, String)>> {
+        let connection = self.connection.lock();
+        let mut statement = connection.prepare(
+            "SELECT file_path, revision FROM read_files WHERE provider_id = ?1 AND review_id = ?2",
+        )?;
+        let files = statement
+            .query_map(params![provider_id, review_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        Ok(files)
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use flutter_rust_bridge::StreamSink;
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::models::*;
+use crate::{runtime, ApiModule, ApiModules, ReviewModule};
+
+/// Polls all configured providers in the background and reports changes to their reviews.
+pub(crate) struct Watcher {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Last known state of the open reviews of a provider
+type ProviderSnapshot = HashMap<String, ReviewSnapshot>;
+
+#[derive(Clone)]
+struct ReviewSnapshot {
+    review: Review,
+    /// Comments by id together with the id of their discussion
+    comments: HashMap<String, (String, ReviewComment)>,
+    revisions: HashSet<String>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self {
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Starts polling every `interval`, replacing a previously started watcher.
+    pub fn start(&self, modules: &'static ApiModules, interval: Duration, sink: StreamSink<ReviewEvent>) {
+        let task = runtime::spawn(watch(modules, interval, sink));
+        if let Some(previous) = self.task.lock().replace(task) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().take() {
+            task.abort();
+        }
+    }
+}
+
+async fn watch(modules: &'static ApiModules, interval: Duration, sink: StreamSink<ReviewEvent>) {
+    let mut snapshots: HashMap<String, ProviderSnapshot> = HashMap::new();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        // the stream was closed on the dart side
+        if !poll(modules, &mut snapshots, |event| sink.add(event)).await {
+            return;
+        }
+    }
+}
+
+/// Takes a new snapshot of every provider and emits the changes since the previous one.
+///
+/// Returns `false` as soon as `emit` does, once nobody listens anymore.
+async fn poll(
+    modules: &ApiModules,
+    snapshots: &mut HashMap<String, ProviderSnapshot>,
+    mut emit: impl FnMut(ReviewEvent) -> bool,
+) -> bool {
+    for provider_id in modules.provider_ids() {
+        let previous = snapshots.get(&provider_id);
+        // an unreachable provider keeps its previous snapshot so its reviews don't appear closed
+        let snapshot = match take_snapshot(modules, &provider_id, previous).await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                log::warn!("Polling {provider_id} failed: {err:#}");
+                continue;
+            }
+        };
+        if let Some(previous) = previous {
+            for event in diff_snapshots(&provider_id, previous, &snapshot) {
+                if !emit(event) {
+                    return false;
+                }
+            }
+        }
+        snapshots.insert(provider_id, snapshot);
+    }
+
+    true
+}
+
+async fn take_snapshot(
+    modules: &ApiModules,
+    provider_id: &str,
+    previous: Option<&ProviderSnapshot>,
+) -> anyhow::Result<ProviderSnapshot> {
+    // the interval may be shorter than the time responses are cached, which the app keeps using
+    let provider = modules.get_provider(provider_id)?;
+    let module = provider.uncached();
+    let reviews = module.get_reviews().await?;
+    let snapshots = reviews
+        .into_iter()
+        .filter(|review| review.open)
+        .map(|review| async move {
+            let review_id = review.id.clone();
+            let unchanged = previous
+                .and_then(|previous| previous.get(&review_id))
+                .filter(|previous| review.updated_at.is_some() && previous.review.updated_at == review.updated_at);
+            // discussions and files are only fetched again once the provider reports a change
+            let result = match unchanged {
+                Some(previous) => Ok(ReviewSnapshot {
+                    review,
+                    ..previous.clone()
+                }),
+                None => take_review_snapshot(module, review).await,
+            };
+
+            (review_id, result)
+        });
+    let results = futures::future::join_all(snapshots).await;
+
+    let mut snapshots = ProviderSnapshot::new();
+    for (review_id, result) in results {
+        match result {
+            Ok(snapshot) => {
+                snapshots.insert(review_id, snapshot);
+            }
+            // a single failing review keeps its previous state instead of failing the whole provider
+            Err(err) => {
+                log::warn!("Polling review {review_id} of {provider_id} failed: {err:#}");
+                if let Some(snapshot) = previous.and_then(|previous| previous.get(&review_id)) {
+                    snapshots.insert(review_id, snapshot.clone());
+                }
+            }
+        }
+    }
+
+    Ok(snapshots)
+}
+
+async fn take_review_snapshot(module: &ApiModule, review: Review) -> anyhow::Result<ReviewSnapshot> {
+    let (discussions, summaries) = futures::future::try_join(
+        module.get_review_discussions(review.id.clone()),
+        module.get_review_file_summaries(review.id.clone()),
+    )
+    .await?;
+
+    Ok(ReviewSnapshot {
+        comments: discussions
+            .into_iter()
+            .flat_map(|discussion| {
+                let discussion_id = discussion.id;
+                discussion
+                    .comments
+                    .into_iter()
+                    .map(move |comment| (comment.id.clone(), (discussion_id.clone(), comment)))
+            })
+            .collect(),
+        revisions: summaries
+            .into_iter()
+            .map(|summary| summary.revision_id)
+            .collect(),
+        review,
+    })
+}
+
+fn diff_snapshots(
+    provider_id: &str,
+    previous: &ProviderSnapshot,
+    current: &ProviderSnapshot,
+) -> Vec<ReviewEvent> {
+    let mut events = vec![];
+    for (review_id, snapshot) in current {
+        let Some(previous) = previous.get(review_id) else {
+            events.push(ReviewEvent::ReviewAdded {
+                provider_id: provider_id.to_string(),
+                review: snapshot.review.clone(),
+            });
+            continue;
+        };
+        if previous.review.state != snapshot.review.state {
+            events.push(ReviewEvent::StateChanged {
+                provider_id: provider_id.to_string(),
+                review_id: review_id.clone(),
+                state: snapshot.review.state.clone(),
+            });
+        }
+        for (comment_id, (discussion_id, comment)) in &snapshot.comments {
+            if !previous.comments.contains_key(comment_id) {
+                events.push(ReviewEvent::NewComment {
+                    provider_id: provider_id.to_string(),
+                    review_id: review_id.clone(),
+                    discussion_id: discussion_id.clone(),
+                    comment: comment.clone(),
+                });
+            }
+        }
+        for revision in snapshot.revisions.difference(&previous.revisions) {
+            events.push(ReviewEvent::NewRevision {
+                provider_id: provider_id.to_string(),
+                review_id: review_id.clone(),
+                revision: revision.clone(),
+            });
+        }
+    }
+    for review_id in previous.keys().filter(|id| !current.contains_key(*id)) {
+        events.push(ReviewEvent::ReviewClosed {
+            provider_id: provider_id.to_string(),
+            review_id: review_id.clone(),
+        });
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROVIDER_ID: &str = "mock";
+
+    async fn mock_modules() -> ApiModules {
+        let modules = ApiModules::new();
+        modules
+            .configure(vec![ProviderSettings {
+                id: PROVIDER_ID.into(),
+                name: "Mock".into(),
+                module: ProviderModule::Mock(MockProviderSettings {
+                    path: concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/mock").into(),
+                })
+                .into(),
+                connection: None,
+            }])
+            .await
+            .unwrap();
+
+        modules
+    }
+
+    async fn poll_events(modules: &ApiModules, snapshots: &mut HashMap<String, ProviderSnapshot>) -> Vec<ReviewEvent> {
+        let mut events = vec![];
+        assert!(poll(modules, snapshots, |event| {
+            events.push(event);
+            true
+        })
+        .await);
+
+        events
+    }
+
+    fn review(id: &str, state: ReviewState) -> Review {
+        Review {
+            id: id.into(),
+            title: "Title".into(),
+            branch_name: "feature".into(),
+            authors: vec![],
+            reviewers: vec![],
+            open: true,
+            state,
+            updated_at: None,
+        }
+    }
+
+    fn comment(id: &str) -> ReviewComment {
+        ReviewComment {
+            id: id.into(),
+            user: User {
+                name: "Jane Doe".into(),
+                avatar_url: None,
+            },
+            text: "Text".into(),
+            timestamp: 0,
+        }
+    }
+
+    fn snapshot(state: ReviewState, comments: &[&str], revisions: &[&str]) -> ReviewSnapshot {
+        ReviewSnapshot {
+            review: review("1", state),
+            comments: comments
+                .iter()
+                .map(|id| (id.to_string(), ("discussion".to_string(), comment(id))))
+                .collect(),
+            revisions: revisions.iter().map(|revision| revision.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn diffs_reviews() {
+        let previous = ProviderSnapshot::from([
+            ("1".to_string(), snapshot(ReviewState::Pending, &["a"], &["r1"])),
+            ("2".to_string(), snapshot(ReviewState::Pending, &[], &[])),
+        ]);
+        let current = ProviderSnapshot::from([
+            ("1".to_string(), snapshot(ReviewState::Approved, &["a", "b"], &["r1", "r2"])),
+            ("3".to_string(), snapshot(ReviewState::Pending, &[], &[])),
+        ]);
+
+        let events = diff_snapshots(PROVIDER_ID, &previous, &current);
+
+        assert_eq!(events.len(), 5, "{events:?}");
+        assert!(events.iter().any(|event| matches!(event,
+            ReviewEvent::StateChanged { review_id, state: ReviewState::Approved, .. } if review_id == "1")));
+        assert!(events.iter().any(|event| matches!(event,
+            ReviewEvent::NewComment { comment, .. } if comment.id == "b")));
+        assert!(events.iter().any(|event| matches!(event,
+            ReviewEvent::NewRevision { revision, .. } if revision == "r2")));
+        assert!(events.iter().any(|event| matches!(event,
+            ReviewEvent::ReviewAdded { review, .. } if review.id == "1")));
+        assert!(events.iter().any(|event| matches!(event,
+            ReviewEvent::ReviewClosed { review_id, .. } if review_id == "2")));
+    }
+
+    #[test]
+    fn unchanged_reviews_emit_nothing() {
+        let previous = ProviderSnapshot::from([("1".to_string(), snapshot(ReviewState::Pending, &["a"], &["r1"]))]);
+
+        assert!(diff_snapshots(PROVIDER_ID, &previous, &previous.clone()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn polling_emits_new_comments() {
+        let modules = mock_modules().await;
+        let mut snapshots = HashMap::new();
+
+        // the first snapshot only records the current state
+        assert!(poll_events(&modules, &mut snapshots).await.is_empty());
+        modules
+            .create_review_discussion(PROVIDER_ID.into(), "1".into(), "Is this tested?".into())
+            .await
+            .unwrap();
+        let events = poll_events(&modules, &mut snapshots).await;
+
+        assert!(
+            matches!(&events[..], [ReviewEvent::NewComment { review_id, comment, .. }]
+                if review_id == "1" && comment.text == "Is this tested?"),
+            "{events:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_reviews_keep_their_details() {
+        let modules = mock_modules().await;
+        let mut snapshots = HashMap::new();
+        poll_events(&modules, &mut snapshots).await;
+
+        // a comment only the snapshot knows survives as long as the provider reports no change
+        let snapshot = snapshots.get_mut(PROVIDER_ID).unwrap().get_mut("1").unwrap();
+        snapshot.comments.insert("known".into(), ("discussion".into(), comment("known")));
+
+        assert!(poll_events(&modules, &mut snapshots).await.is_empty());
+        assert!(snapshots[PROVIDER_ID]["1"].comments.contains_key("known"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Timeouts, retries and cancellation of provider requests.
+
+use std::time::Duration;
+
+use native::api::{self, ApiError};
+use native::models::*;
+use native::modules::upsource::UpsourceModule;
+use native::ReviewModule;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const CONNECTION: ConnectionSettings = ConnectionSettings {
+    timeout_seconds: 1,
+    retries: 1,
+};
+
+fn reviews() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "result": { "reviews": [], "hasMore": false, "totalCount": 0 } }))
+}
+
+async fn serve(rpc: &str, slow_responses: u64, response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/~rpc/{rpc}")))
+        .respond_with(response.clone().set_delay(Duration::from_secs(5)))
+        .up_to_n_times(slow_responses)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!("/~rpc/{rpc}")))
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getUserInfo"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "result": { "infos": [] } })))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+async fn requests(server: &MockServer, rpc: &str) -> usize {
+    let requests = server.received_requests().await.unwrap();
+
+    requests.iter().filter(|request| request.url.path() == format!("/~rpc/{rpc}")).count()
+}
+
+#[tokio::test]
+async fn hung_requests_time_out() {
+    let server = serve("getReviews", 2, reviews()).await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &CONNECTION);
+
+    let err = ApiError::from(module.get_reviews().await.unwrap_err());
+
+    assert!(matches!(err, ApiError::Network { .. }), "{err:?}");
+    assert_eq!(requests(&server, "getReviews").await, 2);
+}
+
+#[tokio::test]
+async fn reads_are_retried() {
+    let server = serve("getReviews", 1, reviews()).await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &CONNECTION);
+
+    module.get_reviews().await.unwrap();
+
+    assert_eq!(requests(&server, "getReviews").await, 2);
+}
+
+#[tokio::test]
+async fn writes_are_not_retried() {
+    let server = serve("createDiscussion", 1, ResponseTemplate::new(200).set_body_json(json!({ "result": {} }))).await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &CONNECTION);
+
+    let result = module
+        .create_review_discussion("config/CFG-CR-1".into(), "Looks good".into())
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(requests(&server, "createDiscussion").await, 1);
+}
+
+// `api` blocks on the shared runtime, so the stand-in runs on a runtime of its own
+#[test]
+fn pending_file_requests_can_be_cancelled() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(serve(
+        "getFileInReviewSummaryInlineChanges",
+        1,
+        ResponseTemplate::new(200).set_body_json(json!({ "result": {} })),
+    ));
+    api::configure_modules(vec![ProviderSettings {
+        id: "upsource".into(),
+        name: "Upsource".into(),
+        module: ProviderModule::Upsource(UpsourceProviderSettings {
+            url: server.uri(),
+            token: "token".into(),
+        })
+        .into(),
+        connection: Some(ConnectionSettings {
+            timeout_seconds: 30,
+            retries: 0,
+        }),
+    }])
+    .unwrap();
+
+    let request = std::thread::spawn(|| {
+        api::get_review_file(
+            "upsource".into(),
+            "config/CFG-CR-1".into(),
+            "src/config.rs".into(),
+            "2".into(),
+            Some("file".into()),
+        )
+    });
+    std::thread::sleep(Duration::from_millis(500));
+    api::cancel_request("file".into()).unwrap();
+
+    let err = request.join().unwrap().unwrap_err();
+    assert!(matches!(err, ApiError::Cancelled { .. }), "{err:?}");
+}
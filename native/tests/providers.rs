@@ -0,0 +1,605 @@
+//! Runs every module through the same contract against a local stand-in of its provider.
+
+mod support;
+
+macro_rules! contract_tests {
+    ($name:ident: $stand_in:ty) => {
+        mod $name {
+            use super::support::contract;
+
+            #[tokio::test]
+            async fn lists_reviews() {
+                contract::lists_reviews::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn lists_discussions() {
+                contract::lists_discussions::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn lists_file_summaries() {
+                contract::lists_file_summaries::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn diffs_files() {
+                contract::diffs_files::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn creates_review_discussions() {
+                contract::creates_review_discussions::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn marks_files_read() {
+                contract::marks_files_read::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn authenticates() {
+                contract::authenticates::<$stand_in>().await;
+            }
+
+            #[tokio::test]
+            async fn rejects_invalid_tokens() {
+                contract::rejects_invalid_tokens::<$stand_in>().await;
+            }
+        }
+    };
+}
+
+contract_tests!(bitbucket: super::support::bitbucket::Bitbucket);
+contract_tests!(gerrit: super::support::gerrit::Gerrit);
+contract_tests!(gitea: super::support::gitea::Gitea);
+contract_tests!(github: super::support::github::Github);
+contract_tests!(gitlab: super::support::gitlab::Gitlab);
+contract_tests!(mock: super::support::mock::Mock);
+contract_tests!(upsource: super::support::upsource::Upsource);
+
+/// Bitbucket spreads a review over its participants and a separate diff, which the contract doesn't look at.
+mod bitbucket_review {
+    use native::models::*;
+    use native::ReviewModule;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::Mock;
+
+    use super::support::bitbucket::Bitbucket;
+    use super::support::{fixture, json, StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn needs_work_of_one_reviewer_rejects_the_review() {
+        let server = Bitbucket::serve().await;
+        let module = Bitbucket::connect(server.as_ref(), TOKEN).await.unwrap();
+
+        let reviews = module.get_reviews().await.unwrap();
+
+        // one reviewer approved, the other one asked for changes
+        assert_eq!(reviews[0].state, ReviewState::Rejected);
+        assert_eq!(reviews[0].reviewers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn counts_changed_lines_from_the_diff() {
+        let server = Bitbucket::serve().await;
+        let module = Bitbucket::connect(server.as_ref(), TOKEN).await.unwrap();
+
+        let summaries = module
+            .get_review_file_summaries(Bitbucket::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        let counts = summaries
+            .iter()
+            .map(|summary| (summary.file_path.as_str(), summary.added_lines, summary.removed_lines))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![("src/config.rs", 3, 1), ("src/duration.rs", 12, 0)]);
+    }
+
+    #[tokio::test]
+    async fn deleted_comments_leave_no_discussion() {
+        let server = Bitbucket::serve().await.unwrap();
+        let mut activities = fixture("bitbucket/activities.json");
+        let mut deletion = activities["values"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|activity| activity["comment"]["id"] == 103)
+            .unwrap()
+            .clone();
+        deletion["commentAction"] = json!("DELETED");
+        activities["values"].as_array_mut().unwrap().insert(0, deletion);
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/projects/ACME/repos/config/pull-requests/1/activities"))
+            .respond_with(json(activities))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        let module = Bitbucket::connect(Some(&server), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Bitbucket::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        assert!(!discussions.is_empty());
+        assert!(discussions.iter().all(|discussion| discussion.id != "103"));
+    }
+}
+
+/// Gitea doesn't report which comment answers which, conversations are rebuilt from their lines.
+mod gitea_review {
+    use std::collections::HashMap;
+
+    use native::api::ApiError;
+    use native::models::ReviewState;
+    use native::ReviewModule;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::Mock;
+
+    use super::support::gitea::Gitea;
+    use super::support::{fixture, json, received, StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn comments_on_the_same_line_form_one_conversation() {
+        let server = Gitea::serve().await;
+        let module = Gitea::connect(server.as_ref(), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Gitea::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        // this also merges unrelated threads started on the same line
+        let file_discussions = discussions
+            .iter()
+            .filter(|discussion| discussion.file.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(file_discussions.len(), 1);
+        assert_eq!(file_discussions[0].comments.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn verdicts_are_fetched_for_pull_requests_of_the_user() {
+        let server = Gitea::serve().await.unwrap();
+        let mut other = fixture("gitea/pulls.json")[0].clone();
+        other["number"] = json!(2);
+        other["requested_reviewers"] = json!([]);
+        Mock::given(method("GET"))
+            .and(path("/api/v1/repos/acme/config/pulls"))
+            .and(query_param("page", "2"))
+            .respond_with(json(json!([other])))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let module = Gitea::connect(Some(&server), TOKEN).await.unwrap();
+
+        let reviews = module.get_reviews().await.unwrap();
+
+        let states = reviews
+            .iter()
+            .map(|review| (review.id.as_str(), review.state.clone()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(states["acme/config/1"], ReviewState::Approved);
+        assert_eq!(states["acme/config/2"], ReviewState::Pending);
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests
+            .iter()
+            .any(|request| request.url.path() == "/api/v1/repos/acme/config/pulls/2/reviews"));
+    }
+
+    #[tokio::test]
+    async fn replies_need_a_conversation() {
+        let server = Gitea::serve().await.unwrap();
+        let module = Gitea::connect(Some(&server), TOKEN).await.unwrap();
+
+        let unknown = module
+            .reply_to_discussion(Gitea::REVIEW_ID.to_string(), "999".into(), "Unknown".into())
+            .await;
+        let general = module
+            .reply_to_discussion(Gitea::REVIEW_ID.to_string(), "103".into(), "General".into())
+            .await;
+
+        let unknown = ApiError::from(unknown.unwrap_err());
+        assert!(matches!(unknown, ApiError::NotFound { .. }), "{unknown:?}");
+        let general = ApiError::from(general.unwrap_err());
+        assert!(matches!(general, ApiError::Provider { .. }), "{general:?}");
+        assert!(!received(&server, "Unknown").await && !received(&server, "General").await);
+    }
+}
+
+/// Gerrit reviews are Code-Review votes and its threads are chains of replies.
+mod gerrit_review {
+    use native::models::*;
+    use native::ReviewModule;
+
+    use super::support::gerrit::Gerrit;
+    use super::support::{StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn maps_code_review_votes_to_states() {
+        let server = Gerrit::serve().await;
+        let module = Gerrit::connect(server.as_ref(), TOKEN).await.unwrap();
+
+        let reviews = module.get_reviews().await.unwrap();
+
+        // a -1 outweighs a +1, a +2 approves
+        let states = reviews.iter().map(|review| review.state.clone()).collect::<Vec<_>>();
+        assert_eq!(states, vec![ReviewState::Rejected, ReviewState::Approved]);
+        // the owner is not a reviewer, neither is anyone in cc
+        assert_eq!(
+            reviews[0].reviewers.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(),
+            vec!["John Roe", "Mock User"]
+        );
+    }
+
+    #[tokio::test]
+    async fn builds_threads_from_replies() {
+        let server = Gerrit::serve().await;
+        let module = Gerrit::connect(server.as_ref(), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Gerrit::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        let thread = discussions
+            .iter()
+            .find(|discussion| discussion.file.is_some())
+            .expect("file discussion is missing");
+        let texts = thread.comments.iter().map(|comment| comment.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["Should this also accept hours?", "Good idea, added them."]);
+        // the reply resolved the thread
+        assert!(thread.resolved);
+        assert!(discussions.iter().any(|discussion| discussion.file.is_none()));
+    }
+}
+
+/// Gitlab approves merge requests, but has no state for requested changes.
+mod gitlab_review {
+    use native::api::ApiError;
+    use native::models::*;
+    use native::ReviewModule;
+    use wiremock::http::Method;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    use super::support::gitlab::Gitlab;
+    use super::support::{StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn requesting_changes_withdraws_the_approval() {
+        let server = Gitlab::serve().await.unwrap();
+        Mock::given(method("POST"))
+            .and(path("/api/v4/projects/7/merge_requests/1/unapprove"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        let module = Gitlab::connect(Some(&server), TOKEN).await.unwrap();
+
+        module
+            .submit_review_verdict(Gitlab::REVIEW_ID.to_string(), ReviewVerdict::RequestChanges, None)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let posted = requests
+            .iter()
+            .filter(|request| request.method == Method::Post)
+            .map(|request| request.url.path())
+            .collect::<Vec<_>>();
+        assert_eq!(posted, vec!["/api/v4/projects/7/merge_requests/1/unapprove"]);
+    }
+
+    #[tokio::test]
+    async fn comments_need_a_body() {
+        let server = Gitlab::serve().await.unwrap();
+        let module = Gitlab::connect(Some(&server), TOKEN).await.unwrap();
+
+        let result = module
+            .submit_review_verdict(Gitlab::REVIEW_ID.to_string(), ReviewVerdict::Comment, Some(String::new()))
+            .await;
+
+        let error = ApiError::from(result.unwrap_err());
+        assert!(matches!(error, ApiError::Provider { .. }), "{error:?}");
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|request| request.method != Method::Post));
+    }
+}
+
+/// Github only reports review threads, which carry the resolved state, over GraphQL.
+mod github_review {
+    use native::api::ApiError;
+    use native::ReviewModule;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::Mock;
+
+    use super::support::github::Github;
+    use super::support::{fixture, json, StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn pages_through_review_threads() {
+        let server = Github::serve().await.unwrap();
+        let second_page = fixture("github/graphql_get_review_threads.json");
+        let mut first_page = second_page.clone();
+        let threads = &mut first_page["data"]["repository"]["pullRequest"]["reviewThreads"];
+        threads["nodes"] = json!([]);
+        threads["pageInfo"] = json!({ "hasNextPage": true, "endCursor": "Y3Vyc29yOjA=" });
+        for (after, page) in [(json!(null), first_page), (json!("Y3Vyc29yOjA="), second_page)] {
+            Mock::given(method("POST"))
+                .and(path("/graphql"))
+                .and(body_partial_json(
+                    json!({ "operationName": "GetReviewThreads", "variables": { "after": after } }),
+                ))
+                .respond_with(json(page))
+                .with_priority(2)
+                .mount(&server)
+                .await;
+        }
+        let module = Github::connect(Some(&server), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Github::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        // the thread on the second page resolved the discussion
+        assert!(discussions.iter().any(|discussion| discussion.resolved));
+    }
+
+    #[tokio::test]
+    async fn diffs_of_an_older_revision_list_its_own_files() {
+        let server = Github::serve().await.unwrap();
+        let mut comparison = fixture("github/compare.json");
+        comparison["files"] = json!([]);
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/acme/config/compare/1111111111111111111111111111111111111111...4444444444444444444444444444444444444444",
+            ))
+            .respond_with(json(comparison))
+            .mount(&server)
+            .await;
+        let module = Github::connect(Some(&server), TOKEN).await.unwrap();
+
+        let error = module
+            .get_review_file_changes(
+                Github::REVIEW_ID.to_string(),
+                "src/duration.rs".into(),
+                "4444444444444444444444444444444444444444".into(),
+            )
+            .await
+            .unwrap_err();
+
+        // the file was only added after that revision
+        assert!(matches!(ApiError::from(error), ApiError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn diffs_of_one_revision_share_the_pull_request() {
+        let server = Github::serve().await.unwrap();
+        let module = Github::connect(Some(&server), TOKEN).await.unwrap();
+        let summaries = module
+            .get_review_file_summaries(Github::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        for summary in &summaries {
+            module
+                .get_review_file_changes(
+                    Github::REVIEW_ID.to_string(),
+                    summary.file_path.clone(),
+                    summary.revision_id.clone(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        let compare_requests = requests
+            .iter()
+            .filter(|request| request.url.path().starts_with("/repos/acme/config/compare/"))
+            .count();
+        assert!(summaries.len() > 1);
+        assert_eq!(compare_requests, 1);
+    }
+}
+
+/// The mock provider reads its fixtures from the file system.
+mod mock_review {
+    use native::api::ApiError;
+    use native::ReviewModule;
+
+    use super::support::mock::Mock;
+    use super::support::{StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn file_paths_stay_inside_the_fixtures() {
+        let module = Mock::connect(None, TOKEN).await.unwrap();
+
+        // points at the reviews.json of the fixtures
+        let result = module
+            .get_review_file_changes(Mock::REVIEW_ID.to_string(), "../../reviews.json".into(), String::new())
+            .await;
+
+        let error = ApiError::from(result.unwrap_err());
+        assert!(matches!(error, ApiError::NotFound { .. }), "{error:?}");
+    }
+}
+
+/// Upsource anchors discussions at text offsets, which need the contents of their files.
+mod upsource_review {
+    use native::models::DiffSide;
+    use native::ReviewModule;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    use super::support::upsource::Upsource;
+    use super::support::{fixture, json, StandIn, TOKEN};
+
+    #[tokio::test]
+    async fn discussions_on_the_base_revision_are_on_the_old_side() {
+        let server = Upsource::serve().await.unwrap();
+        let mut response = fixture("upsource/getReviewSummaryDiscussions.json");
+        let discussion = &mut response["result"]["discussions"][0];
+        discussion["fileName"] = json!("src/config.rs");
+        discussion["revisionId"] = json!("rev-1");
+        discussion["discussionInFile"]["anchor"]["fileId"] = json!("src/config.rs");
+        discussion["discussionInFile"]["anchor"]["revisionId"] = json!("rev-1");
+        Mock::given(method("POST"))
+            .and(path("/~rpc/getReviewSummaryDiscussions"))
+            .respond_with(json(response))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        let module = Upsource::connect(Some(&server), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Upsource::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        let file = discussions[0].file.as_ref().unwrap();
+        assert_eq!(file.revision.as_deref(), Some("rev-1"));
+        assert_eq!(file.side, DiffSide::Old);
+    }
+
+    #[tokio::test]
+    async fn missing_file_contents_leave_discussions_without_lines() {
+        let server = Upsource::serve().await.unwrap();
+        Mock::given(method("POST"))
+            .and(path("/~rpc/getFileContent"))
+            .respond_with(ResponseTemplate::new(404))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        let module = Upsource::connect(Some(&server), TOKEN).await.unwrap();
+
+        let discussions = module
+            .get_review_discussions(Upsource::REVIEW_ID.to_string())
+            .await
+            .unwrap();
+
+        assert!(!discussions.is_empty());
+        assert!(discussions
+            .iter()
+            .filter_map(|discussion| discussion.file.as_ref())
+            .all(|file| file.lines.is_none()));
+    }
+}
+
+/// `api::test_provider` creates the module from the settings, so only providers with a configurable url are covered.
+mod test_provider {
+    use native::api::{self, ApiError};
+    use native::models::*;
+
+    use super::support::gitlab::Gitlab;
+    use super::support::{StandIn, TOKEN};
+
+    fn gitlab_settings(url: String, token: &str) -> ProviderSettings {
+        ProviderSettings {
+            id: "gitlab".into(),
+            name: "Gitlab".into(),
+            module: ProviderModule::Gitlab(GitlabProviderSettings {
+                url,
+                token: token.into(),
+            })
+            .into(),
+            connection: None,
+        }
+    }
+
+    // `api` blocks on the shared runtime, so the stand-in runs on a runtime of its own
+    #[test]
+    fn reports_the_authenticated_user() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(Gitlab::serve()).unwrap();
+
+        let report = api::test_provider(gitlab_settings(server.uri(), TOKEN)).unwrap();
+
+        assert!(report.reachable && report.authenticated, "{report:?}");
+        assert_eq!(report.user.as_ref().map(|user| user.name.as_str()), Some("Mock User"));
+        assert!(report.missing_scopes.is_empty(), "{report:?}");
+        assert!(report.error.is_none(), "{report:?}");
+    }
+
+    #[test]
+    fn reports_invalid_tokens() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(Gitlab::serve()).unwrap();
+
+        let report = api::test_provider(gitlab_settings(server.uri(), "invalid-token")).unwrap();
+
+        assert!(report.reachable && !report.authenticated, "{report:?}");
+        assert!(matches!(report.error, Some(ApiError::Unauthorized { .. })), "{report:?}");
+    }
+
+    #[test]
+    fn reports_unreachable_providers() {
+        let report = api::test_provider(gitlab_settings("http://127.0.0.1:9".into(), TOKEN)).unwrap();
+
+        assert!(!report.reachable && !report.authenticated, "{report:?}");
+        assert!(report.user.is_none());
+    }
+}
+
+/// Providers which don't track read files keep them in the read state store.
+///
+/// This is the only test in here configuring the process wide modules.
+mod read_state {
+    use native::api::{self, ApiError};
+    use native::models::*;
+
+    use super::support::gitlab::Gitlab;
+    use super::support::{StandIn, TOKEN};
+
+    #[test]
+    fn read_files_are_kept_in_the_read_state_store() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(Gitlab::serve()).unwrap();
+        api::configure_modules(vec![ProviderSettings {
+            id: "gitlab".into(),
+            name: "Gitlab".into(),
+            module: ProviderModule::Gitlab(GitlabProviderSettings {
+                url: server.uri(),
+                token: TOKEN.into(),
+            })
+            .into(),
+            connection: None,
+        }])
+        .unwrap();
+        let is_read = || {
+            api::get_review_file_summaries("gitlab".into(), Gitlab::REVIEW_ID.into())
+                .unwrap()
+                .into_iter()
+                .find(|summary| summary.file_path == "src/config.rs")
+                .map(|summary| (summary.is_read, summary.revision_id))
+                .unwrap()
+        };
+        let mark_read = |revision: &str| {
+            api::mark_file_read("gitlab".into(), Gitlab::REVIEW_ID.into(), "src/config.rs".into(), revision.into(), true)
+        };
+
+        let (read, revision) = is_read();
+        assert!(!read);
+        // without the store there is nowhere to keep them
+        let error = mark_read(&revision).unwrap_err();
+        assert!(matches!(error, ApiError::InvalidConfig { .. }), "{error:?}");
+
+        let directory = std::env::temp_dir().join(format!("review-tool-read-state-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("read_state.db").to_string_lossy().into_owned();
+        api::open_read_state_store(path.clone()).unwrap();
+        mark_read(&revision).unwrap();
+        assert!(is_read().0);
+
+        // a new process opens the same file again
+        api::open_read_state_store(path).unwrap();
+        assert!(is_read().0);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
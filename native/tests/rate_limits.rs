@@ -0,0 +1,99 @@
+//! Modules follow the rate limits their provider reports.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use native::modules::upsource::UpsourceModule;
+use native::ReviewModule;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn reviews() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "result": { "reviews": [], "hasMore": false, "totalCount": 0 } }))
+}
+
+/// `getReviews` also looks up the participants of the reviews
+async fn serve_users(server: &MockServer, response: ResponseTemplate) {
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getUserInfo"))
+        .respond_with(response.set_body_json(json!({ "result": { "infos": [] } })))
+        .mount(server)
+        .await;
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+#[tokio::test]
+async fn reports_the_remaining_budget() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getReviews"))
+        .respond_with(
+            reviews()
+                .insert_header("X-RateLimit-Limit", "5000")
+                .insert_header("X-RateLimit-Remaining", "4999")
+                .insert_header("X-RateLimit-Reset", "1700000000"),
+        )
+        .mount(&server)
+        .await;
+    serve_users(&server, ResponseTemplate::new(200)).await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &Default::default());
+
+    assert!(module.rate_limit().remaining.is_none());
+    module.get_reviews().await.unwrap();
+
+    let rate_limit = module.rate_limit();
+    assert_eq!(rate_limit.limit, Some(5000));
+    assert_eq!(rate_limit.remaining, Some(4999));
+    assert_eq!(rate_limit.reset_at, Some(1700000000));
+}
+
+#[tokio::test]
+async fn waits_for_retry_after() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getReviews"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getReviews"))
+        .respond_with(reviews())
+        .mount(&server)
+        .await;
+    serve_users(&server, ResponseTemplate::new(200)).await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &Default::default());
+
+    assert!(module.get_reviews().await.is_err());
+    let started = Instant::now();
+    module.get_reviews().await.unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(500), "{:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn fails_fast_when_the_budget_is_exhausted_for_long() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/~rpc/getReviews"))
+        .respond_with(reviews())
+        .mount(&server)
+        .await;
+    serve_users(
+        &server,
+        ResponseTemplate::new(200)
+            .insert_header("X-RateLimit-Remaining", "0")
+            .insert_header("X-RateLimit-Reset", (unix_now() + 3600).to_string().as_str()),
+    )
+    .await;
+    let module = UpsourceModule::new(server.uri(), "token".into(), &Default::default());
+    module.get_reviews().await.unwrap();
+
+    let err = native::api::ApiError::from(module.get_reviews().await.unwrap_err());
+
+    assert!(matches!(err, native::api::ApiError::RateLimited { reset_at: Some(_), .. }), "{err:?}");
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
@@ -0,0 +1,64 @@
+//! Encrypting the provider tokens with a passphrase.
+//!
+//! The secrets are unlocked for the whole process, so everything runs in a single test.
+
+use native::api::{self, ApiError};
+use native::models::*;
+
+fn secrets_path(name: &str) -> String {
+    let directory = std::env::temp_dir().join(format!("review-tool-secrets-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    directory.join(name).to_string_lossy().into_owned()
+}
+
+fn github_token(providers: &[ProviderSettings]) -> &str {
+    match &*providers[0].module {
+        ProviderModule::Github(github) => &github.token,
+        module => panic!("Unexpected module {module:?}"),
+    }
+}
+
+#[test]
+fn tokens_are_encrypted_with_the_passphrase() {
+    let settings = secrets_path("providers.json");
+    let secrets = secrets_path("secrets.json");
+    let providers = vec![ProviderSettings {
+        id: "github".into(),
+        name: "GitHub".into(),
+        module: ProviderModule::Github(GithubProviderSettings {
+            token: "very-secret-token".into(),
+            query: "is:pr".into(),
+        })
+        .into(),
+        connection: None,
+    }];
+
+    api::unlock_secrets(secrets.clone(), "passphrase".into()).unwrap();
+    api::save_provider_settings(settings.clone(), providers).unwrap();
+
+    for path in [&settings, &secrets] {
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(!content.contains("very-secret-token"), "{content}");
+    }
+    assert_eq!(github_token(&api::load_provider_settings(settings.clone()).unwrap()), "very-secret-token");
+
+    api::lock_secrets().unwrap();
+    let locked = api::load_provider_settings(settings.clone()).unwrap();
+    assert_eq!(github_token(&locked), "");
+
+    // saving the empty tokens would lose the encrypted ones
+    let err = api::save_provider_settings(settings.clone(), locked).unwrap_err();
+    assert!(matches!(err, ApiError::InvalidConfig { .. }), "{err:?}");
+
+    let err = api::unlock_secrets(secrets.clone(), "wrong".into()).unwrap_err();
+    assert!(matches!(err, ApiError::Unauthorized { .. }), "{err:?}");
+
+    let locked = api::load_provider_settings(settings.clone()).unwrap();
+    api::unlock_secrets(secrets, "passphrase".into()).unwrap();
+    assert_eq!(github_token(&api::load_provider_settings(settings.clone()).unwrap()), "very-secret-token");
+
+    // empty tokens of settings loaded while locked keep their secret
+    api::save_provider_settings(settings.clone(), locked).unwrap();
+    assert_eq!(github_token(&api::load_provider_settings(settings).unwrap()), "very-secret-token");
+}
@@ -0,0 +1,93 @@
+//! Loading and saving the provider settings file.
+
+use std::path::{Path, PathBuf};
+
+use native::api::{self, ApiError};
+use native::models::*;
+
+fn settings_path(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("review-tool-settings-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    directory.join(name)
+}
+
+fn load(path: &Path) -> Result<Vec<ProviderSettings>, ApiError> {
+    api::load_provider_settings(path.to_string_lossy().into_owned())
+}
+
+#[test]
+fn missing_file_has_no_providers() {
+    let providers = load(&settings_path("missing.json")).unwrap();
+
+    assert!(providers.is_empty());
+}
+
+#[test]
+fn saved_providers_are_loaded() {
+    let path = settings_path("saved.json");
+    let providers = vec![ProviderSettings {
+        id: "work".into(),
+        name: "Work".into(),
+        module: ProviderModule::Gitlab(GitlabProviderSettings {
+            url: "https://gitlab.example.com".into(),
+            token: "".into(),
+        })
+        .into(),
+        connection: None,
+    }];
+
+    api::save_provider_settings(path.to_string_lossy().into_owned(), providers).unwrap();
+    let providers = load(&path).unwrap();
+
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].id, "work");
+    assert!(matches!(&*providers[0].module, ProviderModule::Gitlab(gitlab) if gitlab.url == "https://gitlab.example.com"));
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains(r#""version": 1"#), "{content}");
+}
+
+#[test]
+fn tokens_need_unlocked_secrets() {
+    let path = settings_path("plaintext.json");
+    let providers = vec![ProviderSettings {
+        id: "work".into(),
+        name: "Work".into(),
+        module: ProviderModule::Gitlab(GitlabProviderSettings {
+            url: "https://gitlab.example.com".into(),
+            token: "token".into(),
+        })
+        .into(),
+        connection: None,
+    }];
+
+    let err = api::save_provider_settings(path.to_string_lossy().into_owned(), providers).unwrap_err();
+
+    assert!(matches!(err, ApiError::InvalidConfig { .. }), "{err:?}");
+    assert!(!path.exists());
+}
+
+#[test]
+fn unversioned_files_are_migrated() {
+    let path = settings_path("unversioned.json");
+    std::fs::write(
+        &path,
+        r#"{ "providers": [{ "id": "mock", "name": "Mock", "module": { "type": "mock", "path": "fixtures/mock" } }] }"#,
+    )
+    .unwrap();
+
+    let providers = load(&path).unwrap();
+
+    assert_eq!(providers.len(), 1);
+    assert!(matches!(&*providers[0].module, ProviderModule::Mock(mock) if mock.path == "fixtures/mock"));
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let path = settings_path("newer.json");
+    std::fs::write(&path, r#"{ "version": 100, "providers": [] }"#).unwrap();
+
+    let err = load(&path).unwrap_err();
+
+    assert!(matches!(err, ApiError::InvalidConfig { .. }), "{err:?}");
+}
@@ -0,0 +1,70 @@
+use native::modules::bitbucket::BitbucketModule;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::{fixture, json, mock_file, require_authorization, StandIn};
+
+const REPOSITORY: &str = "/rest/api/1.0/projects/ACME/repos/config";
+const PULL_REQUEST: &str = "/rest/api/1.0/projects/ACME/repos/config/pull-requests/1";
+const MERGE_BASE_SHA: &str = "3333333333333333333333333333333333333333";
+const HEAD_SHA: &str = "2222222222222222222222222222222222222222";
+
+pub struct Bitbucket;
+
+impl StandIn for Bitbucket {
+    type Module = BitbucketModule;
+
+    const REVIEW_ID: &'static str = "ACME/config/1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        require_authorization(&server, "Authorization", vec![format!("Bearer {}", super::TOKEN)]).await;
+        // the only way to learn who the token belongs to
+        Mock::given(method("GET"))
+            .and(path("/rest/api/1.0/inbox/pull-requests/count"))
+            .respond_with(json(serde_json::json!({ "count": 1 })).insert_header("X-AUSERNAME", "mock"))
+            .mount(&server)
+            .await;
+        let responses = [
+            ("/rest/api/1.0/users", "users"),
+            ("/rest/api/1.0/inbox/pull-requests", "inbox"),
+            (PULL_REQUEST, "pull_request"),
+            (&format!("{PULL_REQUEST}/activities"), "activities"),
+            (&format!("{PULL_REQUEST}/changes"), "changes"),
+            (&format!("{PULL_REQUEST}/diff"), "diff"),
+        ];
+        for (endpoint, name) in responses {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(json(fixture(&format!("bitbucket/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        let files = [
+            ("src/config.rs", MERGE_BASE_SHA, "base/src/config.rs"),
+            ("src/config.rs", HEAD_SHA, "head/src/config.rs"),
+            ("src/duration.rs", HEAD_SHA, "head/src/duration.rs"),
+        ];
+        for (file_path, revision, content) in files {
+            Mock::given(method("GET"))
+                .and(path(format!("{REPOSITORY}/raw/{file_path}")))
+                .and(query_param("at", revision))
+                .respond_with(ResponseTemplate::new(200).set_body_string(mock_file(content)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path(format!("{PULL_REQUEST}/comments")))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<BitbucketModule> {
+        let url = server.expect("Bitbucket needs a server").uri();
+
+        BitbucketModule::new(url, token.to_string(), &Default::default())
+    }
+}
@@ -0,0 +1,175 @@
+//! Behaviour every module has to show against its stand-in.
+
+use native::api::ApiError;
+use native::models::*;
+use native::ReviewModule;
+
+use super::{received, StandIn, REVIEW_TITLE, TOKEN};
+
+async fn connect<S: StandIn>() -> (Option<wiremock::MockServer>, S::Module) {
+    let server = S::serve().await;
+    let module = S::connect(server.as_ref(), TOKEN).await.unwrap();
+
+    (server, module)
+}
+
+async fn file_summary<S: StandIn>(module: &S::Module, file_path: &str) -> ReviewFileSummary {
+    module
+        .get_review_file_summaries(S::REVIEW_ID.to_string())
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|summary| summary.file_path == file_path)
+        .unwrap_or_else(|| panic!("{file_path} is missing"))
+}
+
+pub async fn lists_reviews<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+
+    let reviews = module.get_reviews().await.unwrap();
+
+    let review = reviews
+        .iter()
+        .find(|review| review.id == S::REVIEW_ID)
+        .expect("review is missing");
+    assert_eq!(review.title, REVIEW_TITLE);
+    assert!(review.open);
+    assert_eq!(
+        review.authors.iter().map(|user| user.name.as_str()).collect::<Vec<_>>(),
+        vec!["Jane Doe"]
+    );
+}
+
+pub async fn lists_discussions<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+
+    let discussions = module
+        .get_review_discussions(S::REVIEW_ID.to_string())
+        .await
+        .unwrap();
+
+    let discussion = discussions
+        .iter()
+        .find(|discussion| {
+            discussion
+                .comments
+                .iter()
+                .any(|comment| comment.text == "Should this also accept hours?")
+        })
+        .expect("discussion is missing");
+    let file = discussion.file.as_ref().expect("discussion is not anchored");
+    assert_eq!(file.file_path, "src/duration.rs");
+    assert_eq!(file.file_name, "duration.rs");
+    assert_eq!(file.lines.as_ref().map(|lines| lines.start), Some(4));
+}
+
+pub async fn lists_file_summaries<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+
+    let config = file_summary::<S>(&module, "src/config.rs").await;
+    let duration = file_summary::<S>(&module, "src/duration.rs").await;
+
+    assert_eq!(config.file_name, "config.rs");
+    assert_eq!(config.file_path_segments, vec!["src"]);
+    assert!(matches!(config.change_type, ChangeType::Modified));
+    assert!(matches!(duration.change_type, ChangeType::Added));
+    assert!(!config.revision_id.is_empty());
+}
+
+pub async fn diffs_files<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+    let summary = file_summary::<S>(&module, "src/config.rs").await;
+
+    let changes = module
+        .get_review_file_changes(
+            S::REVIEW_ID.to_string(),
+            summary.file_path,
+            summary.revision_id,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(changes.old_path.as_deref(), Some("src/config.rs"));
+    assert_eq!(changes.new_path.as_deref(), Some("src/config.rs"));
+    let lines = changes
+        .hunks
+        .iter()
+        .flat_map(|hunk| hunk.lines.iter())
+        .collect::<Vec<_>>();
+    assert!(lines.iter().any(|line| {
+        matches!(line.line_type, DiffLineType::Added) && line.text == "use std::time::Duration;"
+    }));
+    assert!(lines.iter().any(|line| {
+        matches!(line.line_type, DiffLineType::Removed) && line.text == "    pub timeout: u64,"
+    }));
+}
+
+pub async fn creates_review_discussions<S: StandIn>() {
+    let (server, module) = connect::<S>().await;
+    let text = "Please add a changelog entry.";
+
+    module
+        .create_review_discussion(S::REVIEW_ID.to_string(), text.to_string())
+        .await
+        .unwrap();
+
+    match server {
+        Some(server) => assert!(received(&server, text).await, "discussion was not sent"),
+        None => {
+            let discussions = module
+                .get_review_discussions(S::REVIEW_ID.to_string())
+                .await
+                .unwrap();
+            assert!(discussions
+                .iter()
+                .flat_map(|discussion| discussion.comments.iter())
+                .any(|comment| comment.text == text));
+        }
+    }
+}
+
+pub async fn marks_files_read<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+    let summary = file_summary::<S>(&module, "src/config.rs").await;
+
+    let result = module
+        .mark_file_read(
+            S::REVIEW_ID.to_string(),
+            summary.file_path,
+            summary.revision_id,
+            true,
+        )
+        .await;
+
+    // modules which don't track read files leave them to the local store
+    assert_eq!(result.is_ok(), module.tracks_read_state(), "{result:?}");
+}
+
+pub async fn authenticates<S: StandIn>() {
+    let (_server, module) = connect::<S>().await;
+
+    let authentication = module.get_authentication().await.unwrap();
+
+    assert_eq!(authentication.user.name, "Mock User");
+    if let Some(scopes) = authentication.scopes {
+        for required in module.required_scopes() {
+            assert!(scopes.iter().any(|scope| scope == required), "{required} is missing in {scopes:?}");
+        }
+    }
+}
+
+pub async fn rejects_invalid_tokens<S: StandIn>() {
+    let Some(server) = S::serve().await else {
+        // nothing to authenticate against
+        return;
+    };
+
+    // modules checking the connection when they are created fail right away
+    let result = match S::connect(Some(&server), "invalid-token").await {
+        Ok(module) => module.get_reviews().await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+
+    let err = ApiError::from(result.expect_err("invalid token was accepted"));
+    assert!(matches!(err, ApiError::Unauthorized { .. }), "{err:?}");
+}
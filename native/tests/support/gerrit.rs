@@ -0,0 +1,89 @@
+use base64::prelude::*;
+use native::modules::gerrit::GerritModule;
+use serde_json::Value;
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::{fixture, mock_file, require_authorization, StandIn};
+
+const USERNAME: &str = "mock";
+const CHANGE: &str = "/a/changes/acme%2Fconfig~1";
+const PATCH_SET: &str = "/a/changes/acme%2Fconfig~1/revisions/2";
+
+pub struct Gerrit;
+
+/// Json as Gerrit sends it, behind a prefix which breaks plain json parsers.
+fn gerrit_json(value: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(format!(")]}}'\n{value}"), "application/json")
+}
+
+impl StandIn for Gerrit {
+    type Module = GerritModule;
+
+    const REVIEW_ID: &'static str = "acme/config/1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        let credentials = BASE64_STANDARD.encode(format!("{USERNAME}:{}", super::TOKEN));
+        require_authorization(&server, "Authorization", vec![format!("Basic {credentials}")]).await;
+        let responses = [
+            ("/a/accounts/self", "account"),
+            ("/a/changes/", "changes"),
+            (CHANGE, "change"),
+            (&format!("{CHANGE}/comments"), "comments"),
+        ];
+        for (endpoint, name) in responses {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(gerrit_json(fixture(&format!("gerrit/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path(format!("{PATCH_SET}/files")))
+            .and(query_param_is_missing("reviewed"))
+            .respond_with(gerrit_json(fixture("gerrit/files.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{PATCH_SET}/files")))
+            .and(query_param("reviewed", ""))
+            .respond_with(gerrit_json(fixture("gerrit/reviewed.json")))
+            .mount(&server)
+            .await;
+        let files = [
+            ("src%2Fconfig.rs", true, "base/src/config.rs"),
+            ("src%2Fconfig.rs", false, "head/src/config.rs"),
+            ("src%2Fduration.rs", false, "head/src/duration.rs"),
+        ];
+        for (file_path, parent, content) in files {
+            let endpoint = path(format!("{PATCH_SET}/files/{file_path}/content"));
+            let mock = match parent {
+                true => Mock::given(method("GET")).and(endpoint).and(query_param("parent", "1")),
+                false => Mock::given(method("GET")).and(endpoint).and(query_param_is_missing("parent")),
+            };
+            // file contents come as base64 text
+            mock.respond_with(ResponseTemplate::new(200).set_body_string(BASE64_STANDARD.encode(mock_file(content))))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("PUT"))
+            .and(path(format!("{PATCH_SET}/files/src%2Fconfig.rs/reviewed")))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("{CHANGE}/revisions/current/review")))
+            .respond_with(gerrit_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<GerritModule> {
+        let url = server.expect("Gerrit needs a server").uri();
+
+        GerritModule::new(url, USERNAME.to_string(), token.to_string(), &Default::default())
+    }
+}
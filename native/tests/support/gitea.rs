@@ -0,0 +1,72 @@
+use native::modules::gitea::GiteaModule;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+use super::{fixture, json, mock_file, require_authorization, StandIn};
+
+const REPOSITORY: &str = "/api/v1/repos/acme/config";
+const MERGE_BASE_SHA: &str = "3333333333333333333333333333333333333333";
+const HEAD_SHA: &str = "2222222222222222222222222222222222222222";
+
+pub struct Gitea;
+
+impl StandIn for Gitea {
+    type Module = GiteaModule;
+
+    const REVIEW_ID: &'static str = "acme/config/1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        require_authorization(&server, "Authorization", vec![format!("token {}", super::TOKEN)]).await;
+        let responses = [
+            ("/api/v1/user", "user"),
+            ("/api/v1/user/repos", "repos"),
+            (&format!("{REPOSITORY}/pulls"), "pulls"),
+            (&format!("{REPOSITORY}/pulls/1"), "pull"),
+            (&format!("{REPOSITORY}/pulls/1/reviews"), "reviews"),
+            (&format!("{REPOSITORY}/pulls/1/reviews/5/comments"), "review_comments"),
+            (&format!("{REPOSITORY}/pulls/1/files"), "files"),
+            (&format!("{REPOSITORY}/issues/1/comments"), "issue_comments"),
+        ];
+        for (endpoint, name) in responses {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(json(fixture(&format!("gitea/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        // every list fits on the first page
+        Mock::given(method("GET"))
+            .and(|request: &Request| request.url.query_pairs().any(|(key, value)| key == "page" && value != "1"))
+            .respond_with(json(serde_json::json!([])))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        let files = [
+            ("src/config.rs", MERGE_BASE_SHA, "base/src/config.rs"),
+            ("src/config.rs", HEAD_SHA, "head/src/config.rs"),
+            ("src/duration.rs", HEAD_SHA, "head/src/duration.rs"),
+        ];
+        for (file_path, revision, content) in files {
+            Mock::given(method("GET"))
+                .and(path(format!("{REPOSITORY}/raw/{file_path}")))
+                .and(query_param("ref", revision))
+                .respond_with(ResponseTemplate::new(200).set_body_string(mock_file(content)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path(format!("{REPOSITORY}/issues/1/comments")))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<GiteaModule> {
+        let url = server.expect("Gitea needs a server").uri();
+
+        GiteaModule::new(url, token.to_string(), &Default::default())
+    }
+}
@@ -0,0 +1,120 @@
+use base64::prelude::*;
+use native::modules::github::GithubModule;
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, method, path, path_regex, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::{fixture, json, mock_file, require_authorization, StandIn};
+
+const PULL_REQUEST: &str = "/repos/acme/config/pulls/1";
+const MERGE_BASE_SHA: &str = "3333333333333333333333333333333333333333";
+const HEAD_SHA: &str = "2222222222222222222222222222222222222222";
+
+pub struct Github;
+
+impl StandIn for Github {
+    type Module = GithubModule;
+
+    const REVIEW_ID: &'static str = "acme/config/1/PR_kwDOA1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        // the REST client and the GraphQL client authenticate differently
+        require_authorization(
+            &server,
+            "Authorization",
+            vec![format!("token {}", super::TOKEN), format!("Bearer {}", super::TOKEN)],
+        )
+        .await;
+        let operations = [
+            ("GetReviews", "get_reviews"),
+            ("GetReviewThreads", "get_review_threads"),
+            ("GetReviewFileSummaries", "get_review_file_summaries"),
+            ("MarkFileAsViewed", "mark_file_as_viewed"),
+        ];
+        for (operation, name) in operations {
+            Mock::given(method("POST"))
+                .and(path("/graphql"))
+                .and(body_partial_json(json!({ "operationName": operation })))
+                .respond_with(json(fixture(&format!("github/graphql_{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        // classic tokens list their scopes on every response
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(json!({ "operationName": "GetViewer" })))
+            .respond_with(
+                json(fixture("github/graphql_get_viewer.json")).insert_header("x-oauth-scopes", "read:org, repo"),
+            )
+            .mount(&server)
+            .await;
+        let responses = [
+            ("/users/jane", "user_jane"),
+            ("/users/john", "user_john"),
+            ("/users/mock", "user_mock"),
+            (PULL_REQUEST, "pull_request"),
+            (&format!("{PULL_REQUEST}/comments"), "review_comments"),
+            (&format!("{PULL_REQUEST}/files"), "files"),
+            (
+                &format!("/repos/acme/config/compare/1111111111111111111111111111111111111111...{HEAD_SHA}"),
+                "compare",
+            ),
+        ];
+        for (endpoint, name) in responses {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(json(fixture(&format!("github/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        let files = [
+            ("config", MERGE_BASE_SHA, "base/src/config.rs"),
+            ("config", HEAD_SHA, "head/src/config.rs"),
+            ("duration", HEAD_SHA, "head/src/duration.rs"),
+        ];
+        for (name, revision, content) in files {
+            Mock::given(method("GET"))
+                .and(path_regex(format!(r"^/repos/acme/config/contents/src(/|%2F){name}\.rs$")))
+                .and(query_param("ref", revision))
+                .respond_with(json(content_file(content)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path("/repos/acme/config/issues/1/comments"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(fixture("github/issue_comment.json")))
+            .mount(&server)
+            .await;
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<GithubModule> {
+        let url = server.expect("Github needs a server").uri();
+
+        GithubModule::with_api_url(token.to_string(), "is:pr is:open".to_string(), url, &Default::default())
+    }
+}
+
+/// Response of the contents api, which splits the base64 encoded content into lines.
+fn content_file(path: &str) -> serde_json::Value {
+    let content = BASE64_STANDARD.encode(mock_file(path));
+    let content = content
+        .as_bytes()
+        .chunks(60)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    json!({
+        "type": "file",
+        "encoding": "base64",
+        "size": content.len(),
+        "name": path.rsplit('/').next(),
+        "path": path,
+        "content": content,
+        "sha": "4444444444444444444444444444444444444444",
+        "_links": {},
+    })
+}
@@ -0,0 +1,63 @@
+use native::modules::gitlab::GitlabModule;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use super::{fixture, json, mock_file, require_authorization, StandIn};
+
+const MERGE_REQUEST: &str = "/api/v4/projects/7/merge_requests/1";
+const BASE_SHA: &str = "1111111111111111111111111111111111111111";
+const HEAD_SHA: &str = "2222222222222222222222222222222222222222";
+
+pub struct Gitlab;
+
+impl StandIn for Gitlab {
+    type Module = GitlabModule;
+
+    const REVIEW_ID: &'static str = "7/1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        require_authorization(&server, "PRIVATE-TOKEN", vec![super::TOKEN.to_string()]).await;
+        let responses = [
+            ("/api/v4/user", "user"),
+            ("/api/v4/personal_access_tokens/self", "personal_access_token"),
+            ("/api/v4/projects", "projects"),
+            ("/api/v4/projects/7/merge_requests", "merge_requests"),
+            (&format!("{MERGE_REQUEST}/discussions"), "discussions"),
+            (&format!("{MERGE_REQUEST}/changes"), "changes"),
+        ];
+        for (endpoint, name) in responses {
+            Mock::given(method("GET"))
+                .and(path(endpoint))
+                .respond_with(json(fixture(&format!("gitlab/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+        let files = [
+            ("src%2Fconfig.rs", BASE_SHA, "base/src/config.rs"),
+            ("src%2Fconfig.rs", HEAD_SHA, "head/src/config.rs"),
+            ("src%2Fduration.rs", HEAD_SHA, "head/src/duration.rs"),
+        ];
+        for (file_path, revision, content) in files {
+            Mock::given(method("GET"))
+                .and(path(format!("/api/v4/projects/7/repository/files/{file_path}/raw")))
+                .and(query_param("ref", revision))
+                .respond_with(ResponseTemplate::new(200).set_body_string(mock_file(content)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path(format!("{MERGE_REQUEST}/discussions")))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<GitlabModule> {
+        let url = server.expect("Gitlab needs a server").uri();
+
+        GitlabModule::new(url, token.to_string(), &Default::default()).await
+    }
+}
@@ -0,0 +1,20 @@
+use native::modules::mock::MockModule;
+use wiremock::MockServer;
+
+use super::StandIn;
+
+pub struct Mock;
+
+impl StandIn for Mock {
+    type Module = MockModule;
+
+    const REVIEW_ID: &'static str = "1";
+
+    async fn serve() -> Option<MockServer> {
+        None
+    }
+
+    async fn connect(_server: Option<&MockServer>, _token: &str) -> anyhow::Result<MockModule> {
+        MockModule::new(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/mock").to_string())
+    }
+}
@@ -0,0 +1,91 @@
+//! Local stand-ins for the provider apis, all serving the same review so every module
+//! can run through the same contract.
+
+use std::path::PathBuf;
+
+use native::ReviewModule;
+use serde_json::Value;
+use wiremock::http::HeaderName;
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+pub mod bitbucket;
+pub mod contract;
+pub mod gerrit;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod mock;
+pub mod upsource;
+
+pub const TOKEN: &str = "contract-token";
+
+pub const REVIEW_TITLE: &str = "Parse durations in the config file";
+
+pub trait StandIn {
+    type Module: ReviewModule;
+
+    /// Id of the review titled [`REVIEW_TITLE`]
+    const REVIEW_ID: &'static str;
+
+    /// Starts the server, `None` for providers which don't talk to one.
+    async fn serve() -> Option<MockServer>;
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<Self::Module>;
+}
+
+pub fn fixture(path: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path);
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Reading {}: {err}", path.display()));
+
+    serde_json::from_str(&content).unwrap()
+}
+
+/// Content of a file of the mock provider fixture, shared by the stand-ins serving raw files.
+pub fn mock_file(path: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/mock/1")
+        .join(path);
+
+    std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("Reading {}: {err}", path.display()))
+}
+
+pub fn json(value: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(value)
+}
+
+/// Answers every request without one of the `accepted` authorization values with `401`.
+pub async fn require_authorization(server: &MockServer, header: &'static str, accepted: Vec<String>) {
+    Mock::given(move |request: &Request| {
+        let value = request
+            .headers
+            .get(&HeaderName::from(header))
+            .map(|values| values.last().as_str());
+        !value.is_some_and(|value| accepted.iter().any(|accepted| accepted == value))
+    })
+    .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+        "message": "401 Unauthorized"
+    })))
+    .with_priority(1)
+    .mount(server)
+    .await;
+}
+
+/// Whether any request received by the server contains `text` in its body.
+pub async fn received(server: &MockServer, text: &str) -> bool {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .any(|request| {
+            let body = String::from_utf8_lossy(&request.body);
+            body.contains(text) || body.contains(&form_urlencoded(text))
+        })
+}
+
+fn form_urlencoded(text: &str) -> String {
+    url::form_urlencoded::byte_serialize(text.as_bytes()).collect()
+}
@@ -0,0 +1,46 @@
+use native::modules::upsource::UpsourceModule;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer};
+
+use super::{fixture, json, require_authorization, StandIn};
+
+/// Rpc methods answered by the stand-in, each with the response in `fixtures/upsource/<method>.json`
+const METHODS: &[&str] = &[
+    "getReviews",
+    "getUserInfo",
+    "getCurrentUser",
+    "getReviewSummaryDiscussions",
+    "getFileContent",
+    "getReviewSummaryChanges",
+    "getFileInReviewSummaryInlineChanges",
+    "createDiscussion",
+    "setFileInReviewReadStatus",
+];
+
+pub struct Upsource;
+
+impl StandIn for Upsource {
+    type Module = UpsourceModule;
+
+    const REVIEW_ID: &'static str = "config/CFG-CR-1";
+
+    async fn serve() -> Option<MockServer> {
+        let server = MockServer::start().await;
+        require_authorization(&server, "Authorization", vec![format!("Bearer {}", super::TOKEN)]).await;
+        for name in METHODS {
+            Mock::given(method("POST"))
+                .and(path(format!("/~rpc/{name}")))
+                .respond_with(json(fixture(&format!("upsource/{name}.json"))))
+                .mount(&server)
+                .await;
+        }
+
+        Some(server)
+    }
+
+    async fn connect(server: Option<&MockServer>, token: &str) -> anyhow::Result<UpsourceModule> {
+        let url = server.expect("Upsource needs a server").uri();
+
+        Ok(UpsourceModule::new(url, token.to_string(), &Default::default()))
+    }
+}